@@ -1,7 +1,28 @@
+use clifford::decoder::mwpm::Mwpm;
 use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
 use indicatif::ProgressBar;
+use std::time::Instant;
+
+/// syndrome測定を含む1 shotのシミュレーションにかかる時間 (decodeは含まない)
+fn simulation_time(distance: usize, shots: usize) {
+    let mut code = RotatedSurfaceCode::new(distance, distance, 0.01, 0.01, 0);
+    code.initialize();
+    code.syndrome_measurement();
+
+    let start = Instant::now();
+    for _ in 0..shots {
+        code.reset();
+        code.run();
+    }
+    let elapsed = start.elapsed().as_secs_f64() * 1e3 / shots as f64;
+    println!("d = {}: {:.3} ms/shot", distance, elapsed);
+}
 
 fn main() {
+    for d in [9, 11, 15] {
+        simulation_time(d, 100);
+    }
+
     let loop_num = 10000;
     let distance = [3, 5, 7, 9, 11];
     let error_rate = [0.01, 0.02, 0.03, 0.04, 0.05, 0.06, 0.07, 0.08];
@@ -17,6 +38,7 @@ fn main() {
             println!("Progress {}/{}", count, loop_time);
 
             let mut code = RotatedSurfaceCode::new(d, d, p, p, seed);
+            let mut decoder = Mwpm::new(d);
 
             code.initialize();
             code.syndrome_measurement();
@@ -28,7 +50,7 @@ fn main() {
             for _ in 0..loop_num {
                 code.reset();
                 code.run();
-                code.decode_mwpm(d);
+                code.decode_with(&mut decoder);
 
                 let ans = code.logical_value();
 
@@ -11,7 +11,6 @@ fn main() {
     code.syndrome_measurement();
 
     let mut error_num = 0;
-    let mut abnormal = 0;
 
     for i in 0..loop_num {
         code.reset();
@@ -24,9 +23,6 @@ fn main() {
             error_num += 1;
         }
 
-        if ans == u8::MAX {
-            abnormal += 1;
-        }
         println!("ans = {}, loop {}", ans, i);
         if ans != 0 {
             println!("{}", "#########################################################################################\nerror\n#########################################################################################".red());
@@ -35,10 +31,6 @@ fn main() {
         println!("");
     }
 
-    println!("abnormal: {}", abnormal);
     println!("error_num: {}", error_num);
-    println!(
-        "error rate: {}",
-        (error_num + abnormal) as f32 / loop_num as f32
-    );
+    println!("error rate: {}", error_num as f32 / loop_num as f32);
 }
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::simulator::Operation;
+
+pub mod qasm;
+pub mod stim;
+
+/// 回路 (オペレーションの列)
+///
+/// シミュレータとは独立に組み立て, SimulatorInterface::add_circuitでどのbackendにも渡せる
+#[derive(Clone, Debug, Default)]
+pub struct Circuit {
+    operations: Vec<Operation>,
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Circuit {
+            operations: Vec::new(),
+        }
+    }
+
+    /// オペレーションを追加する
+    pub fn push(&mut self, operation: Operation) {
+        self.operations.push(operation);
+    }
+
+    /// 回路の後ろにotherを繋げる
+    pub fn append(&mut self, other: &Circuit) {
+        self.operations.extend(other.operations.iter().cloned());
+    }
+
+    /// operations in range as a new circuit
+    pub fn slice(&self, range: Range<usize>) -> Circuit {
+        Circuit {
+            operations: self.operations[range].to_vec(),
+        }
+    }
+
+    /// 回路をn回繰り返した回路 (展開する. 展開しない場合はOperation::Repeatを使う)
+    pub fn repeat(&self, n: usize) -> Circuit {
+        (0..n)
+            .flat_map(|_| self.operations.iter().cloned())
+            .collect()
+    }
+
+    /// REPEATブロックを全て展開した回路
+    pub fn flattened(&self) -> Circuit {
+        let mut circuit = Circuit::new();
+        for op in self.operations.iter() {
+            match op {
+                Operation::Repeat(n, body) => circuit.append(&body.flattened().repeat(*n)),
+                op => circuit.push(op.clone()),
+            }
+        }
+        circuit
+    }
+
+    /// start以降のオペレーションを置き換える
+    pub fn replace_from(&mut self, start: usize, operations: Vec<Operation>) {
+        self.operations.truncate(start);
+        self.operations.extend(operations);
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Operation> {
+        self.operations.iter()
+    }
+
+    /// number of operations
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// 作用するqubitの最大index + 1
+    pub fn qubit_count(&self) -> usize {
+        self.operations
+            .iter()
+            .flat_map(|op| op.qubits())
+            .max()
+            .map_or(0, |a| a + 1)
+    }
+
+    /// number of measurement outcomes recorded by the circuit
+    pub fn measurement_count(&self) -> usize {
+        self.count(&|op| op.is_measurement())
+    }
+
+    /// number of detectors
+    pub fn detector_count(&self) -> usize {
+        self.count(&|op| matches!(op, Operation::Detector(_, _)))
+    }
+
+    /// REPEATブロックを繰り返し回数分数えたときのfを満たすオペレーションの数
+    fn count(&self, f: &dyn Fn(&Operation) -> bool) -> usize {
+        self.operations
+            .iter()
+            .map(|op| match op {
+                Operation::Repeat(n, body) => n * body.count(f),
+                op => f(op) as usize,
+            })
+            .sum()
+    }
+
+    /// 参照されるlogical observableのindexの最大値 + 1
+    pub fn observable_count(&self) -> usize {
+        self.operations
+            .iter()
+            .filter_map(|op| match op {
+                Operation::ObservableInclude(observable, _) => Some(observable + 1),
+                Operation::Repeat(_, body) => Some(body.observable_count()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// オペレーションの名前ごとの個数 (REPEATブロックの中身は繰り返し回数分数える)
+    pub fn operation_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for op in self.operations.iter() {
+            match op {
+                Operation::Repeat(n, body) => {
+                    for (name, count) in body.operation_counts() {
+                        *counts.entry(name).or_insert(0) += n * count;
+                    }
+                }
+                op => *counts.entry(op.name()).or_insert(0) += 1,
+            }
+        }
+        counts
+    }
+
+    /// 回路の深さ (ノイズと注釈を除き, 同じqubitに作用しないオペレーションは同じ層に並べる)
+    pub fn depth(&self) -> usize {
+        let mut layer = vec![0; self.qubit_count()];
+        self.stack_layers(&mut layer);
+        layer.into_iter().max().unwrap_or(0)
+    }
+
+    /// 各qubitの層の高さにオペレーションを積む
+    fn stack_layers(&self, layer: &mut [usize]) {
+        for op in self.operations.iter().filter(|op| !op.is_noise()) {
+            if let Operation::Repeat(n, body) = op {
+                for _ in 0..*n {
+                    body.stack_layers(layer);
+                }
+                continue;
+            }
+            let qubits = op.qubits();
+            let Some(l) = qubits.iter().map(|&a| layer[a]).max() else {
+                continue;
+            };
+            for a in qubits {
+                layer[a] = l + 1;
+            }
+        }
+    }
+}
+
+impl From<Vec<Operation>> for Circuit {
+    fn from(operations: Vec<Operation>) -> Self {
+        Circuit { operations }
+    }
+}
+
+impl FromIterator<Operation> for Circuit {
+    fn from_iter<I: IntoIterator<Item = Operation>>(iter: I) -> Self {
+        Circuit {
+            operations: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Operation> for Circuit {
+    fn extend<I: IntoIterator<Item = Operation>>(&mut self, iter: I) {
+        self.operations.extend(iter);
+    }
+}
+
+impl<'a> IntoIterator for &'a Circuit {
+    type Item = &'a Operation;
+    type IntoIter = std::slice::Iter<'a, Operation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.operations.iter()
+    }
+}
+
+impl IntoIterator for Circuit {
+    type Item = Operation;
+    type IntoIter = std::vec::IntoIter<Operation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.operations.into_iter()
+    }
+}
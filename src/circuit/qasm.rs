@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::Circuit;
+use crate::simulator::{pauli::Pauli, Operation};
+
+/// OpenQASMのversion
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QasmVersion {
+    V2,
+    V3,
+}
+
+/// 標準ライブラリにあるnon-Clifford gate (エラーメッセージに用いる)
+const NON_CLIFFORD_GATES: [&str; 23] = [
+    "u", "u0", "u1", "u2", "u3", "p", "phase", "rx", "ry", "rz", "cp", "cphase", "crx", "cry",
+    "crz", "cu", "cu1", "cu3", "ch", "ccx", "cswap", "rxx", "rzz",
+];
+
+impl Circuit {
+    /// OpenQASM 2/3のファイルを読み込む
+    pub fn from_qasm_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_qasm(&fs::read_to_string(path)?)
+    }
+
+    /// OpenQASM 2/3のClifford回路をパースする
+    ///
+    /// qreg/creg (qubit/bit), h, s, sdg, x, y, z, sx, sxdg, cx, cy, cz, swap, measure, reset, barrierに対応する.
+    /// Operationにあるt, tdgも読めるが, それ以外のnon-Clifford gateはエラーになる.
+    /// 複数のregisterは宣言順に通し番号のqubitにし, 測定結果はcregのindexによらず実行順にmeasurement recordに入る.
+    /// barrierはTICKになる.
+    /// "if (c[0]) x q[1];" (2.0では1 bitのcregについての"if (c==1)") はFeedbackになる.
+    pub fn from_qasm(text: &str) -> io::Result<Self> {
+        let error = |line_number, message, statement| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}: {}", line_number, message, statement),
+            )
+        };
+        let (statements, rest) = statements(text);
+        let mut parser = QasmParser::default();
+        for (line_number, statement) in statements {
+            parser
+                .parse_statement(&statement)
+                .map_err(|message| error(line_number, message, statement.clone()))?;
+        }
+        if let Some((line_number, statement)) = rest {
+            return Err(error(line_number, "missing ';'".to_string(), statement));
+        }
+        Ok(parser.circuit)
+    }
+
+    /// OpenQASMの文字列にする
+    ///
+    /// qubitはq[n], 測定結果は実行順にc[m]に入る. REPEATブロックは展開し, ノイズと注釈は書き出さない.
+    /// 測定error rateも無視する. X, Y基底の測定・リセットなどはh, sx, resetで書き換える.
+    /// Feedbackは"if (c[i]) x q[a];"と書くので, OpenQASM 3でのみ書き出せる.
+    /// return Err if the circuit contains an operation that cannot be written in OpenQASM
+    pub fn to_qasm(&self, version: QasmVersion) -> Result<String, String> {
+        let mut writer = QasmWriter {
+            version,
+            text: String::new(),
+            measurement: 0,
+        };
+        match version {
+            QasmVersion::V2 => {
+                writeln!(writer.text, "OPENQASM 2.0;\ninclude \"qelib1.inc\";").unwrap();
+                writeln!(writer.text, "qreg q[{}];", self.qubit_count()).unwrap();
+                writeln!(writer.text, "creg c[{}];", self.measurement_count()).unwrap();
+            }
+            QasmVersion::V3 => {
+                writeln!(writer.text, "OPENQASM 3.0;\ninclude \"stdgates.inc\";").unwrap();
+                writeln!(writer.text, "qubit[{}] q;", self.qubit_count()).unwrap();
+                writeln!(writer.text, "bit[{}] c;", self.measurement_count()).unwrap();
+            }
+        }
+        writer.write_operations(self)?;
+        Ok(writer.text)
+    }
+
+    /// OpenQASMのファイルに書き出す
+    pub fn write_qasm_file<P: AsRef<Path>>(&self, path: P, version: QasmVersion) -> io::Result<()> {
+        let text = self
+            .to_qasm(version)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidInput, message))?;
+        fs::write(path, text)
+    }
+}
+
+/// 文の始まりの行番号と文
+type Statement = (usize, String);
+
+/// コメントを除き, ';'で区切った文
+/// ';'で終わらない末尾の文があれば2番目に返す
+fn statements(text: &str) -> (Vec<Statement>, Option<Statement>) {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.split("//").next().unwrap();
+        for c in line.chars() {
+            if current.trim().is_empty() {
+                start = line_number + 1;
+            }
+            if c == ';' {
+                statements.push((start, current.trim().to_string()));
+                current.clear();
+            } else {
+                current.push(c);
+            }
+        }
+        current.push(' ');
+    }
+    let rest = (!current.trim().is_empty()).then(|| (start, current.trim().to_string()));
+    (statements, rest)
+}
+
+#[derive(Default)]
+struct QasmParser {
+    circuit: Circuit,
+    /// registerの名前から(先頭のindex, 大きさ)
+    qregs: HashMap<String, (usize, usize)>,
+    cregs: HashMap<String, usize>,
+    qubit_num: usize,
+    /// classical bitに最後に書き込んだ測定のmeasurement recordでのindex
+    bits: HashMap<(String, usize), usize>,
+    measurement_count: usize,
+}
+
+impl QasmParser {
+    fn parse_statement(&mut self, statement: &str) -> Result<(), String> {
+        if statement.is_empty() {
+            return Ok(());
+        }
+        let end = statement
+            .find(|c: char| c == '(' || c == '[' || c.is_whitespace())
+            .unwrap_or(statement.len());
+        let (keyword, rest) = statement.split_at(end);
+        let rest = rest.trim();
+
+        match keyword {
+            "OPENQASM" => match rest {
+                "2" | "2.0" | "3" | "3.0" => Ok(()),
+                _ => Err(format!("unsupported OpenQASM version {}", rest)),
+            },
+            "include" => Ok(()),
+            "qreg" | "creg" => {
+                let (name, size) = register(rest)?;
+                self.declare(keyword == "qreg", name, size.unwrap_or(1))
+            }
+            "qubit" | "bit" => {
+                // "qubit[n] name" or "qubit name"
+                let (size, name) = match rest.strip_prefix('[') {
+                    Some(rest) => {
+                        let close = rest.find(']').ok_or("missing ']'")?;
+                        let size = rest[..close]
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("invalid register size '{}'", &rest[..close]))?;
+                        (size, rest[close + 1..].trim())
+                    }
+                    None => (1, rest),
+                };
+                self.declare(keyword == "qubit", identifier(name)?, size)
+            }
+            "measure" => {
+                // "measure q[0] -> c[0]" or "measure q[0]"
+                let (qubits, bits) = match rest.split_once("->") {
+                    Some((qubits, bits)) => (qubits, Some(bits)),
+                    None => (rest, None),
+                };
+                self.measure(qubits, bits)
+            }
+            "reset" => {
+                let qubits = self.operand(rest)?;
+                self.circuit.extend(qubits.into_iter().map(Operation::R));
+                Ok(())
+            }
+            "barrier" => {
+                self.operands(rest)?;
+                self.circuit.push(Operation::Tick);
+                Ok(())
+            }
+            "gate" | "opaque" | "def" => Err("custom gate definitions are not supported".into()),
+            "if" => self.feedback(rest),
+            _ if statement.contains("= measure") || statement.contains("=measure") => {
+                // "c[0] = measure q[0]"
+                let (bits, qubits) = statement.split_once('=').unwrap();
+                let qubits = qubits.trim().strip_prefix("measure").unwrap();
+                self.measure(qubits, Some(bits))
+            }
+            name => self.gate(name, rest),
+        }
+    }
+
+    fn declare(&mut self, quantum: bool, name: &str, size: usize) -> Result<(), String> {
+        if self.qregs.contains_key(name) || self.cregs.contains_key(name) {
+            return Err(format!("register '{}' is already declared", name));
+        }
+        if quantum {
+            self.qregs.insert(name.to_string(), (self.qubit_num, size));
+            self.qubit_num += size;
+        } else {
+            self.cregs.insert(name.to_string(), size);
+        }
+        Ok(())
+    }
+
+    fn measure(&mut self, qubits: &str, bits: Option<&str>) -> Result<(), String> {
+        let qubits = self.operand(qubits)?;
+        if let Some(bits) = bits {
+            let (name, index) = register(bits.trim())?;
+            let size = *self
+                .cregs
+                .get(name)
+                .ok_or(format!("undeclared classical register '{}'", name))?;
+            let count = match index {
+                Some(i) if i >= size => {
+                    return Err(format!("index {} out of range of '{}'", i, name))
+                }
+                Some(_) => 1,
+                None => size,
+            };
+            if count != qubits.len() {
+                return Err("register sizes do not match".to_string());
+            }
+            let first = index.unwrap_or(0);
+            for i in 0..count {
+                self.bits
+                    .insert((name.to_string(), first + i), self.measurement_count + i);
+            }
+        }
+        self.measurement_count += qubits.len();
+        self.circuit
+            .extend(qubits.into_iter().map(|a| Operation::M(a, 0.0)));
+        Ok(())
+    }
+
+    /// "(c[i]) x q[a]", "(c[i] == 1) x q[a]" or "(c == 1) x q[a]" (cが1 bitのとき)
+    fn feedback(&mut self, rest: &str) -> Result<(), String> {
+        let (condition, body) = rest
+            .strip_prefix('(')
+            .and_then(|rest| rest.split_once(')'))
+            .ok_or("condition must be enclosed in '(' and ')'")?;
+        let bit = match condition.split_once("==") {
+            Some((bit, value)) if value.trim() == "1" => bit.trim(),
+            Some(_) => return Err("only '== 1' conditions are supported".to_string()),
+            None => condition.trim(),
+        };
+        let (name, index) = register(bit)?;
+        let size = *self
+            .cregs
+            .get(name)
+            .ok_or(format!("undeclared classical register '{}'", name))?;
+        let index = match index {
+            Some(i) => i,
+            None if size == 1 => 0,
+            None => return Err("condition on a multi-bit register is not supported".to_string()),
+        };
+        let record = *self
+            .bits
+            .get(&(name.to_string(), index))
+            .ok_or(format!("{}[{}] is not measured", name, index))?;
+        let lookback = self.measurement_count - record;
+
+        let body = body.trim();
+        let end = body.find(char::is_whitespace).unwrap_or(body.len());
+        let pauli = match &body[..end] {
+            "x" => Pauli::X,
+            "y" => Pauli::Y,
+            "z" => Pauli::Z,
+            gate => {
+                return Err(format!(
+                    "only x, y and z can be classically controlled, got '{}'",
+                    gate
+                ))
+            }
+        };
+        let qubits = self.operand(&body[end..])?;
+        self.circuit.extend(
+            qubits
+                .into_iter()
+                .map(|a| Operation::Feedback(pauli, lookback, a)),
+        );
+        Ok(())
+    }
+
+    fn gate(&mut self, name: &str, rest: &str) -> Result<(), String> {
+        let single: Option<fn(usize) -> Operation> = match name {
+            "h" => Some(Operation::H),
+            "s" => Some(Operation::S),
+            "sdg" => Some(Operation::SDag),
+            "x" => Some(Operation::X),
+            "y" => Some(Operation::Y),
+            "z" => Some(Operation::Z),
+            "sx" => Some(Operation::SqrtX),
+            "sxdg" => Some(Operation::SqrtXDag),
+            "t" => Some(Operation::T),
+            "tdg" => Some(Operation::TDag),
+            _ => None,
+        };
+        let double: Option<fn(usize, usize) -> Operation> = match name {
+            "cx" | "CX" | "cnot" => Some(Operation::CX),
+            "cy" => Some(Operation::CY),
+            "cz" => Some(Operation::CZ),
+            "swap" => Some(Operation::Swap),
+            _ => None,
+        };
+
+        if let Some(gate) = single {
+            let operands = self.operands(rest)?;
+            let [qubits] = &operands[..] else {
+                return Err(format!("{} takes 1 operand", name));
+            };
+            self.circuit.extend(qubits.iter().map(|&a| gate(a)));
+            Ok(())
+        } else if let Some(gate) = double {
+            let operands = self.operands(rest)?;
+            let [a, b] = &operands[..] else {
+                return Err(format!("{} takes 2 operands", name));
+            };
+            // registerを渡した場合は要素ごとに作用させる
+            let pairs: Vec<_> = match (a.len(), b.len()) {
+                (1, n) => b.iter().map(|&b| (a[0], b)).take(n).collect(),
+                (n, 1) => a.iter().map(|&a| (a, b[0])).take(n).collect(),
+                (n, m) if n == m => a.iter().copied().zip(b.iter().copied()).collect(),
+                _ => return Err("register sizes do not match".to_string()),
+            };
+            if let Some(&(a, _)) = pairs.iter().find(|(a, b)| a == b) {
+                return Err(format!("{} acts twice on qubit {}", name, a));
+            }
+            self.circuit
+                .extend(pairs.into_iter().map(|(a, b)| gate(a, b)));
+            Ok(())
+        } else if name == "id" {
+            // identityは何もしない
+            self.operands(rest).map(|_| ())
+        } else if NON_CLIFFORD_GATES.contains(&name.to_lowercase().as_str()) {
+            Err(format!("non-Clifford gate '{}' is not supported", name))
+        } else {
+            Err(format!("unknown gate '{}'", name))
+        }
+    }
+
+    /// ','区切りのoperand
+    fn operands(&self, rest: &str) -> Result<Vec<Vec<usize>>, String> {
+        if rest.is_empty() {
+            return Err("missing operand".to_string());
+        }
+        rest.split(',')
+            .map(|operand| self.operand(operand))
+            .collect()
+    }
+
+    /// "q[i]"ならそのqubit, "q"ならregisterの全てのqubit
+    fn operand(&self, operand: &str) -> Result<Vec<usize>, String> {
+        let (name, index) = register(operand.trim())?;
+        let &(offset, size) = self
+            .qregs
+            .get(name)
+            .ok_or(format!("undeclared quantum register '{}'", name))?;
+        match index {
+            Some(i) if i >= size => Err(format!("index {} out of range of '{}'", i, name)),
+            Some(i) => Ok(vec![offset + i]),
+            None => Ok((offset..offset + size).collect()),
+        }
+    }
+}
+
+/// "name[i]"を(name, Some(i)), "name"を(name, None)にする
+fn register(text: &str) -> Result<(&str, Option<usize>), String> {
+    match text.split_once('[') {
+        Some((name, index)) => {
+            let index = index
+                .strip_suffix(']')
+                .and_then(|index| index.trim().parse().ok())
+                .ok_or(format!("invalid index in '{}'", text))?;
+            Ok((identifier(name.trim())?, Some(index)))
+        }
+        None => Ok((identifier(text)?, None)),
+    }
+}
+
+fn identifier(name: &str) -> Result<&str, String> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if valid {
+        Ok(name)
+    } else {
+        Err(format!("invalid identifier '{}'", name))
+    }
+}
+
+struct QasmWriter {
+    version: QasmVersion,
+    text: String,
+    /// 次の測定結果を入れるcのindex
+    measurement: usize,
+}
+
+impl QasmWriter {
+    fn write_operations(&mut self, circuit: &Circuit) -> Result<(), String> {
+        for op in circuit.iter() {
+            match op {
+                Operation::Repeat(n, body) => {
+                    for _ in 0..*n {
+                        self.write_operations(body)?;
+                    }
+                }
+                Operation::Tick => self.line("barrier q"),
+                Operation::H(a) => self.gate("h", &[*a]),
+                Operation::S(a) => self.gate("s", &[*a]),
+                Operation::SDag(a) => self.gate("sdg", &[*a]),
+                Operation::X(a) => self.gate("x", &[*a]),
+                Operation::Y(a) => self.gate("y", &[*a]),
+                Operation::Z(a) => self.gate("z", &[*a]),
+                Operation::SqrtX(a) => self.gate("sx", &[*a]),
+                Operation::SqrtXDag(a) => self.gate("sxdg", &[*a]),
+                // sqrt(Y) = H Z, sqrt(Y)^dag = Z H (大域位相を除く)
+                Operation::SqrtY(a) => {
+                    self.gate("z", &[*a]);
+                    self.gate("h", &[*a]);
+                }
+                Operation::SqrtYDag(a) => {
+                    self.gate("h", &[*a]);
+                    self.gate("z", &[*a]);
+                }
+                Operation::T(a) => self.gate("t", &[*a]),
+                Operation::TDag(a) => self.gate("tdg", &[*a]),
+                Operation::CX(a, b) => self.gate("cx", &[*a, *b]),
+                Operation::CY(a, b) => self.gate("cy", &[*a, *b]),
+                Operation::CZ(a, b) => self.gate("cz", &[*a, *b]),
+                Operation::Swap(a, b) => self.gate("swap", &[*a, *b]),
+                // X-controlled Z = 向きを逆にしたCX
+                Operation::XCZ(a, b) => self.gate("cx", &[*b, *a]),
+                Operation::YCZ(a, b) => self.gate("cy", &[*b, *a]),
+                Operation::M(a, _) => self.measure(*a),
+                Operation::MR(a, _) => {
+                    self.measure(*a);
+                    self.gate("reset", &[*a]);
+                }
+                Operation::MX(a, _) => {
+                    self.gate("h", &[*a]);
+                    self.measure(*a);
+                    self.gate("h", &[*a]);
+                }
+                Operation::MY(a, _) => {
+                    self.gate("sx", &[*a]);
+                    self.measure(*a);
+                    self.gate("sxdg", &[*a]);
+                }
+                Operation::R(a) => self.gate("reset", &[*a]),
+                Operation::RX(a) => {
+                    self.gate("reset", &[*a]);
+                    self.gate("h", &[*a]);
+                }
+                Operation::RY(a) => {
+                    self.gate("reset", &[*a]);
+                    self.gate("sxdg", &[*a]);
+                }
+                Operation::MRX(a, _) => {
+                    self.gate("h", &[*a]);
+                    self.measure(*a);
+                    self.gate("reset", &[*a]);
+                    self.gate("h", &[*a]);
+                }
+                Operation::MRY(a, _) => {
+                    self.gate("sx", &[*a]);
+                    self.measure(*a);
+                    self.gate("reset", &[*a]);
+                    self.gate("sxdg", &[*a]);
+                }
+                Operation::Depolarizing(_, _)
+                | Operation::PauliChannel1(_, _, _, _)
+                | Operation::Depolarizing2(_, _, _)
+                | Operation::PauliChannel2(_, _, _)
+                | Operation::Detector(_, _)
+                | Operation::ObservableInclude(_, _) => (),
+                Operation::Feedback(Pauli::I, _, _) => (),
+                Operation::Feedback(pauli, lookback, a) => {
+                    if self.version == QasmVersion::V2 {
+                        return Err("classically controlled Pauli needs OpenQASM 3".to_string());
+                    }
+                    let gate = format!("{:?}", pauli).to_lowercase();
+                    let bit = self.measurement - lookback;
+                    self.line(&format!("if (c[{}]) {} q[{}]", bit, gate, a));
+                }
+                op => return Err(format!("{:?} cannot be written in OpenQASM", op)),
+            }
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, statement: &str) {
+        writeln!(self.text, "{};", statement).unwrap();
+    }
+
+    fn gate(&mut self, name: &str, qubits: &[usize]) {
+        let operands: Vec<_> = qubits.iter().map(|a| format!("q[{}]", a)).collect();
+        self.line(&format!("{} {}", name, operands.join(", ")));
+    }
+
+    fn measure(&mut self, a: usize) {
+        let statement = match self.version {
+            QasmVersion::V2 => format!("measure q[{}] -> c[{}]", a, self.measurement),
+            QasmVersion::V3 => format!("c[{}] = measure q[{}]", self.measurement, a),
+        };
+        self.line(&statement);
+        self.measurement += 1;
+    }
+}
@@ -0,0 +1,486 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::Circuit;
+use crate::simulator::{pauli::Pauli, Operation};
+
+impl Circuit {
+    /// Stim形式 (.stim) の回路ファイルを読み込む
+    pub fn from_stim_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_stim(&fs::read_to_string(path)?)
+    }
+
+    /// Stim形式の回路をパースする
+    ///
+    /// gate, 測定・リセット, ノイズチャネル, DETECTOR, OBSERVABLE_INCLUDE, TICK, REPEATに対応する.
+    /// "CX rec[-1] 5"のように測定結果で制御されたCX, CY, CZはFeedbackになる.
+    /// QUBIT_COORDSとSHIFT_COORDSは読み飛ばす (detectorの座標は書かれた値のまま).
+    /// Stimにない T, T_DAG, M_TO_ZERO も読める.
+    /// エラーメッセージには行番号が入る.
+    pub fn from_stim(text: &str) -> io::Result<Self> {
+        // (REPEATの行番号, 繰り返し回数, body)
+        let mut blocks = vec![(0, 1, Circuit::new())];
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}: {}", line_number + 1, message, line),
+                )
+            };
+
+            if line == "}" {
+                if blocks.len() == 1 {
+                    return Err(invalid("'}' without REPEAT"));
+                }
+                let (_, repetitions, body) = blocks.pop().unwrap();
+                blocks
+                    .last_mut()
+                    .unwrap()
+                    .2
+                    .push(Operation::Repeat(repetitions, body));
+            } else if let Some(count) = repeat_count(line) {
+                let repetitions = count.map_err(|message| invalid(&message))?;
+                blocks.push((line_number + 1, repetitions, Circuit::new()));
+            } else {
+                let operations = parse_instruction(line).map_err(|message| invalid(&message))?;
+                blocks.last_mut().unwrap().2.extend(operations);
+            }
+        }
+
+        if blocks.len() > 1 {
+            let (line_number, _, _) = blocks.last().unwrap();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: REPEAT block is not closed", line_number),
+            ));
+        }
+        Ok(blocks.pop().unwrap().2)
+    }
+
+    /// Stim形式の文字列にする (1行に1オペレーション, REPEATブロックは展開しない)
+    ///
+    /// Pauli::IのFeedbackは恒等写像なので書き出さない.
+    /// Stimにない T, T_DAG, M_TO_ZERO を含む回路はErr
+    pub fn to_stim(&self) -> Result<String, String> {
+        let mut text = String::new();
+        write_operations(&mut text, self, 0)?;
+        Ok(text)
+    }
+
+    /// Stim形式で回路ファイルに書き出す (Stimで書けない回路ならInvalidInputのエラー)
+    pub fn write_stim_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = self
+            .to_stim()
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidInput, message))?;
+        fs::write(path, text)
+    }
+}
+
+/// "REPEAT n {"なら繰り返し回数
+fn repeat_count(line: &str) -> Option<Result<usize, String>> {
+    let end = line
+        .find(|c: char| !c.is_alphabetic())
+        .unwrap_or(line.len());
+    let (name, rest) = line.split_at(end);
+    if !name.eq_ignore_ascii_case("REPEAT") {
+        return None;
+    }
+    Some(match rest.trim().strip_suffix('{') {
+        Some(count) => count
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid repetition count '{}'", count.trim())),
+        None => Err("REPEAT must be followed by a count and '{'".to_string()),
+    })
+}
+
+/// "NAME(args) targets"を名前, 引数, targetに分ける
+fn split_instruction(line: &str) -> Result<(String, Vec<f32>, Vec<&str>), String> {
+    let end = line
+        .find(|c: char| c == '(' || c.is_whitespace())
+        .unwrap_or(line.len());
+    let (name, mut rest) = line.split_at(end);
+    let mut args = Vec::new();
+    if let Some(inner) = rest.trim_start().strip_prefix('(') {
+        let close = inner.find(')').ok_or("missing ')'")?;
+        args = inner[..close]
+            .split(',')
+            .map(|arg| {
+                arg.trim()
+                    .parse()
+                    .map_err(|_| format!("invalid argument '{}'", arg.trim()))
+            })
+            .collect::<Result<_, _>>()?;
+        rest = &inner[close + 1..];
+    }
+    Ok((
+        name.to_ascii_uppercase(),
+        args,
+        rest.split_whitespace().collect(),
+    ))
+}
+
+/// 引数をとらない1 qubitのオペレーション
+fn single_qubit_operation(name: &str) -> Option<fn(usize) -> Operation> {
+    Some(match name {
+        "H" | "H_XZ" => Operation::H,
+        "S" | "SQRT_Z" => Operation::S,
+        "S_DAG" | "SQRT_Z_DAG" => Operation::SDag,
+        "X" => Operation::X,
+        "Y" => Operation::Y,
+        "Z" => Operation::Z,
+        "SQRT_X" => Operation::SqrtX,
+        "SQRT_X_DAG" => Operation::SqrtXDag,
+        "SQRT_Y" => Operation::SqrtY,
+        "SQRT_Y_DAG" => Operation::SqrtYDag,
+        "T" => Operation::T,
+        "T_DAG" => Operation::TDag,
+        "R" | "RZ" => Operation::R,
+        "RX" => Operation::RX,
+        "RY" => Operation::RY,
+        "M_TO_ZERO" => Operation::MToZero,
+        _ => return None,
+    })
+}
+
+/// 引数をとらない2 qubit gate
+fn two_qubit_gate(name: &str) -> Option<fn(usize, usize) -> Operation> {
+    Some(match name {
+        "CX" | "CNOT" | "ZCX" => Operation::CX,
+        "CY" | "ZCY" => Operation::CY,
+        "CZ" | "ZCZ" => Operation::CZ,
+        "SWAP" => Operation::Swap,
+        "ISWAP" => Operation::ISwap,
+        "ISWAP_DAG" => Operation::ISwapDag,
+        "XCX" => Operation::XCX,
+        "XCY" => Operation::XCY,
+        "XCZ" => Operation::XCZ,
+        "YCX" => Operation::YCX,
+        "YCY" => Operation::YCY,
+        "YCZ" => Operation::YCZ,
+        _ => return None,
+    })
+}
+
+/// 測定エラーの確率を引数にとる1 qubitの測定
+fn measurement(name: &str) -> Option<fn(usize, f32) -> Operation> {
+    Some(match name {
+        "M" | "MZ" => Operation::M,
+        "MX" => Operation::MX,
+        "MY" => Operation::MY,
+        "MR" | "MRZ" => Operation::MR,
+        "MRX" => Operation::MRX,
+        "MRY" => Operation::MRY,
+        _ => return None,
+    })
+}
+
+/// 1行をオペレーションの列にする (targetが複数あれば1つずつに分ける)
+fn parse_instruction(line: &str) -> Result<Vec<Operation>, String> {
+    let (name, args, targets) = split_instruction(line)?;
+    let expect_args = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} takes {} arguments, got {}",
+                name,
+                n,
+                args.len()
+            ))
+        }
+    };
+
+    if let Some(operation) = single_qubit_operation(&name) {
+        expect_args(0)?;
+        return Ok(qubits(&targets)?.into_iter().map(operation).collect());
+    }
+    if let Some(gate) = two_qubit_gate(&name) {
+        expect_args(0)?;
+        if targets.iter().any(|target| target.starts_with("rec[")) {
+            return feedback(&name, gate, &targets);
+        }
+        return Ok(pairs(&targets)?
+            .into_iter()
+            .map(|(a, b)| gate(a, b))
+            .collect());
+    }
+    if let Some(measurement) = measurement(&name) {
+        let p = measurement_error_rate(&name, &args)?;
+        return Ok(qubits(&targets)?
+            .into_iter()
+            .map(|a| measurement(a, p))
+            .collect());
+    }
+
+    let operations = match name.as_str() {
+        "MPP" => {
+            let p = measurement_error_rate(&name, &args)?;
+            // "X0*Z1"や"X0 * Z1"を1つのPauli積にまとめる
+            let joined = targets.join(" ").replace(" *", "*").replace("* ", "*");
+            joined
+                .split_whitespace()
+                .map(|product| {
+                    let (sign, product) = pauli_product(product)?;
+                    Operation::measurement_pauli_product(&product, sign, p)
+                })
+                .collect::<Result<_, String>>()?
+        }
+        "DEPOLARIZE1" => {
+            expect_args(1)?;
+            let p = args[0];
+            qubits(&targets)?
+                .into_iter()
+                .map(|a| Operation::Depolarizing(a, p))
+                .collect()
+        }
+        "X_ERROR" | "Y_ERROR" | "Z_ERROR" | "PAULI_CHANNEL_1" => {
+            let p = match name.as_str() {
+                "X_ERROR" => expect_args(1).map(|_| [args[0], 0.0, 0.0])?,
+                "Y_ERROR" => expect_args(1).map(|_| [0.0, args[0], 0.0])?,
+                "Z_ERROR" => expect_args(1).map(|_| [0.0, 0.0, args[0]])?,
+                _ => expect_args(3).map(|_| [args[0], args[1], args[2]])?,
+            };
+            qubits(&targets)?
+                .into_iter()
+                .map(|a| Operation::PauliChannel1(a, p[0], p[1], p[2]))
+                .collect()
+        }
+        "DEPOLARIZE2" => {
+            expect_args(1)?;
+            let p = args[0];
+            pairs(&targets)?
+                .into_iter()
+                .map(|(a, b)| Operation::Depolarizing2(a, b, p))
+                .collect()
+        }
+        "PAULI_CHANNEL_2" => {
+            expect_args(15)?;
+            let p: [f32; 15] = args.as_slice().try_into().unwrap();
+            pairs(&targets)?
+                .into_iter()
+                .map(|(a, b)| Operation::PauliChannel2(a, b, p))
+                .collect()
+        }
+        "DETECTOR" => vec![Operation::Detector(args, lookback(&targets)?)],
+        "OBSERVABLE_INCLUDE" => {
+            expect_args(1)?;
+            if args[0] < 0.0 || args[0].fract() != 0.0 {
+                return Err(format!("invalid observable index {}", args[0]));
+            }
+            vec![Operation::ObservableInclude(
+                args[0] as usize,
+                lookback(&targets)?,
+            )]
+        }
+        "TICK" => {
+            expect_args(0)?;
+            if !targets.is_empty() {
+                return Err("TICK takes no targets".to_string());
+            }
+            vec![Operation::Tick]
+        }
+        // 恒等写像と座標の注釈は回路に影響しない
+        "I" => {
+            qubits(&targets)?;
+            vec![]
+        }
+        "QUBIT_COORDS" | "SHIFT_COORDS" => vec![],
+        _ => return Err(format!("unsupported operation {}", name)),
+    };
+    Ok(operations)
+}
+
+/// 測定の引数 (省略すれば0)
+fn measurement_error_rate(name: &str, args: &[f32]) -> Result<f32, String> {
+    match *args {
+        [] => Ok(0.0),
+        [p] => Ok(p),
+        _ => Err(format!("{} takes at most 1 argument", name)),
+    }
+}
+
+fn qubits(targets: &[&str]) -> Result<Vec<usize>, String> {
+    targets
+        .iter()
+        .map(|target| match target.parse() {
+            Ok(a) => Ok(a),
+            Err(_) if target.starts_with('!') => {
+                Err(format!("inverted target '{}' is not supported", target))
+            }
+            Err(_) => Err(format!("invalid qubit target '{}'", target)),
+        })
+        .collect()
+}
+
+fn pairs(targets: &[&str]) -> Result<Vec<(usize, usize)>, String> {
+    let qubits = qubits(targets)?;
+    if qubits.len() % 2 != 0 {
+        return Err("two qubit operation needs an even number of targets".to_string());
+    }
+    Ok(qubits.chunks(2).map(|pair| (pair[0], pair[1])).collect())
+}
+
+/// "CX rec[-k] q"のような測定結果で制御されたPauliを含む2 qubit gate (CZはtargetが逆順でもよい)
+fn feedback(
+    name: &str,
+    gate: fn(usize, usize) -> Operation,
+    targets: &[&str],
+) -> Result<Vec<Operation>, String> {
+    let pauli = match name {
+        "CX" | "CNOT" | "ZCX" => Pauli::X,
+        "CY" | "ZCY" => Pauli::Y,
+        "CZ" | "ZCZ" => Pauli::Z,
+        _ => {
+            return Err(format!(
+                "{} cannot be controlled by a measurement record",
+                name
+            ))
+        }
+    };
+    let pairs = targets.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err("two qubit operation needs an even number of targets".to_string());
+    }
+    pairs
+        .map(|pair| match (lookback(&pair[..1]), lookback(&pair[1..])) {
+            (Ok(k), Err(_)) => Ok(Operation::Feedback(pauli, k[0], qubits(&pair[1..])?[0])),
+            (Err(_), Ok(k)) if pauli == Pauli::Z => {
+                Ok(Operation::Feedback(pauli, k[0], qubits(&pair[..1])?[0]))
+            }
+            (Err(_), Err(_)) => {
+                let [a, b] = qubits(pair)?[..] else {
+                    unreachable!()
+                };
+                Ok(gate(a, b))
+            }
+            _ => Err(format!(
+                "invalid feedback targets '{} {}'",
+                pair[0], pair[1]
+            )),
+        })
+        .collect()
+}
+
+/// rec[-k]のkの列
+fn lookback(targets: &[&str]) -> Result<Vec<usize>, String> {
+    targets
+        .iter()
+        .map(|target| {
+            target
+                .strip_prefix("rec[-")
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|k| k.parse().ok())
+                .filter(|&k| k > 0)
+                .ok_or(format!("invalid measurement record target '{}'", target))
+        })
+        .collect()
+}
+
+/// "X0*Z1*Y2"や"!X0*Z1"を(sign, Pauli積)にする ("!"が付いた項ごとに符号が反転する)
+fn pauli_product(product: &str) -> Result<(u8, Vec<(usize, Pauli)>), String> {
+    let mut sign = 0;
+    let product = product
+        .split('*')
+        .map(|term| {
+            let term = match term.strip_prefix('!') {
+                Some(rest) => {
+                    sign ^= 1;
+                    rest
+                }
+                None => term,
+            };
+            let pauli = match term.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('X') => Pauli::X,
+                Some('Y') => Pauli::Y,
+                Some('Z') => Pauli::Z,
+                _ => return Err(format!("invalid Pauli target '{}'", term)),
+            };
+            let a = term[1..]
+                .parse()
+                .map_err(|_| format!("invalid Pauli target '{}'", term))?;
+            Ok((a, pauli))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok((sign, product))
+}
+
+fn write_operations(text: &mut String, circuit: &Circuit, indent: usize) -> Result<(), String> {
+    for op in circuit.iter() {
+        let (args, targets): (Vec<f32>, Vec<String>) = match op {
+            Operation::Repeat(n, body) => {
+                writeln!(text, "{:indent$}REPEAT {} {{", "", n).unwrap();
+                write_operations(text, body, indent + 4)?;
+                writeln!(text, "{:indent$}}}", "").unwrap();
+                continue;
+            }
+            Operation::T(_) | Operation::TDag(_) | Operation::MToZero(_) => {
+                return Err(format!("{:?} cannot be written in Stim", op));
+            }
+            // 恒等写像
+            Operation::Feedback(Pauli::I, _, _) => continue,
+            Operation::M(a, p)
+            | Operation::MR(a, p)
+            | Operation::MX(a, p)
+            | Operation::MY(a, p)
+            | Operation::MRX(a, p)
+            | Operation::MRY(a, p) => (nonzero(*p), vec![a.to_string()]),
+            Operation::MPP(product, sign, p) => {
+                let product = product
+                    .iter()
+                    .map(|(a, pauli)| format!("{:?}{}", pauli, a))
+                    .collect::<Vec<_>>()
+                    .join("*");
+                let invert = if *sign == 1 { "!" } else { "" };
+                (nonzero(*p), vec![format!("{}{}", invert, product)])
+            }
+            Operation::Depolarizing(_, p) | Operation::Depolarizing2(_, _, p) => {
+                (vec![*p], qubit_targets(op))
+            }
+            Operation::PauliChannel1(_, px, py, pz) => (vec![*px, *py, *pz], qubit_targets(op)),
+            Operation::PauliChannel2(_, _, p) => (p.to_vec(), qubit_targets(op)),
+            Operation::Detector(coords, lookback) => (coords.clone(), rec_targets(lookback)),
+            Operation::ObservableInclude(observable, lookback) => {
+                (vec![*observable as f32], rec_targets(lookback))
+            }
+            Operation::Feedback(_, lookback, a) => {
+                (vec![], vec![format!("rec[-{}]", lookback), a.to_string()])
+            }
+            op => (vec![], qubit_targets(op)),
+        };
+
+        write!(text, "{:indent$}{}", "", op.name()).unwrap();
+        if !args.is_empty() {
+            let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+            write!(text, "({})", args.join(", ")).unwrap();
+        }
+        for target in targets {
+            write!(text, " {}", target).unwrap();
+        }
+        text.push('\n');
+    }
+    Ok(())
+}
+
+fn nonzero(p: f32) -> Vec<f32> {
+    if p == 0.0 {
+        vec![]
+    } else {
+        vec![p]
+    }
+}
+
+fn qubit_targets(op: &Operation) -> Vec<String> {
+    op.qubits().iter().map(|a| a.to_string()).collect()
+}
+
+fn rec_targets(lookback: &[usize]) -> Vec<String> {
+    lookback.iter().map(|k| format!("rec[-{}]", k)).collect()
+}
@@ -1,5 +1,92 @@
 pub mod mwpm;
+pub mod sparse_blossom;
+pub mod union_find;
 
+use crate::qubit_graph::ungraph::UnGraph;
+
+/// decode_batchで使うdecoding graph
+/// detectorのindexとsyndrome graphのnodeの対応, observable (data qubitの座標) を持つ
+#[derive(Debug, Clone)]
+pub struct DecodingGraph {
+    graph: UnGraph,
+    detectors: Vec<((i32, i32, i32), usize)>,
+    observable: Vec<(i32, i32)>,
+}
+
+impl DecodingGraph {
+    pub fn new(
+        graph: &UnGraph,
+        detectors: &[((i32, i32, i32), usize)],
+        observable: &[(i32, i32)],
+    ) -> Self {
+        let mut graph = graph.clone();
+        graph.reset_register();
+        Self {
+            graph,
+            detectors: detectors.to_vec(),
+            observable: observable.to_vec(),
+        }
+    }
+
+    /// 1shot分のdetection event (detectorのindex順のbit) をgraphに書き込む
+    fn load(&mut self, detection_events: &[u8]) {
+        self.graph.reset_register();
+        for &(coord, id) in self.detectors.iter() {
+            self.graph.flip_classical_register(&coord, detection_events[id]);
+        }
+        self.graph.flip_boundary_defect();
+    }
+}
+
+/// syndrome graph上のdetection eventからcorrectionを求めるdecoder
+///
+/// graphのedge weightがerror modelを表し, classical registerが1のnodeがdefectになる
 pub trait Decoder {
+    /// configureで設定したdecoding graph
+    fn decoding_graph_mut(&mut self) -> &mut Option<DecodingGraph>;
+
+    /// decoding graph (edge weightとboundary) と, detectorのindexからnodeへの対応,
+    /// observable (data qubitの座標) を設定する
+    fn configure(
+        &mut self,
+        graph: &UnGraph,
+        detectors: &[((i32, i32, i32), usize)],
+        observable: &[(i32, i32)],
+    ) {
+        *self.decoding_graph_mut() = Some(DecodingGraph::new(graph, detectors, observable));
+    }
+
+    /// defectが書き込まれたgraphをdecodeして, 反転させるdata qubitの座標を返す
+    /// defectの数が奇数の時刻はboundaryを反転させておく (UnGraph::flip_boundary_defect)
+    fn decode(&mut self, graph: &UnGraph) -> Vec<(i32, i32)>;
+
+    /// correctionがobservable (data qubitの座標) を反転させるかを返す
+    fn predict_observable(&mut self, graph: &UnGraph, observable: &[(i32, i32)]) -> u8 {
+        let flips = self
+            .decode(graph)
+            .into_iter()
+            .filter(|qubit| observable.contains(qubit))
+            .count();
+        (flips % 2) as u8
+    }
 
-}
\ No newline at end of file
+    /// shotごとのdetection event (detectorのindex順のbit) をdecodeし, observableの反転の予測を返す
+    fn decode_batch(&mut self, detection_events: &[Vec<u8>]) -> Vec<u8> {
+        let mut decoding_graph = self
+            .decoding_graph_mut()
+            .take()
+            .expect("configure must be called before decode_batch");
+        let prediction = detection_events
+            .iter()
+            .map(|events| {
+                decoding_graph.load(events);
+                let DecodingGraph {
+                    graph, observable, ..
+                } = &decoding_graph;
+                self.predict_observable(graph, observable)
+            })
+            .collect();
+        *self.decoding_graph_mut() = Some(decoding_graph);
+        prediction
+    }
+}
@@ -1,7 +1,8 @@
+use crate::decoder::{DecodingGraph, Decoder};
 use crate::qubit_graph::ungraph::UnGraph;
 
 use itertools::Itertools;
-use petgraph::graphmap::GraphMap;
+use petgraph::graphmap::{GraphMap, NodeTrait};
 use petgraph::graphmap::UnGraphMap;
 use petgraph::Undirected;
 use std::cmp::Ordering;
@@ -13,6 +14,15 @@ use retworkx_core::petgraph as rpet;
 
 use hashbrown::{HashMap, HashSet};
 
+type Coord = (i32, i32, i32);
+/// 最短路木 (到達できる各nodeの1つ前のnodeとsからの距離)
+type PathTree = HashMap<Coord, (Coord, f32)>;
+/// matching graphと, その各edgeに対応するsyndrome graph上のpath
+type MatchingGraph = (
+    GraphMap<MatchingNode, f32, Undirected>,
+    HashMap<(MatchingNode, MatchingNode), Vec<Coord>>,
+);
+
 #[derive(Clone, PartialEq, Debug)]
 struct State {
     distance: f32,
@@ -81,7 +91,6 @@ pub fn local_dijkstra(
             if graph
                 .get_register(&coord)
                 .unwrap_or_else(|| panic!("{:?} isn't exist", coord))
-                .get()
                 == 1
             {
                 m_nearest_node.push(State {
@@ -140,7 +149,7 @@ fn construct_syndrome_graph(
     let mut path_detail = HashMap::new();
 
     for (&coord, defect) in graph.iter_classical_register() {
-        if defect.get() == 1 {
+        if *defect == 1 {
             let path = local_dijkstra(graph, m, &coord);
 
             for (p, d) in path.into_iter() {
@@ -159,9 +168,118 @@ fn construct_syndrome_graph(
     (local_graph, path_detail)
 }
 
-fn minimum_weight_perfect_matching(
-    local_graph: GraphMap<(i32, i32, i32), f32, Undirected>,
-) -> Vec<((i32, i32, i32), (i32, i32, i32))> {
+/// 全てのdefectの組を結んだmatching graphのnode
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+enum MatchingNode {
+    Defect(Coord),
+    /// defectごとのvirtual boundary
+    Boundary(Coord),
+}
+
+/// sからの最短路木
+fn shortest_path_tree(graph: &UnGraph, s: &Coord) -> PathTree {
+    let mut tree = HashMap::new();
+    let mut heap = BinaryHeap::new();
+    heap.push(State {
+        distance: 0.0,
+        coord: *s,
+        predecessor: *s,
+    });
+
+    while let Some(State {
+        distance,
+        coord,
+        predecessor,
+    }) = heap.pop()
+    {
+        if tree.contains_key(&coord) {
+            continue;
+        }
+        tree.insert(coord, (predecessor, distance));
+        for v in graph.neighbors(&coord).unwrap() {
+            if !tree.contains_key(v) {
+                heap.push(State {
+                    distance: distance + graph.edge_weight(&(coord, *v)).unwrap(),
+                    coord: *v,
+                    predecessor: coord,
+                });
+            }
+        }
+    }
+    tree
+}
+
+/// 最短路木のvから根までのpath
+fn path_in_tree(tree: &PathTree, v: Coord) -> Vec<Coord> {
+    let mut path = vec![v];
+    let mut coord = v;
+    loop {
+        let (predecessor, _) = tree[&coord];
+        if predecessor == coord {
+            break;
+        }
+        path.push(predecessor);
+        coord = predecessor;
+    }
+    path
+}
+
+/// 全てのdefectの組と, 各defectから最も近いboundaryへの最短路でmatching graphを作る
+/// virtual boundary同士はweight 0で結ぶので, 必ずperfect matchingが存在する
+fn construct_complete_syndrome_graph(graph: &UnGraph) -> MatchingGraph {
+    // boundaryのdefectはvirtual boundaryで置き換える
+    let defects: Vec<_> = graph
+        .iter_classical_register()
+        .filter(|&(coord, &defect)| defect == 1 && !graph.is_boundary(coord).unwrap())
+        .map(|(&coord, _)| coord)
+        .sorted()
+        .collect();
+
+    let mut complete_graph = UnGraphMap::new();
+    let mut path_detail = HashMap::new();
+
+    for (i, &u) in defects.iter().enumerate() {
+        let tree = shortest_path_tree(graph, &u);
+
+        for &v in defects.iter().skip(i + 1) {
+            if let Some(&(_, d)) = tree.get(&v) {
+                let (u, v, p) = (
+                    MatchingNode::Defect(u),
+                    MatchingNode::Defect(v),
+                    path_in_tree(&tree, v),
+                );
+                complete_graph.add_edge(u, v, -d);
+                path_detail.insert((u, v), p.clone());
+                path_detail.insert((v, u), p);
+            }
+        }
+
+        let nearest_boundary = tree
+            .iter()
+            .filter(|&(coord, _)| graph.is_boundary(coord).unwrap())
+            .min_by(|(a, (_, d_a)), (b, (_, d_b))| d_a.partial_cmp(d_b).unwrap().then(a.cmp(b)));
+        if let Some((&b, &(_, d))) = nearest_boundary {
+            let (u, v, p) = (
+                MatchingNode::Defect(u),
+                MatchingNode::Boundary(u),
+                path_in_tree(&tree, b),
+            );
+            complete_graph.add_edge(u, v, -d);
+            path_detail.insert((u, v), p.clone());
+            path_detail.insert((v, u), p);
+        }
+
+        for &v in defects.iter().take(i) {
+            complete_graph.add_edge(MatchingNode::Boundary(u), MatchingNode::Boundary(v), 0.0);
+        }
+    }
+
+    (complete_graph, path_detail)
+}
+
+fn minimum_weight_perfect_matching<N: NodeTrait>(
+    local_graph: GraphMap<N, f32, Undirected>,
+) -> Vec<(N, N)> {
     let mut coord_to_index = HashMap::new();
     let mut index_to_coord = HashMap::new();
 
@@ -203,23 +321,23 @@ fn minimum_weight_perfect_matching(
     matching
 }
 
-fn decide_correction_qubit(
+/// matchingしたdefectを結ぶpathから, correctionするdata qubitを決める
+pub(crate) fn decide_correction_qubit<'a>(
     graph: &UnGraph,
-    matching: Vec<((i32, i32, i32), (i32, i32, i32))>,
-    path_detail: HashMap<((i32, i32, i32), (i32, i32, i32)), Vec<(i32, i32, i32)>>,
+    paths: impl Iterator<Item = &'a Vec<(i32, i32, i32)>>,
 ) -> Vec<(i32, i32)> {
     let mut correction_qubit = Vec::new();
 
     // 空間方向にedgeが存在するものだけを抽出
-    for (u, v) in matching.into_iter() {
+    for correction_path in paths {
+        let (u, v) = (
+            correction_path[0],
+            correction_path[correction_path.len() - 1],
+        );
         if cfg!(debug_assertions) {
             println!("edge {:?}, {:?}", u, v);
         }
         if (u.0 != v.0) || (u.1 != v.1) {
-            let correction_path = path_detail
-                .get(&(u, v))
-                .unwrap_or_else(|| panic!("edge: {:?} is not exist", (u, v)));
-
             if cfg!(debug_assertions) {
                 let weight: f32 = correction_path
                     .iter()
@@ -230,7 +348,7 @@ fn decide_correction_qubit(
             }
 
             correction_path
-                .into_iter()
+                .iter()
                 .tuple_windows()
                 .filter(|(&u, &v)| (u.0 != v.0) || (u.1 != v.1))
                 .filter(|(u, v)| !(graph.is_boundary(u).unwrap() && graph.is_boundary(v).unwrap()))
@@ -241,8 +359,89 @@ fn decide_correction_qubit(
 }
 
 /// decode
+/// m個の近いdefectだけを結んだgraphにperfect matchingがなければ, decode_exactで求め直す
 pub fn decode(graph: &UnGraph, m: usize) -> Vec<(i32, i32)> {
     let (local_graph, path_detail) = construct_syndrome_graph(graph, m);
     let matching = minimum_weight_perfect_matching(local_graph);
-    decide_correction_qubit(graph, matching, path_detail)
+
+    let defect_num = graph
+        .iter_classical_register()
+        .filter(|&(_, &defect)| defect == 1)
+        .count();
+    if matching.len() * 2 != defect_num {
+        if cfg!(debug_assertions) {
+            println!(
+                "no perfect matching with m = {}, fall back to exact mwpm",
+                m
+            );
+        }
+        return decode_exact(graph);
+    }
+
+    let paths = matching.iter().map(|edge| {
+        path_detail
+            .get(edge)
+            .unwrap_or_else(|| panic!("edge: {:?} is not exist", edge))
+    });
+    decide_correction_qubit(graph, paths)
+}
+
+/// 全てのdefectの組を最短路で結んだgraphで厳密にdecodeする
+pub fn decode_exact(graph: &UnGraph) -> Vec<(i32, i32)> {
+    let (complete_graph, path_detail) = construct_complete_syndrome_graph(graph);
+    let matching = minimum_weight_perfect_matching(complete_graph);
+
+    // virtual boundary同士のmatchingはcorrectionに関係しない
+    let paths = matching
+        .iter()
+        .filter(|(u, v)| {
+            !matches!(
+                (u, v),
+                (MatchingNode::Boundary(_), MatchingNode::Boundary(_))
+            )
+        })
+        .map(|edge| {
+            path_detail
+                .get(edge)
+                .unwrap_or_else(|| panic!("edge: {:?} is not exist", edge))
+        });
+    decide_correction_qubit(graph, paths)
+}
+
+/// 各defectを近いm個のdefectとだけ結んだgraphでmatchingするdecoder
+/// mを指定しなければ全てのdefectの組を結んだgraphで厳密にmatchingする
+#[derive(Debug, Clone)]
+pub struct Mwpm {
+    m: Option<usize>,
+    decoding_graph: Option<DecodingGraph>,
+}
+
+impl Mwpm {
+    pub fn new(m: usize) -> Self {
+        Self {
+            m: Some(m),
+            decoding_graph: None,
+        }
+    }
+
+    /// 厳密なMWPM
+    pub fn exact() -> Self {
+        Self {
+            m: None,
+            decoding_graph: None,
+        }
+    }
+}
+
+impl Decoder for Mwpm {
+    fn decoding_graph_mut(&mut self) -> &mut Option<DecodingGraph> {
+        &mut self.decoding_graph
+    }
+
+    fn decode(&mut self, graph: &UnGraph) -> Vec<(i32, i32)> {
+        match self.m {
+            Some(m) => decode(graph, m),
+            None => decode_exact(graph),
+        }
+    }
 }
@@ -0,0 +1,780 @@
+use crate::decoder::{mwpm, DecodingGraph, Decoder};
+use crate::qubit_graph::ungraph::UnGraph;
+
+use hashbrown::HashMap;
+use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+type Coord = (i32, i32, i32);
+
+/// edge weightを整数にするときの倍率
+/// weightを偶数にしておくと, 全てのeventが整数の時刻に起こる
+const WEIGHT_SCALE: f32 = 10000.0;
+
+fn discretize(weight: f32) -> i64 {
+    2 * (weight * WEIGHT_SCALE).round() as i64
+}
+
+/// 2つのdetection event (またはdetection eventとboundary) を結ぶpath
+#[derive(Clone, Debug)]
+struct Edge {
+    path: Vec<Coord>,
+}
+
+impl Edge {
+    fn source(&self) -> Coord {
+        self.path[0]
+    }
+
+    fn target(&self) -> Coord {
+        self.path[self.path.len() - 1]
+    }
+
+    fn reversed(&self) -> Edge {
+        Edge {
+            path: self.path.iter().rev().copied().collect(),
+        }
+    }
+
+    /// selfの終点から続くotherを繋げる
+    fn join(&self, other: &Edge) -> Edge {
+        debug_assert_eq!(self.target(), other.source());
+        Edge {
+            path: self
+                .path
+                .iter()
+                .chain(other.path.iter().skip(1))
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Partner {
+    Region(usize),
+    Boundary,
+}
+
+/// detection eventから育てた領域 (trivial region) またはblossom
+#[derive(Debug)]
+struct Region {
+    /// 半径は radius + slope * (time - since)
+    radius: i64,
+    since: i64,
+    /// 外側の木のnodeは1, 内側は-1, matchingしたregionとblossomの子は0
+    slope: i64,
+    blossom_parent: Option<usize>,
+    /// blossomの子 (cycleの順) と次の子へのedge
+    blossom_children: Vec<(usize, Edge)>,
+    /// このregionが直接到達したnode (到達した順)
+    shell: Vec<Coord>,
+    /// trivial regionのdetection event
+    source: Option<Coord>,
+    tree_node: Option<usize>,
+    /// 木に含まれないregionのmatching
+    matched: Option<(Partner, Edge)>,
+    alive: bool,
+    version: u64,
+}
+
+impl Region {
+    fn new(source: Option<Coord>, time: i64, blossom_children: Vec<(usize, Edge)>) -> Self {
+        Self {
+            radius: 0,
+            since: time,
+            slope: 1,
+            blossom_parent: None,
+            blossom_children,
+            shell: source.into_iter().collect(),
+            source,
+            tree_node: None,
+            matched: None,
+            alive: true,
+            version: 0,
+        }
+    }
+}
+
+/// alternating treeのnode (innerとそれにmatchingしたouterの組, rootはouterのみ)
+#[derive(Debug)]
+struct TreeNode {
+    inner: Option<usize>,
+    outer: usize,
+    /// innerからouterへのedge
+    inner_to_outer: Option<Edge>,
+    /// 親のnodeと, innerから親のouterへのedge
+    parent: Option<(usize, Edge)>,
+    children: Vec<usize>,
+}
+
+/// detector graphのnodeに到達したregionの情報
+#[derive(Debug)]
+struct NodeState {
+    region: Option<usize>,
+    /// 到達したときのregionの半径
+    arrival: i64,
+    /// どのnodeから到達したか (detection eventでは自身)
+    predecessor: Coord,
+    version: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Event {
+    /// nodeと隣のnodeの間のedgeがtightになる
+    Node(Coord, u64),
+    /// shrinkしているregionのnodeを手放す, またはregionの半径が0になる
+    Shrink(usize, u64),
+}
+
+/// detector graph上でregionを育てて, detection eventの最小重み完全マッチングを求める
+struct Matcher<'a> {
+    graph: &'a UnGraph,
+    time: i64,
+    regions: Vec<Region>,
+    tree_nodes: Vec<TreeNode>,
+    nodes: HashMap<Coord, NodeState>,
+    /// detection eventのtrivial region
+    source_region: HashMap<Coord, usize>,
+    queue: BinaryHeap<Reverse<(i64, u64, Event)>>,
+    sequence: u64,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(graph: &'a UnGraph) -> Self {
+        let mut matcher = Self {
+            graph,
+            time: 0,
+            regions: Vec::new(),
+            tree_nodes: Vec::new(),
+            nodes: HashMap::new(),
+            source_region: HashMap::new(),
+            queue: BinaryHeap::new(),
+            sequence: 0,
+        };
+
+        // boundaryのdefectは使わず, 全てのregionがboundaryとmatchingできるようにする
+        let sources: Vec<_> = graph
+            .iter_classical_register()
+            .filter(|&(coord, &defect)| defect == 1 && !matcher.is_boundary(coord))
+            .map(|(&coord, _)| coord)
+            .sorted()
+            .collect();
+        for source in sources {
+            let region = matcher.regions.len();
+            let mut r = Region::new(Some(source), 0, Vec::new());
+            r.tree_node = Some(matcher.tree_nodes.len());
+            matcher.regions.push(r);
+            matcher.tree_nodes.push(TreeNode {
+                inner: None,
+                outer: region,
+                inner_to_outer: None,
+                parent: None,
+                children: Vec::new(),
+            });
+            matcher.source_region.insert(source, region);
+            matcher.nodes.insert(
+                source,
+                NodeState {
+                    region: Some(region),
+                    arrival: 0,
+                    predecessor: source,
+                    version: 0,
+                },
+            );
+            matcher.schedule_node(source);
+        }
+        matcher
+    }
+
+    fn is_boundary(&self, coord: &Coord) -> bool {
+        self.graph.is_boundary(coord).unwrap_or(false)
+    }
+
+    fn weight(&self, u: Coord, v: Coord) -> i64 {
+        discretize(self.graph.edge_weight(&(u, v)).unwrap())
+    }
+
+    fn radius(&self, region: usize) -> i64 {
+        let r = &self.regions[region];
+        r.radius + r.slope * (self.time - r.since)
+    }
+
+    fn set_slope(&mut self, region: usize, slope: i64) {
+        let radius = self.radius(region);
+        let r = &mut self.regions[region];
+        r.radius = radius;
+        r.since = self.time;
+        r.slope = slope;
+    }
+
+    fn top(&self, mut region: usize) -> usize {
+        while let Some(parent) = self.regions[region].blossom_parent {
+            region = parent;
+        }
+        region
+    }
+
+    /// nodeからregionの境界までの距離
+    fn local_radius(&self, v: Coord) -> i64 {
+        let state = &self.nodes[&v];
+        let mut region = state.region;
+        let mut radius = -state.arrival;
+        while let Some(r) = region {
+            radius += self.radius(r);
+            region = self.regions[r].blossom_parent;
+        }
+        radius
+    }
+
+    fn push(&mut self, time: i64, event: Event) {
+        self.sequence += 1;
+        self.queue.push(Reverse((time, self.sequence, event)));
+    }
+
+    /// node v (regionはtop) とnの間のedgeがtightになる時刻
+    /// どちらかのregionがgrowしていて, 近づく速さが正のときだけ衝突する
+    fn collision_time(&self, v: Coord, top: usize, radius: i64, n: Coord) -> Option<i64> {
+        let w = self.weight(v, n);
+        let slope = self.regions[top].slope;
+        let other = match self.nodes.get(&n).and_then(|state| state.region) {
+            Some(r) if !self.is_boundary(&n) => Some(self.top(r)),
+            _ => None,
+        };
+        match other {
+            None => (slope == 1).then(|| self.time + (w - radius).max(0)),
+            Some(other) if other == top => None,
+            Some(other) => {
+                let slack = (w - radius - self.local_radius(n)).max(0);
+                match slope + self.regions[other].slope {
+                    2 => {
+                        debug_assert!(slack % 2 == 0, "collision at half-integer time");
+                        Some(self.time + (slack + 1) / 2)
+                    }
+                    1 => Some(self.time + slack),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn schedule_node(&mut self, v: Coord) {
+        let state = self.nodes.get_mut(&v).unwrap();
+        state.version += 1;
+        let (version, region) = (state.version, state.region);
+        let Some(region) = region else {
+            return;
+        };
+        let top = self.top(region);
+        if self.regions[top].slope == -1 {
+            return;
+        }
+        let radius = self.local_radius(v);
+        let next = self
+            .graph
+            .neighbors(&v)
+            .unwrap()
+            .iter()
+            .filter_map(|&n| self.collision_time(v, top, radius, n))
+            .min();
+        if let Some(time) = next {
+            self.push(time, Event::Node(v, version));
+        }
+    }
+
+    fn schedule_shrink(&mut self, region: usize) {
+        self.regions[region].version += 1;
+        let r = &self.regions[region];
+        if !r.alive || r.blossom_parent.is_some() || r.slope != -1 {
+            return;
+        }
+        let radius = self.radius(region);
+        let time = match r.shell.last() {
+            Some(v) if Some(*v) != r.source => self.time + (radius - self.nodes[v].arrival).max(0),
+            _ => self.time + radius.max(0),
+        };
+        self.push(time, Event::Shrink(region, r.version));
+    }
+
+    /// regionとその子孫のnode
+    fn region_nodes(&self, region: usize) -> Vec<Coord> {
+        let mut nodes = Vec::new();
+        let mut stack = vec![region];
+        while let Some(r) = stack.pop() {
+            nodes.extend(self.regions[r].shell.iter().copied());
+            stack.extend(self.regions[r].blossom_children.iter().map(|(c, _)| *c));
+        }
+        nodes
+    }
+
+    /// slopeが変わったregionのeventを作り直す
+    fn reschedule_region(&mut self, region: usize) {
+        for v in self.region_nodes(region) {
+            self.schedule_node(v);
+        }
+        self.schedule_shrink(region);
+    }
+
+    /// vからdetection eventまでのpath
+    fn chain(&self, v: Coord) -> Vec<Coord> {
+        let mut path = vec![v];
+        let mut coord = v;
+        loop {
+            let predecessor = self.nodes[&coord].predecessor;
+            if predecessor == coord {
+                break;
+            }
+            path.push(predecessor);
+            coord = predecessor;
+        }
+        path
+    }
+
+    fn run(&mut self) {
+        while let Some(Reverse((time, _, event))) = self.queue.pop() {
+            match event {
+                Event::Node(v, version) => {
+                    if self.nodes[&v].version == version {
+                        self.time = time;
+                        self.process_node(v);
+                    }
+                }
+                Event::Shrink(region, version) => {
+                    if self.regions[region].version == version {
+                        self.time = time;
+                        self.process_shrink(region);
+                    }
+                }
+            }
+        }
+    }
+
+    fn process_node(&mut self, v: Coord) {
+        let top = self.top(self.nodes[&v].region.unwrap());
+        let radius = self.local_radius(v);
+        let graph = self.graph;
+        let hit = graph
+            .neighbors(&v)
+            .unwrap()
+            .iter()
+            .find(|&&n| self.collision_time(v, top, radius, n) == Some(self.time));
+        if let Some(&n) = hit {
+            let mut path = self.chain(v);
+            path.reverse();
+            if self.is_boundary(&n) {
+                path.push(n);
+                self.region_hit_boundary(top, Edge { path });
+            } else {
+                match self.nodes.get(&n).and_then(|state| state.region) {
+                    None => self.grow(top, v, n),
+                    Some(r) => {
+                        path.extend(self.chain(n));
+                        let (other, edge) = (self.top(r), Edge { path });
+                        // growしている方から衝突させる
+                        if self.regions[top].slope == 1 {
+                            self.region_hit_region(top, other, edge);
+                        } else {
+                            self.region_hit_region(other, top, edge.reversed());
+                        }
+                    }
+                }
+            }
+        }
+        self.schedule_node(v);
+    }
+
+    fn process_shrink(&mut self, region: usize) {
+        let r = &self.regions[region];
+        match r.shell.last() {
+            Some(&v) if Some(v) != r.source => {
+                self.release(region, v);
+                self.schedule_shrink(region);
+            }
+            _ if !r.blossom_children.is_empty() => self.shatter(region),
+            _ => self.implode(region),
+        }
+    }
+
+    /// regionが空のnode nに到達する
+    fn grow(&mut self, region: usize, v: Coord, n: Coord) {
+        let arrival = self.radius(region);
+        let state = self.nodes.entry(n).or_insert(NodeState {
+            region: None,
+            arrival,
+            predecessor: v,
+            version: 0,
+        });
+        state.region = Some(region);
+        state.arrival = arrival;
+        state.predecessor = v;
+        self.regions[region].shell.push(n);
+        self.schedule_node(n);
+    }
+
+    /// shrinkしているregionがnode vを手放す
+    fn release(&mut self, region: usize, v: Coord) {
+        self.regions[region].shell.pop();
+        self.nodes.get_mut(&v).unwrap().region = None;
+        self.schedule_node(v);
+        // 隣のregionはvにgrowできるようになる
+        let graph = self.graph;
+        for &n in graph.neighbors(&v).unwrap().iter() {
+            if self
+                .nodes
+                .get(&n)
+                .is_some_and(|state| state.region.is_some())
+            {
+                self.schedule_node(n);
+            }
+        }
+    }
+
+    fn region_hit_boundary(&mut self, region: usize, edge: Edge) {
+        let node = self.regions[region].tree_node.unwrap();
+        self.augment(node, Partner::Boundary, edge);
+    }
+
+    /// growしているregion aがregion bに衝突する
+    fn region_hit_region(&mut self, a: usize, b: usize, edge: Edge) {
+        let node_a = self.regions[a].tree_node.unwrap();
+        match (self.regions[b].tree_node, self.regions[b].matched.clone()) {
+            (Some(node_b), _) => {
+                if self.ancestors(node_a).last() == self.ancestors(node_b).last() {
+                    self.form_blossom(a, b, edge);
+                } else {
+                    self.augment(node_a, Partner::Region(b), edge.clone());
+                    self.augment(node_b, Partner::Region(a), edge.reversed());
+                }
+            }
+            (None, Some((Partner::Boundary, _))) => {
+                self.regions[b].matched = Some((Partner::Region(a), edge.reversed()));
+                self.augment(node_a, Partner::Region(b), edge);
+            }
+            (None, Some((Partner::Region(c), match_edge))) => {
+                // bとcをaの木に加える
+                self.regions[b].matched = None;
+                self.regions[c].matched = None;
+                let node = self.tree_nodes.len();
+                self.tree_nodes.push(TreeNode {
+                    inner: Some(b),
+                    outer: c,
+                    inner_to_outer: Some(match_edge),
+                    parent: Some((node_a, edge.reversed())),
+                    children: Vec::new(),
+                });
+                self.tree_nodes[node_a].children.push(node);
+                self.regions[b].tree_node = Some(node);
+                self.regions[c].tree_node = Some(node);
+                self.set_slope(b, -1);
+                self.set_slope(c, 1);
+                self.reschedule_region(b);
+                self.reschedule_region(c);
+            }
+            (None, None) => unreachable!("region {} is neither matched nor in a tree", b),
+        }
+    }
+
+    /// nodeから根までのnode
+    fn ancestors(&self, mut node: usize) -> Vec<usize> {
+        let mut ancestors = vec![node];
+        while let Some((parent, _)) = self.tree_nodes[node].parent {
+            ancestors.push(parent);
+            node = parent;
+        }
+        ancestors
+    }
+
+    fn tree_members(&self, root: usize) -> Vec<usize> {
+        let mut members = Vec::new();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            members.push(node);
+            stack.extend(self.tree_nodes[node].children.iter().copied());
+        }
+        members
+    }
+
+    fn set_match(&mut self, region: usize, partner: Partner, edge: Edge) {
+        self.regions[region].matched = Some((partner, edge));
+    }
+
+    /// nodeのouterをpartnerとmatchingさせ, 根までのpathに沿ってmatchingを入れ替えて木を解体する
+    fn augment(&mut self, node: usize, partner: Partner, edge: Edge) {
+        let root = *self.ancestors(node).last().unwrap();
+        let members = self.tree_members(root);
+
+        for &n in members.iter() {
+            let TreeNode {
+                inner,
+                outer,
+                inner_to_outer,
+                ..
+            } = &self.tree_nodes[n];
+            if let (Some(inner), Some(edge)) = (*inner, inner_to_outer.clone()) {
+                let outer = *outer;
+                self.set_match(outer, Partner::Region(inner), edge.reversed());
+                self.set_match(inner, Partner::Region(outer), edge);
+            }
+        }
+
+        self.set_match(self.tree_nodes[node].outer, partner, edge);
+        let mut n = node;
+        while let Some((parent, edge)) = self.tree_nodes[n].parent.clone() {
+            let inner = self.tree_nodes[n].inner.unwrap();
+            let outer = self.tree_nodes[parent].outer;
+            self.set_match(outer, Partner::Region(inner), edge.reversed());
+            self.set_match(inner, Partner::Region(outer), edge);
+            n = parent;
+        }
+
+        for &n in members.iter() {
+            let regions = [self.tree_nodes[n].inner, Some(self.tree_nodes[n].outer)];
+            for region in regions.into_iter().flatten() {
+                self.regions[region].tree_node = None;
+                self.set_slope(region, 0);
+                self.reschedule_region(region);
+            }
+        }
+    }
+
+    /// 同じ木のouter a, bが衝突したとき, 共通の祖先までのcycleをblossomにする
+    fn form_blossom(&mut self, a: usize, b: usize, edge: Edge) {
+        let ancestors_a = self.ancestors(self.regions[a].tree_node.unwrap());
+        let ancestors_b = self.ancestors(self.regions[b].tree_node.unwrap());
+        let lca = *ancestors_a
+            .iter()
+            .find(|n| ancestors_b.contains(n))
+            .unwrap();
+        let path_a: Vec<_> = ancestors_a.into_iter().take_while(|&n| n != lca).collect();
+        let path_b: Vec<_> = ancestors_b.into_iter().take_while(|&n| n != lca).collect();
+
+        // bから共通の祖先まで上り, aまで下る
+        let mut cycle = Vec::new();
+        for &n in path_b.iter() {
+            let node = &self.tree_nodes[n];
+            cycle.push((node.outer, node.inner_to_outer.as_ref().unwrap().reversed()));
+            cycle.push((node.inner.unwrap(), node.parent.as_ref().unwrap().1.clone()));
+        }
+        let mut current = self.tree_nodes[lca].outer;
+        for &n in path_a.iter().rev() {
+            let node = &self.tree_nodes[n];
+            cycle.push((current, node.parent.as_ref().unwrap().1.reversed()));
+            cycle.push((node.inner.unwrap(), node.inner_to_outer.clone().unwrap()));
+            current = node.outer;
+        }
+        debug_assert_eq!(current, a);
+        cycle.push((current, edge));
+
+        let blossom = self.regions.len();
+        for &(child, _) in cycle.iter() {
+            self.set_slope(child, 0);
+            let c = &mut self.regions[child];
+            c.blossom_parent = Some(blossom);
+            c.tree_node = None;
+            c.version += 1;
+        }
+        let mut region = Region::new(None, self.time, cycle);
+        region.tree_node = Some(lca);
+        self.regions.push(region);
+
+        // pathのnodeの子を共通の祖先に付け替える
+        let path: Vec<_> = path_a.into_iter().chain(path_b).collect();
+        let mut children: Vec<_> = self.tree_nodes[lca]
+            .children
+            .iter()
+            .copied()
+            .filter(|c| !path.contains(c))
+            .collect();
+        for &n in path.iter() {
+            for c in self.tree_nodes[n].children.clone() {
+                if !path.contains(&c) {
+                    self.tree_nodes[c].parent.as_mut().unwrap().0 = lca;
+                    children.push(c);
+                }
+            }
+        }
+        self.tree_nodes[lca].children = children;
+        self.tree_nodes[lca].outer = blossom;
+
+        self.reschedule_region(blossom);
+    }
+
+    /// 半径が0になった内側のblossomを子に戻す
+    fn shatter(&mut self, blossom: usize) {
+        let node = self.regions[blossom].tree_node.unwrap();
+        let (parent_node, parent_edge) = self.tree_nodes[node].parent.clone().unwrap();
+        let match_edge = self.tree_nodes[node].inner_to_outer.clone().unwrap();
+        let children = std::mem::take(&mut self.regions[blossom].blossom_children);
+        self.regions[blossom].alive = false;
+        self.regions[blossom].tree_node = None;
+        for &(child, _) in children.iter() {
+            self.regions[child].blossom_parent = None;
+        }
+
+        let k = children.len();
+        let position = |matcher: &Self, source: Coord| {
+            let child = matcher.top(matcher.source_region[&source]);
+            children.iter().position(|&(c, _)| c == child).unwrap()
+        };
+        let i_parent = position(self, parent_edge.source());
+        let i_match = position(self, match_edge.source());
+        // 親とつながる子からmatchingした子まで, 偶数本のedgeで進む向き
+        let forward = ((i_match + k - i_parent) % k).is_multiple_of(2);
+        let step = |i: usize| {
+            if forward {
+                (i + 1) % k
+            } else {
+                (i + k - 1) % k
+            }
+        };
+        let edge_from = |i: usize| match forward {
+            true => children[i].1.clone(),
+            false => children[(i + k - 1) % k].1.reversed(),
+        };
+
+        // 木に残る子のpath
+        let mut path = vec![i_parent];
+        while *path.last().unwrap() != i_match {
+            path.push(step(*path.last().unwrap()));
+        }
+        let mut parent = (parent_node, parent_edge);
+        self.tree_nodes[parent_node].children.retain(|&c| c != node);
+        for pair in path[..path.len() - 1].chunks_exact(2) {
+            let (inner, outer) = (children[pair[0]].0, children[pair[1]].0);
+            let new = self.tree_nodes.len();
+            self.tree_nodes.push(TreeNode {
+                inner: Some(inner),
+                outer,
+                inner_to_outer: Some(edge_from(pair[0])),
+                parent: Some(parent),
+                children: Vec::new(),
+            });
+            let parent_node = self.tree_nodes[new].parent.as_ref().unwrap().0;
+            self.tree_nodes[parent_node].children.push(new);
+            self.regions[inner].tree_node = Some(new);
+            self.regions[outer].tree_node = Some(new);
+            self.set_slope(inner, -1);
+            self.set_slope(outer, 1);
+            parent = (new, edge_from(pair[1]).reversed());
+        }
+        let inner = children[i_match].0;
+        self.tree_nodes[node].inner = Some(inner);
+        self.tree_nodes[parent.0].children.push(node);
+        self.tree_nodes[node].parent = Some(parent);
+        self.regions[inner].tree_node = Some(node);
+        self.set_slope(inner, -1);
+
+        // 残りの子は隣同士でmatchingする
+        let mut i = step(i_match);
+        while i != i_parent {
+            let j = step(i);
+            let edge = edge_from(i);
+            let (u, v) = (children[i].0, children[j].0);
+            self.set_match(u, Partner::Region(v), edge.clone());
+            self.set_match(v, Partner::Region(u), edge.reversed());
+            i = step(j);
+        }
+
+        for (child, _) in children {
+            self.reschedule_region(child);
+        }
+    }
+
+    /// 半径が0になった内側のtrivial regionを挟んで, 親と子のouterが衝突する
+    fn implode(&mut self, region: usize) {
+        let node = self.regions[region].tree_node.unwrap();
+        let (parent_node, parent_edge) = self.tree_nodes[node].parent.clone().unwrap();
+        let match_edge = self.tree_nodes[node].inner_to_outer.clone().unwrap();
+        let (outer, parent_outer) = (
+            self.tree_nodes[node].outer,
+            self.tree_nodes[parent_node].outer,
+        );
+        self.region_hit_region(
+            outer,
+            parent_outer,
+            match_edge.reversed().join(&parent_edge),
+        );
+    }
+
+    /// blossomを展開して, sourceから入るmatching以外のpathを集める
+    fn expand(&self, region: usize, source: Coord, paths: &mut Vec<Vec<Coord>>) {
+        let children = &self.regions[region].blossom_children;
+        if children.is_empty() {
+            return;
+        }
+        let mut child = self.source_region[&source];
+        while self.regions[child].blossom_parent != Some(region) {
+            child = self.regions[child].blossom_parent.unwrap();
+        }
+        let k = children.len();
+        let i = children.iter().position(|&(c, _)| c == child).unwrap();
+        self.expand(child, source, paths);
+        for j in (1..k).step_by(2).map(|j| (i + j) % k) {
+            let (u, edge) = &children[j];
+            let v = children[(j + 1) % k].0;
+            paths.push(edge.path.clone());
+            self.expand(*u, edge.source(), paths);
+            self.expand(v, edge.target(), paths);
+        }
+    }
+
+    fn matched_paths(&self) -> Vec<Vec<Coord>> {
+        let mut paths = Vec::new();
+        for (index, region) in self.regions.iter().enumerate() {
+            if !region.alive || region.blossom_parent.is_some() {
+                continue;
+            }
+            match region.matched {
+                Some((Partner::Boundary, ref edge)) => {
+                    paths.push(edge.path.clone());
+                    self.expand(index, edge.source(), &mut paths);
+                }
+                Some((Partner::Region(other), ref edge)) => {
+                    if index < other {
+                        paths.push(edge.path.clone());
+                        self.expand(index, edge.source(), &mut paths);
+                        self.expand(other, edge.target(), &mut paths);
+                    }
+                }
+                None => panic!("no perfect matching for region {}", index),
+            }
+        }
+        paths
+    }
+}
+
+/// matchingしたdetection event同士 (またはdetection eventとboundary) を結ぶpath
+pub fn matched_paths(graph: &UnGraph) -> Vec<Vec<(i32, i32, i32)>> {
+    let mut matcher = Matcher::new(graph);
+    matcher.run();
+    matcher.matched_paths()
+}
+
+/// decode
+pub fn decode(graph: &UnGraph) -> Vec<(i32, i32)> {
+    let paths = matched_paths(graph);
+    mwpm::decide_correction_qubit(graph, paths.iter())
+}
+
+/// detector graph上でregionを育てるsparse blossomによるMWPM decoder
+#[derive(Debug, Clone, Default)]
+pub struct SparseBlossom {
+    decoding_graph: Option<DecodingGraph>,
+}
+
+impl SparseBlossom {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Decoder for SparseBlossom {
+    fn decoding_graph_mut(&mut self) -> &mut Option<DecodingGraph> {
+        &mut self.decoding_graph
+    }
+
+    fn decode(&mut self, graph: &UnGraph) -> Vec<(i32, i32)> {
+        decode(graph)
+    }
+}
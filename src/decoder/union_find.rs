@@ -0,0 +1,340 @@
+use crate::decoder::{mwpm, DecodingGraph, Decoder};
+use crate::qubit_graph::ungraph::UnGraph;
+
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+type Coord = (i32, i32, i32);
+type Edge = (Coord, Coord);
+
+/// edge weightを整数にするときの倍率
+const WEIGHT_SCALE: f32 = 10000.0;
+
+fn discretize(weight: f32) -> i64 {
+    (weight * WEIGHT_SCALE).round() as i64
+}
+
+fn edge_key(u: Coord, v: Coord) -> (Coord, Coord) {
+    if u < v {
+        (u, v)
+    } else {
+        (v, u)
+    }
+}
+
+/// union-findのcluster (rootのnodeが持つ)
+#[derive(Debug)]
+struct Cluster {
+    /// clusterに含まれるdefectの数の偶奇
+    parity: u8,
+    has_boundary: bool,
+    nodes: Vec<Coord>,
+    /// clusterの内側のnodeから外側へのedge (併合したときにつなげ, 内側になったedgeはupdate_boundaryで取り除く)
+    boundary: Vec<(Coord, Coord)>,
+}
+
+/// edgeの育ち具合 (updatedの時刻でのsupportと, それ以降に単位時間あたり育つ量)
+#[derive(Debug, Default)]
+struct EdgeGrowth {
+    support: i64,
+    updated: i64,
+    /// 両端のactiveなclusterの数
+    rate: i64,
+    /// rateを変えるたびに増やし, queueに残った古い予定を無視する
+    version: u32,
+}
+
+/// detector graph上でclusterを育てるunion-find decoder
+struct ClusterGrowth<'a> {
+    graph: &'a UnGraph,
+    parent: HashMap<Coord, Coord>,
+    clusters: HashMap<Coord, Cluster>,
+    /// edgeがどれだけ育ったか (weightに達するとclusterがつながる)
+    edges: HashMap<(Coord, Coord), EdgeGrowth>,
+    /// edgeが育ち切る時刻の順のqueue (時刻, edge, version)
+    queue: BinaryHeap<Reverse<(i64, Edge, u32)>>,
+    time: i64,
+}
+
+impl<'a> ClusterGrowth<'a> {
+    fn new(graph: &'a UnGraph) -> Self {
+        let mut growth = Self {
+            graph,
+            parent: HashMap::new(),
+            clusters: HashMap::new(),
+            edges: HashMap::new(),
+            queue: BinaryHeap::new(),
+            time: 0,
+        };
+        // boundaryのdefectは使わない
+        let defects: Vec<_> = graph
+            .iter_classical_register()
+            .filter(|&(coord, &defect)| defect == 1 && !growth.is_boundary(coord))
+            .map(|(&coord, _)| coord)
+            .sorted()
+            .collect();
+        for defect in defects {
+            growth.add_node(defect, 1);
+        }
+        growth
+    }
+
+    fn is_boundary(&self, coord: &Coord) -> bool {
+        self.graph.is_boundary(coord).unwrap_or(false)
+    }
+
+    fn weight(&self, u: Coord, v: Coord) -> i64 {
+        discretize(self.graph.edge_weight(&(u, v)).unwrap())
+    }
+
+    fn add_node(&mut self, node: Coord, parity: u8) {
+        let is_boundary = self.is_boundary(&node);
+        // boundary同士のedgeはgrowしない
+        let boundary = self
+            .graph
+            .neighbors(&node)
+            .unwrap()
+            .iter()
+            .filter(|&v| !(is_boundary && self.is_boundary(v)))
+            .map(|&v| (node, v))
+            .collect();
+        self.parent.insert(node, node);
+        self.clusters.insert(
+            node,
+            Cluster {
+                parity,
+                has_boundary: is_boundary,
+                nodes: vec![node],
+                boundary,
+            },
+        );
+    }
+
+    fn find(&mut self, node: Coord) -> Option<Coord> {
+        let mut root = *self.parent.get(&node)?;
+        while self.parent[&root] != root {
+            root = self.parent[&root];
+        }
+        // path compression
+        let mut current = node;
+        while current != root {
+            current = self.parent.insert(current, root).unwrap();
+        }
+        Some(root)
+    }
+
+    /// 小さいclusterを大きいclusterに併合する
+    fn union(&mut self, u: Coord, v: Coord) {
+        for node in [u, v] {
+            if !self.parent.contains_key(&node) {
+                self.add_node(node, 0);
+            }
+        }
+        let (u, v) = (self.find(u).unwrap(), self.find(v).unwrap());
+        if u == v {
+            return;
+        }
+        let (large, small) = match self.clusters[&u].nodes.len() >= self.clusters[&v].nodes.len() {
+            true => (u, v),
+            false => (v, u),
+        };
+        let small_cluster = self.clusters.remove(&small).unwrap();
+        self.parent.insert(small, large);
+        let cluster = self.clusters.get_mut(&large).unwrap();
+        cluster.parity ^= small_cluster.parity;
+        cluster.has_boundary |= small_cluster.has_boundary;
+        cluster.nodes.extend(small_cluster.nodes);
+        cluster.boundary.extend(small_cluster.boundary);
+    }
+
+    /// defectの数が奇数でboundaryに触れていないclusterだけがgrowする
+    fn is_active(&self, root: &Coord) -> bool {
+        let cluster = &self.clusters[root];
+        cluster.parity == 1 && !cluster.has_boundary
+    }
+
+    /// edgeが育つ速さを現在の時刻から変え, 育ち切る時刻をqueueに入れる
+    fn set_rate(&mut self, edge: Edge, rate: i64) {
+        let weight = self.weight(edge.0, edge.1);
+        let time = self.time;
+        let growth = self.edges.entry(edge).or_default();
+        if growth.rate == rate {
+            return;
+        }
+        growth.support = (growth.support + growth.rate * (time - growth.updated)).min(weight);
+        growth.updated = time;
+        growth.rate = rate;
+        growth.version += 1;
+        if rate > 0 {
+            let remaining = (weight - growth.support).max(0);
+            let finish = time + (remaining + rate - 1) / rate;
+            self.queue.push(Reverse((finish, edge, growth.version)));
+        }
+    }
+
+    /// clusterの外側のedgeの速さを両端のclusterに合わせ, 内側になったedgeは止めて取り除く
+    fn update_boundary(&mut self, root: Coord) {
+        let boundary = std::mem::take(&mut self.clusters.get_mut(&root).unwrap().boundary);
+        let (outer, inner): (Vec<_>, Vec<_>) = boundary
+            .into_iter()
+            .partition(|&(_, v)| self.find(v) != Some(root));
+        for (u, v) in inner {
+            self.set_rate(edge_key(u, v), 0);
+        }
+        let active = self.is_active(&root) as i64;
+        for &(u, v) in outer.iter() {
+            let other = self.find(v).map_or(0, |other| self.is_active(&other) as i64);
+            self.set_rate(edge_key(u, v), active + other);
+        }
+        self.clusters.get_mut(&root).unwrap().boundary = outer;
+    }
+
+    /// 全てのclusterが偶数またはboundaryに触れるまでgrowする
+    ///
+    /// activeなclusterの外側のedgeはweightに比例して同時に育つので, 育ち切る時刻の早いedgeから順につなげる
+    fn run(&mut self) {
+        let roots: Vec<Coord> = self.clusters.keys().copied().sorted().collect();
+        for root in roots {
+            if self.is_active(&root) {
+                self.update_boundary(root);
+            }
+        }
+
+        while let Some(&Reverse((time, _, _))) = self.queue.peek() {
+            self.time = time;
+            let mut grown = Vec::new();
+            while let Some(&Reverse((finish, edge, version))) = self.queue.peek() {
+                if finish != time {
+                    break;
+                }
+                self.queue.pop();
+                let weight = self.weight(edge.0, edge.1);
+                let growth = self.edges.get_mut(&edge).unwrap();
+                if growth.version != version {
+                    continue;
+                }
+                *growth = EdgeGrowth {
+                    support: weight,
+                    updated: time,
+                    rate: 0,
+                    version: version + 1,
+                };
+                grown.push(edge);
+            }
+            grown.sort();
+            for &(u, v) in grown.iter() {
+                self.union(u, v);
+            }
+
+            // 併合したclusterの外側のedgeの速さを更新する
+            let roots: Vec<Coord> = grown
+                .iter()
+                .map(|&(u, _)| self.find(u).unwrap())
+                .sorted()
+                .dedup()
+                .collect();
+            for root in roots {
+                self.update_boundary(root);
+            }
+        }
+    }
+
+    /// 育ち切ったedgeの全域森を作り, 葉から順にdefectを消すedgeを選ぶ
+    fn peel(&self) -> Vec<(Coord, Coord)> {
+        let mut erasure: HashMap<Coord, Vec<Coord>> = HashMap::new();
+        for (&(u, v), growth) in self.edges.iter() {
+            if growth.support == self.weight(u, v) {
+                erasure.entry(u).or_default().push(v);
+                erasure.entry(v).or_default().push(u);
+            }
+        }
+
+        // boundaryのnodeを根にして, defectがboundaryに吸収されるようにする
+        let mut roots: Vec<Coord> = erasure.keys().copied().sorted().collect();
+        roots.sort_by_key(|coord| !self.is_boundary(coord));
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        let mut tree_parent = HashMap::new();
+        for root in roots {
+            if !visited.insert(root) {
+                continue;
+            }
+            let mut queue = VecDeque::from([root]);
+            while let Some(u) = queue.pop_front() {
+                order.push(u);
+                for &v in erasure[&u].iter().sorted() {
+                    if self.is_boundary(&u) && self.is_boundary(&v) {
+                        continue;
+                    }
+                    if visited.insert(v) {
+                        tree_parent.insert(v, u);
+                        queue.push_back(v);
+                    }
+                }
+            }
+        }
+
+        let mut defect: HashSet<Coord> = self
+            .clusters
+            .values()
+            .flat_map(|cluster| cluster.nodes.iter())
+            .filter(|&coord| !self.is_boundary(coord))
+            .filter(|&coord| self.graph.get_register(coord) == Some(1))
+            .copied()
+            .collect();
+        let mut correction = Vec::new();
+        for &v in order.iter().rev() {
+            if !defect.remove(&v) {
+                continue;
+            }
+            if let Some(&u) = tree_parent.get(&v) {
+                correction.push((v, u));
+                if !self.is_boundary(&u) && !defect.remove(&u) {
+                    defect.insert(u);
+                }
+            }
+        }
+        debug_assert!(defect.is_empty(), "unpaired defects {:?}", defect);
+        correction
+    }
+}
+
+/// defectを消すedge (peelingで選んだedge)
+pub fn correction_edges(graph: &UnGraph) -> Vec<(Coord, Coord)> {
+    let mut growth = ClusterGrowth::new(graph);
+    growth.run();
+    growth.peel()
+}
+
+/// decode
+pub fn decode(graph: &UnGraph) -> Vec<(i32, i32)> {
+    let paths: Vec<_> = correction_edges(graph)
+        .into_iter()
+        .map(|(u, v)| vec![u, v])
+        .collect();
+    mwpm::decide_correction_qubit(graph, paths.iter())
+}
+
+/// clusterをweightに比例して育て, peelingでcorrectionを求めるunion-find decoder
+#[derive(Debug, Clone, Default)]
+pub struct UnionFind {
+    decoding_graph: Option<DecodingGraph>,
+}
+
+impl UnionFind {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Decoder for UnionFind {
+    fn decoding_graph_mut(&mut self) -> &mut Option<DecodingGraph> {
+        &mut self.decoding_graph
+    }
+
+    fn decode(&mut self, graph: &UnGraph) -> Vec<(i32, i32)> {
+        decode(graph)
+    }
+}
@@ -1,3 +1,4 @@
+pub mod circuit;
 pub mod simulator;
 pub mod qec_code;
 pub mod decoder;
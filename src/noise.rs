@@ -1 +1,3 @@
-pub mod noise_model;
\ No newline at end of file
+pub mod calibration;
+pub mod circuit_noise;
+pub mod noise_model;
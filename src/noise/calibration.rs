@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::noise_model::NoiseType;
+
+/// couplerの両端のqubit
+pub type Coupler = ((i32, i32), (i32, i32));
+
+/// qubitごとのキャリブレーションデータ
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QubitCalibration {
+    pub t1: f32,
+    pub t2: f32,
+    pub readout_error: f32,
+    pub gate_1q_fidelity: Option<f32>,
+}
+
+/// 実機のキャリブレーションデータ
+///
+/// ファイルは1行に1つの項目を空白区切りで書く. `#`以降はコメント.
+/// 時間の単位はT1, T2, momentで揃える (例えばμs).
+///
+/// ```text
+/// # qubit x y T1 T2 readout_error [1q gate fidelity]
+/// qubit 0 0 52.1 40.3 0.012
+/// qubit 1 1 48.7 35.0 0.020 0.9995
+/// # coupler x1 y1 x2 y2 CX fidelity
+/// coupler 0 0 1 1 0.991
+/// # 1 momentの時間 (idleノイズの計算に用いる)
+/// moment_time 0.04
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Calibration {
+    pub qubit: HashMap<(i32, i32), QubitCalibration>,
+    /// couplerごとのCX fidelity
+    pub coupler: HashMap<Coupler, f32>,
+    /// 1 momentの時間
+    pub moment_time: f32,
+}
+
+impl Calibration {
+    /// キャリブレーションファイルを読み込む
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// キャリブレーションデータの文字列をパースする
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut calibration = Calibration::default();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}: {}", line_number + 1, message, line),
+                )
+            };
+
+            let mut fields = line.split_whitespace();
+            let key = fields.next().unwrap();
+            let fields: Vec<&str> = fields.collect();
+            // qubit, couplerは先頭の座標が整数
+            let coord_len = match key {
+                "qubit" => 2,
+                "coupler" => 4,
+                _ => 0,
+            }
+            .min(fields.len());
+            let coords: Vec<i32> = fields[..coord_len]
+                .iter()
+                .map(|field| field.parse().map_err(|_| invalid("invalid coordinate")))
+                .collect::<io::Result<_>>()?;
+            let values: Vec<f32> = fields[coord_len..]
+                .iter()
+                .map(|field| field.parse().map_err(|_| invalid("invalid number")))
+                .collect::<io::Result<_>>()?;
+
+            match (key, coords.as_slice(), values.as_slice()) {
+                ("qubit", &[x, y], &[t1, t2, readout_error]) => {
+                    calibration.qubit.insert(
+                        (x, y),
+                        QubitCalibration {
+                            t1,
+                            t2,
+                            readout_error,
+                            gate_1q_fidelity: None,
+                        },
+                    );
+                }
+                ("qubit", &[x, y], &[t1, t2, readout_error, fidelity]) => {
+                    calibration.qubit.insert(
+                        (x, y),
+                        QubitCalibration {
+                            t1,
+                            t2,
+                            readout_error,
+                            gate_1q_fidelity: Some(fidelity),
+                        },
+                    );
+                }
+                ("coupler", &[x1, y1, x2, y2], &[fidelity]) => {
+                    calibration.coupler.insert(((x1, y1), (x2, y2)), fidelity);
+                }
+                ("moment_time", _, &[time]) => calibration.moment_time = time,
+                ("qubit", _, _) | ("coupler", _, _) | ("moment_time", _, _) => {
+                    return Err(invalid("wrong number of values"))
+                }
+                _ => return Err(invalid("unknown key")),
+            }
+        }
+
+        Ok(calibration)
+    }
+}
+
+/// 2q gateのfidelityから脱分極ノイズのerror rateを求める (F = 1 - 4p/5)
+pub fn gate_2q_error_rate(fidelity: f32) -> f32 {
+    5.0 / 4.0 * (1.0 - fidelity)
+}
+
+/// 1q gateのfidelityから脱分極ノイズのerror rateを求める (F = 1 - 2p/3)
+pub fn gate_1q_error_rate(fidelity: f32) -> f32 {
+    3.0 / 2.0 * (1.0 - fidelity)
+}
+
+/// 時間tのT1, T2緩和をPauli twirlしたPauli channel
+pub fn idle_noise(time: f32, t1: f32, t2: f32) -> NoiseType {
+    let p_xy = (1.0 - (-time / t1).exp()) / 4.0;
+    let p_z = (1.0 - (-time / t2).exp()) / 2.0 - p_xy;
+    NoiseType::PauliChannel1(p_xy, p_xy, p_z.max(0.0))
+}
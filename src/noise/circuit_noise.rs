@@ -0,0 +1,430 @@
+use std::collections::BTreeSet;
+
+use super::noise_model::{DeviceErrorMap, NoiseModel, NoiseType};
+use crate::qubit_graph::ungraph::UnGraph;
+use crate::simulator::{pauli::Pauli, Operation};
+
+/// ノイズモデルから決まる各オペレーションのerror rate
+enum NoiseRate<'a> {
+    Uniform {
+        p_1q: f32,
+        p_2q: f32,
+        p_idle: f32,
+        /// 測定・リセット中のidle
+        p_idle_measurement: f32,
+        p_reset: f32,
+        p_measurement: f32,
+        /// data qubitへの脱分極ノイズ
+        p_data: f32,
+        /// data qubitへのノイズを各roundで入れるか
+        data_every_round: bool,
+    },
+    Device(&'a DeviceErrorMap),
+}
+
+impl<'a> NoiseRate<'a> {
+    fn new(model: &'a NoiseModel) -> Self {
+        let circuit =
+            |p_1q, p_2q, p_idle, p_idle_measurement, p_reset, p_measurement| NoiseRate::Uniform {
+                p_1q,
+                p_2q,
+                p_idle,
+                p_idle_measurement,
+                p_reset,
+                p_measurement,
+                p_data: 0.0,
+                data_every_round: false,
+            };
+        let data = |p, data_every_round, p_measurement| NoiseRate::Uniform {
+            p_1q: 0.0,
+            p_2q: 0.0,
+            p_idle: 0.0,
+            p_idle_measurement: 0.0,
+            p_reset: 0.0,
+            p_measurement,
+            p_data: p,
+            data_every_round,
+        };
+
+        match *model {
+            NoiseModel::CodeCapacity(p) => data(p, false, 0.0),
+            NoiseModel::Phenomenological(p, p_m) => data(p, true, p_m),
+            NoiseModel::SD6(p) => circuit(p, p, p, p, p, p),
+            NoiseModel::SI1000(p) => circuit(p / 10.0, p, p / 10.0, 2.0 * p, 2.0 * p, 5.0 * p),
+            NoiseModel::UniformCircuit {
+                p_1q,
+                p_2q,
+                p_idle,
+                p_reset,
+                p_measurement,
+            } => circuit(p_1q, p_2q, p_idle, p_idle, p_reset, p_measurement),
+            NoiseModel::Device(ref error_map) => NoiseRate::Device(error_map),
+        }
+    }
+
+    fn gate_1q(&self, a: usize) -> Option<Operation> {
+        let p = match self {
+            NoiseRate::Uniform { p_1q, .. } => *p_1q,
+            NoiseRate::Device(error_map) => *error_map.gate_1q.get(&a).unwrap_or(&0.0),
+        };
+        (p > 0.0).then_some(Operation::Depolarizing(a, p))
+    }
+
+    fn gate_2q(&self, a: usize, b: usize) -> Option<Operation> {
+        let p = match self {
+            NoiseRate::Uniform { p_2q, .. } => *p_2q,
+            NoiseRate::Device(error_map) => *error_map.gate_2q.get(&(a, b)).unwrap_or(&0.0),
+        };
+        (p > 0.0).then_some(Operation::Depolarizing2(a, b, p))
+    }
+
+    fn idle(&self, a: usize, during_measurement: bool) -> Option<Operation> {
+        match self {
+            NoiseRate::Uniform {
+                p_idle,
+                p_idle_measurement,
+                ..
+            } => {
+                let p = if during_measurement {
+                    *p_idle_measurement
+                } else {
+                    *p_idle
+                };
+                (p > 0.0).then_some(Operation::Depolarizing(a, p))
+            }
+            NoiseRate::Device(error_map) => match error_map.idle.get(&a) {
+                Some(&NoiseType::Depolarizing(p)) if p > 0.0 => Some(Operation::Depolarizing(a, p)),
+                Some(&NoiseType::PauliChannel1(px, py, pz)) if px + py + pz > 0.0 => {
+                    Some(Operation::PauliChannel1(a, px, py, pz))
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// リセット後の状態を反転させるエラー
+    fn reset(&self, a: usize, basis: Pauli) -> Option<Operation> {
+        let p = match self {
+            NoiseRate::Uniform { p_reset, .. } => *p_reset,
+            NoiseRate::Device(error_map) => *error_map.reset.get(&a).unwrap_or(&0.0),
+        };
+        (p > 0.0).then_some(match basis {
+            Pauli::X => Operation::PauliChannel1(a, 0.0, 0.0, p),
+            _ => Operation::PauliChannel1(a, p, 0.0, 0.0),
+        })
+    }
+
+    /// 測定エラー (Pauli積の測定では各qubitのうち最大のもの)
+    fn measurement(&self, qubits: &[usize]) -> f32 {
+        match self {
+            NoiseRate::Uniform { p_measurement, .. } => *p_measurement,
+            NoiseRate::Device(error_map) => qubits
+                .iter()
+                .map(|a| *error_map.measurement.get(a).unwrap_or(&0.0))
+                .fold(0.0, f32::max),
+        }
+    }
+
+    /// data qubitへのノイズ
+    fn data(&self, a: usize) -> Option<Operation> {
+        match self {
+            NoiseRate::Uniform { p_data, .. } if *p_data > 0.0 => {
+                Some(Operation::Depolarizing(a, *p_data))
+            }
+            _ => None,
+        }
+    }
+
+    fn data_every_round(&self) -> bool {
+        match self {
+            NoiseRate::Uniform {
+                data_every_round, ..
+            } => *data_every_round,
+            NoiseRate::Device(_) => false,
+        }
+    }
+}
+
+/// オペレーションの種類
+enum Kind {
+    Gate1,
+    Gate2,
+    Measurement,
+    /// リセット (引数はリセットの基底)
+    Reset(Pauli),
+    MeasurementAndReset(Pauli),
+    /// ノイズや射影など、ノイズを挿入しないもの
+    Other,
+}
+
+fn kind(operation: &Operation) -> Kind {
+    match operation {
+        Operation::H(_)
+        | Operation::S(_)
+        | Operation::X(_)
+        | Operation::Z(_)
+        | Operation::Y(_)
+        | Operation::SDag(_)
+        | Operation::SqrtX(_)
+        | Operation::SqrtXDag(_)
+        | Operation::SqrtY(_)
+        | Operation::SqrtYDag(_)
+        | Operation::T(_)
+        | Operation::TDag(_)
+        | Operation::Feedback(_, _, _) => Kind::Gate1,
+        Operation::CX(_, _)
+        | Operation::CZ(_, _)
+        | Operation::CY(_, _)
+        | Operation::Swap(_, _)
+        | Operation::ISwap(_, _)
+        | Operation::ISwapDag(_, _)
+        | Operation::XCX(_, _)
+        | Operation::XCY(_, _)
+        | Operation::XCZ(_, _)
+        | Operation::YCX(_, _)
+        | Operation::YCY(_, _)
+        | Operation::YCZ(_, _) => Kind::Gate2,
+        Operation::M(_, _) | Operation::MX(_, _) | Operation::MY(_, _) => Kind::Measurement,
+        Operation::MPP(_, _, _) => Kind::Measurement,
+        Operation::R(_) => Kind::Reset(Pauli::Z),
+        Operation::RX(_) => Kind::Reset(Pauli::X),
+        Operation::RY(_) => Kind::Reset(Pauli::Y),
+        Operation::MR(_, _) => Kind::MeasurementAndReset(Pauli::Z),
+        Operation::MRX(_, _) => Kind::MeasurementAndReset(Pauli::X),
+        Operation::MRY(_, _) => Kind::MeasurementAndReset(Pauli::Y),
+        Operation::MToZero(_)
+        | Operation::Depolarizing(_, _)
+        | Operation::PauliChannel1(_, _, _, _)
+        | Operation::Depolarizing2(_, _, _)
+        | Operation::PauliChannel2(_, _, _)
+        | Operation::Detector(_, _)
+        | Operation::ObservableInclude(_, _)
+        | Operation::Tick
+        | Operation::Repeat(_, _) => Kind::Other,
+    }
+}
+
+/// 測定オペレーションのerror rateを書き換える
+fn with_measurement_error(operation: &Operation, p: f32) -> Operation {
+    match operation {
+        Operation::M(a, _) => Operation::M(*a, p),
+        Operation::MR(a, _) => Operation::MR(*a, p),
+        Operation::MX(a, _) => Operation::MX(*a, p),
+        Operation::MY(a, _) => Operation::MY(*a, p),
+        Operation::MRX(a, _) => Operation::MRX(*a, p),
+        Operation::MRY(a, _) => Operation::MRY(*a, p),
+        Operation::MPP(product, sign, _) => Operation::MPP(product.clone(), *sign, p),
+        other => other.clone(),
+    }
+}
+
+/// ノイズのない回路にノイズモデルに従ってノイズを挿入する
+///
+/// 回路は先頭から貪欲にmoment (同じqubitに作用しない, gateのみまたは測定・リセットのみのオペレーションの集まり) に分けられ,
+/// gate・リセットの後にはノイズが, 測定にはmeasurement errorが設定される.
+/// そのmomentで何もしないqubitにはidleノイズが入る.
+/// data qubitへのノイズは最初のmomentの前 (phenomenologicalでは測定の後のmomentの前にも) に入る.
+/// TICKは常にmomentの区切りになり, REPEATブロックのbodyには同じ規則でノイズを挿入する.
+pub fn apply_noise_model(
+    operations: &[Operation],
+    model: &NoiseModel,
+    data_qubit: &[usize],
+) -> Vec<Operation> {
+    let rate = NoiseRate::new(model);
+
+    // idleノイズの対象となるqubit
+    let mut qubits: BTreeSet<usize> = data_qubit.iter().copied().collect();
+    for operation in operations.iter() {
+        qubits.extend(operation.qubits());
+    }
+
+    let mut insert_data_noise = true;
+    insert_noise(
+        operations,
+        &rate,
+        &qubits,
+        data_qubit,
+        &mut insert_data_noise,
+    )
+}
+
+fn insert_noise(
+    operations: &[Operation],
+    rate: &NoiseRate,
+    qubits: &BTreeSet<usize>,
+    data_qubit: &[usize],
+    insert_data_noise: &mut bool,
+) -> Vec<Operation> {
+    // momentに分割
+    // 同じqubitに作用するか, gateと測定・リセットが切り替わるときに次のmomentにする
+    // TICKとREPEATはそれだけで1つのmomentにする
+    let mut moments: Vec<Vec<&Operation>> = vec![Vec::new()];
+    let mut used = BTreeSet::new();
+    let mut moment_is_measurement = None;
+    for operation in operations.iter() {
+        if matches!(operation, Operation::Tick | Operation::Repeat(_, _)) {
+            moments.push(vec![operation]);
+            moments.push(Vec::new());
+            used.clear();
+            moment_is_measurement = None;
+            continue;
+        }
+        let targets = operation.qubits();
+        let is_measurement = match kind(operation) {
+            Kind::Gate1 | Kind::Gate2 => Some(false),
+            Kind::Measurement | Kind::Reset(_) | Kind::MeasurementAndReset(_) => Some(true),
+            Kind::Other => None,
+        };
+        let class_changed = match (moment_is_measurement, is_measurement) {
+            (Some(current), Some(next)) => current != next,
+            _ => false,
+        };
+        if class_changed || targets.iter().any(|a| used.contains(a)) {
+            moments.push(Vec::new());
+            used.clear();
+            moment_is_measurement = None;
+        }
+        used.extend(targets);
+        moment_is_measurement = moment_is_measurement.or(is_measurement);
+        moments.last_mut().unwrap().push(operation);
+    }
+
+    let mut noisy = Vec::new();
+    for moment in moments.into_iter().filter(|moment| !moment.is_empty()) {
+        if let [Operation::Repeat(n, body)] = moment[..] {
+            // 一度だけ入れるdata qubitへのノイズはbodyの外に出す
+            if *insert_data_noise && !rate.data_every_round() {
+                noisy.extend(data_qubit.iter().filter_map(|&a| rate.data(a)));
+                *insert_data_noise = false;
+            }
+            let body = insert_noise(
+                body.operations(),
+                rate,
+                qubits,
+                data_qubit,
+                insert_data_noise,
+            );
+            noisy.push(Operation::Repeat(*n, body.into()));
+            continue;
+        }
+
+        let kinds: Vec<_> = moment.iter().map(|operation| kind(operation)).collect();
+        let has_gate = kinds
+            .iter()
+            .any(|k| matches!(k, Kind::Gate1 | Kind::Gate2 | Kind::Reset(_)));
+        let has_measurement = kinds
+            .iter()
+            .any(|k| matches!(k, Kind::Measurement | Kind::MeasurementAndReset(_)));
+
+        if *insert_data_noise && has_gate {
+            noisy.extend(data_qubit.iter().filter_map(|&a| rate.data(a)));
+            *insert_data_noise = false;
+        }
+
+        let mut used = BTreeSet::new();
+        for (operation, k) in moment.into_iter().zip(kinds.iter()) {
+            let targets = operation.qubits();
+            match *k {
+                Kind::Gate1 => {
+                    noisy.push(operation.clone());
+                    noisy.extend(rate.gate_1q(targets[0]));
+                }
+                Kind::Gate2 => {
+                    noisy.push(operation.clone());
+                    noisy.extend(rate.gate_2q(targets[0], targets[1]));
+                }
+                Kind::Measurement => {
+                    noisy.push(with_measurement_error(
+                        operation,
+                        rate.measurement(&targets),
+                    ));
+                }
+                Kind::Reset(basis) => {
+                    noisy.push(operation.clone());
+                    noisy.extend(rate.reset(targets[0], basis));
+                }
+                Kind::MeasurementAndReset(basis) => {
+                    noisy.push(with_measurement_error(
+                        operation,
+                        rate.measurement(&targets),
+                    ));
+                    noisy.extend(rate.reset(targets[0], basis));
+                }
+                Kind::Other => noisy.push(operation.clone()),
+            }
+            used.extend(targets);
+        }
+
+        // idle noise (ノイズなどしか含まないmomentには入れない)
+        if !has_gate && !has_measurement {
+            continue;
+        }
+        let during_measurement =
+            has_measurement || kinds.iter().any(|k| matches!(k, Kind::Reset(_)));
+        noisy.extend(
+            qubits
+                .iter()
+                .filter(|a| !used.contains(a))
+                .filter_map(|&a| rate.idle(a, during_measurement)),
+        );
+
+        if has_measurement && rate.data_every_round() {
+            *insert_data_noise = true;
+        }
+    }
+
+    noisy
+}
+
+/// qubitの`basis`の測定を反転させるPauliが, ノイズのオペレーションで起こる確率
+fn flip_probability(operation: &Operation, basis: Pauli) -> f32 {
+    let (x, z) = basis.to_bits();
+    let flips = |pauli: Pauli| {
+        let (px, pz) = pauli.to_bits();
+        (px & z) ^ (pz & x) == 1
+    };
+    match *operation {
+        // X, Y, Zのうち2つが反転させる
+        Operation::Depolarizing(_, p) => 2.0 * p / 3.0,
+        Operation::PauliChannel1(_, px, py, pz) => [(Pauli::X, px), (Pauli::Y, py), (Pauli::Z, pz)]
+            .into_iter()
+            .filter(|&(pauli, _)| flips(pauli))
+            .map(|(_, p)| p)
+            .sum(),
+        // 15個のPauliのうち, このqubitの成分が反転させる8個 (相手のqubitの成分は問わない)
+        Operation::Depolarizing2(_, _, p) => 8.0 * p / 15.0,
+        _ => 0.0,
+    }
+}
+
+/// syndrome測定の1roundで, qubitの`basis`の測定を反転させるエラーが奇数回起こる確率
+///
+/// partnerは1roundの4つのCXのmomentでqubitとCXを行うqubit (CXのないmomentではidleノイズが入る).
+/// ancillaは測定・リセットのエラーを, data qubitは測定中のidleノイズとdata qubitへのノイズを含む.
+/// CXで伝搬するエラーは含まないので, 回路を作った後はdetector error modelから求め直す.
+pub fn round_flip_rate(
+    model: &NoiseModel,
+    qubit: usize,
+    partners: &[usize],
+    ancilla: bool,
+    basis: Pauli,
+) -> f32 {
+    let rate = NoiseRate::new(model);
+    let mut noise: Vec<Operation> = partners
+        .iter()
+        .filter_map(|&partner| rate.gate_2q(qubit, partner))
+        .collect();
+    noise.extend((partners.len()..4).filter_map(|_| rate.idle(qubit, false)));
+    let mut p = 0.0;
+    if ancilla {
+        p = rate.measurement(&[qubit]);
+        noise.extend(rate.reset(qubit, basis));
+    } else {
+        noise.extend(rate.idle(qubit, true));
+        noise.extend(rate.data(qubit));
+    }
+    noise
+        .iter()
+        .map(|operation| flip_probability(operation, basis))
+        .fold(p, UnGraph::merge_error_rate)
+}
@@ -1,4 +1,98 @@
+use std::collections::HashMap;
+
 #[derive(Clone, Copy, Debug)]
 pub enum NoiseType {
     Depolarizing(f32),
-}
\ No newline at end of file
+    /// X, Y, Z error with probability px, py, pz
+    PauliChannel1(f32, f32, f32),
+    /// two qubit depolarizing noise over the 15 non-identity Paulis
+    Depolarizing2(f32),
+    /// two qubit Pauli error in the order IX, IY, IZ, XI, ..., ZZ
+    PauliChannel2([f32; 15]),
+}
+/// qubit, couplerごとのerror rate (simulatorのindexで指定する)
+#[derive(Clone, Debug, Default)]
+pub struct DeviceErrorMap {
+    /// 1q gateの脱分極ノイズ
+    pub gate_1q: HashMap<usize, f32>,
+    /// 2q gateの脱分極ノイズ (両方の向きを持つ)
+    pub gate_2q: HashMap<(usize, usize), f32>,
+    /// 1 momentあたりのidleノイズ
+    pub idle: HashMap<usize, NoiseType>,
+    /// 測定エラー
+    pub measurement: HashMap<usize, f32>,
+    /// リセットエラー (キャリブレーションから作る場合は測定エラーと同じ値)
+    pub reset: HashMap<usize, f32>,
+}
+
+impl DeviceErrorMap {
+    /// 2q gateエラーの平均
+    pub fn mean_gate_2q(&self) -> f32 {
+        mean(self.gate_2q.values())
+    }
+
+    /// 測定エラーの平均
+    pub fn mean_measurement(&self) -> f32 {
+        mean(self.measurement.values())
+    }
+}
+
+fn mean<'a>(values: impl ExactSizeIterator<Item = &'a f32>) -> f32 {
+    let len = values.len();
+    if len == 0 {
+        0.0
+    } else {
+        values.sum::<f32>() / len as f32
+    }
+}
+
+/// 回路全体にノイズを挿入するためのノイズモデル
+#[derive(Clone, Debug)]
+pub enum NoiseModel {
+    /// 最初にdata qubitへ脱分極ノイズp, 測定は無ノイズ
+    CodeCapacity(f32),
+    /// 各roundの開始時にdata qubitへ脱分極ノイズp, 測定エラーp_m
+    Phenomenological(f32, f32),
+    /// standard depolarizing circuit noise
+    /// (1q, 2q gate, idle, reset, measurementすべてp)
+    SD6(f32),
+    /// superconducting-inspired circuit noise
+    /// (1q gate, idle: p/10, 2q gate: p, reset: 2p, measurement: 5p, 測定中のidle: 2p)
+    SI1000(f32),
+    /// gateの種類ごとにerror rateを指定する回路ノイズ
+    UniformCircuit {
+        p_1q: f32,
+        p_2q: f32,
+        p_idle: f32,
+        p_reset: f32,
+        p_measurement: f32,
+    },
+    /// qubit, couplerごとのerror rateによる回路ノイズ
+    Device(Box<DeviceErrorMap>),
+}
+
+impl NoiseModel {
+    /// 代表的な物理エラー率
+    pub fn error_rate(&self) -> f32 {
+        match *self {
+            NoiseModel::CodeCapacity(p)
+            | NoiseModel::Phenomenological(p, _)
+            | NoiseModel::SD6(p)
+            | NoiseModel::SI1000(p) => p,
+            NoiseModel::UniformCircuit { p_2q, .. } => p_2q,
+            NoiseModel::Device(ref error_map) => error_map.mean_gate_2q(),
+        }
+    }
+
+    /// 測定エラー率
+    pub fn measurement_error_rate(&self) -> f32 {
+        match *self {
+            NoiseModel::CodeCapacity(_) => 0.0,
+            NoiseModel::Phenomenological(_, p_m) => p_m,
+            NoiseModel::SD6(p) => p,
+            NoiseModel::SI1000(p) => 5.0 * p,
+            NoiseModel::UniformCircuit { p_measurement, .. } => p_measurement,
+            NoiseModel::Device(ref error_map) => error_map.mean_measurement(),
+        }
+    }
+}
@@ -1,14 +1,19 @@
 use itertools::Itertools;
-use std::cell::Cell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::io;
 
-use crate::decoder::mwpm;
-use crate::noise::noise_model::NoiseType;
+use crate::circuit::Circuit;
+use crate::decoder::{mwpm::Mwpm, Decoder};
+use crate::noise::{calibration::Calibration, circuit_noise, noise_model::NoiseModel};
 use crate::qec_code::stabilizer::Stabilizer;
 use crate::qubit_graph::ungraph::UnGraph;
 use crate::qubit_network::QubitNetwork;
-use crate::simulator::{frame::PauliFrame, Type};
+use crate::simulator::{
+    frame::PauliFrame, frame_simulator, measurement_record::MeasurementRecord, pauli::Pauli,
+    Operation, Type,
+};
+
+type Node = (i32, i32, i32);
 
 pub struct RotatedSurfaceCode {
     distance: usize,
@@ -17,17 +22,86 @@ pub struct RotatedSurfaceCode {
     z_stabilizers: Vec<Stabilizer>,
     x_stabilizers: Vec<Stabilizer>,
     data_qubit: Vec<(i32, i32)>,
-    classical_register: Vec<Vec<Rc<Cell<u8>>>>,
+    classical_register: Vec<Vec<u8>>,
     measurement_graph_z: UnGraph,
     measurement_graph_x: UnGraph,
+    /// syndrome graphのnodeとdetectorのindexの対応
+    detector_z: Vec<((i32, i32, i32), usize)>,
+    detector_x: Vec<((i32, i32, i32), usize)>,
+    /// initializeでのXスタビライザーの測定のmeasurement recordでのindex
+    initial_measurement_x: Vec<((i32, i32), usize)>,
+    /// 最後のdata qubitの測定のmeasurement recordでのindex
+    data_measurement: Vec<((i32, i32), usize)>,
     single_round_measurement_graph_z: UnGraph,
     pauli_frame: PauliFrame,
+    noise_model: NoiseModel,
     error_rate: f32,
-    measurement_error_rate: f32,
 }
 
 impl RotatedSurfaceCode {
+    /// phenomenological noiseのrotated surface codeを作成する
     pub fn new(distance: usize, round: usize, p: f32, p_m: f32, seed: u64) -> Self {
+        Self::new_with_noise_model(distance, round, NoiseModel::Phenomenological(p, p_m), seed)
+    }
+
+    /// 指定したノイズモデルのrotated surface codeを作成する
+    pub fn new_with_noise_model(
+        distance: usize,
+        round: usize,
+        noise_model: NoiseModel,
+        seed: u64,
+    ) -> Self {
+        Self::new_with_simulator(distance, round, noise_model, Type::CHPSimulator, seed)
+    }
+
+    /// 指定したノイズモデルとシミュレータのrotated surface codeを作成する
+    pub fn new_with_simulator(
+        distance: usize,
+        round: usize,
+        noise_model: NoiseModel,
+        sim_type: Type,
+        seed: u64,
+    ) -> Self {
+        let p = noise_model.error_rate();
+        Self::new_with_network_noise(distance, round, p, sim_type, seed, |_| noise_model)
+    }
+
+    /// キャリブレーションデータのqubit, couplerごとのerror rateを用いたrotated surface codeを作成する
+    /// (キャリブレーションデータにないqubit, couplerは無ノイズ)
+    /// latticeにないqubit, couplerがあればInvalidDataのエラーを返す
+    pub fn new_with_calibration(
+        distance: usize,
+        round: usize,
+        calibration: &Calibration,
+        seed: u64,
+    ) -> io::Result<Self> {
+        let mut result = Ok(());
+        let code = Self::new_with_network_noise(
+            distance,
+            round,
+            0.0,
+            Type::CHPSimulator,
+            seed,
+            |network| {
+                result = network.load_calibration(calibration);
+                NoiseModel::Device(Box::new(network.device_error_map()))
+            },
+        );
+        result.map(|()| code)
+    }
+
+    /// qubit networkを作成した後にノイズモデルを決める
+    fn new_with_network_noise<F>(
+        distance: usize,
+        round: usize,
+        p_network: f32,
+        sim_type: Type,
+        seed: u64,
+        noise_model: F,
+    ) -> Self
+    where
+        F: FnOnce(&mut QubitNetwork) -> NoiseModel,
+    {
         if distance % 2 == 0 {
             panic!("distance must be odd number.");
         }
@@ -54,34 +128,88 @@ impl RotatedSurfaceCode {
         }
 
         // qubit networkの作成
-        let network = QubitNetwork::new_rotated_planer_lattice_from_vec(
+        let mut network = QubitNetwork::new_rotated_planer_lattice_from_vec(
             data_qubit.clone(),
             measurement_qubit_z.clone(),
             measurement_qubit_x.clone(),
-            p,
-            Type::CHPSimulator,
+            p_network,
+            sim_type,
             seed,
         );
+        let noise_model = noise_model(&mut network);
+        let p = noise_model.error_rate();
+        let p_m = noise_model.measurement_error_rate();
+        // networkの測定はqubitごとの測定エラーを用いるので, 一様なノイズモデルではその値に揃える
+        if !matches!(noise_model, NoiseModel::Device(_)) {
+            let qubits: Vec<_> = network.index_to_sim().keys().copied().collect();
+            for qubit in qubits {
+                network.set_measurement_error_rate(qubit, p_m);
+            }
+        }
+
+        // make stabilizers
+        let z_stabilizers = Self::gen_stabilizer(&measurement_qubit_z, &data_qubit, 'Z');
+        let x_stabilizers = Self::gen_stabilizer(&measurement_qubit_x, &data_qubit, 'X');
 
         // make syndrome graph
-        let measurement_graph_z =
-            Self::gen_measurement_graph(&measurement_qubit_z, round, distance, 'Z', p, seed);
-        let measurement_graph_x =
-            Self::gen_measurement_graph(&measurement_qubit_x, round, distance, 'X', p, seed);
-        let single_round_measurement_graph_z =
-            Self::gen_measurement_graph(&measurement_qubit_z, 1, distance, 'Z', p, seed);
+        // edgeのweightは, そのedgeのdata qubit (空間方向) またはancilla (時間方向) のエラーから求める
+        let index_to_sim = network.index_to_sim();
+        let mut partners: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for stabilizer in z_stabilizers.iter().chain(x_stabilizers.iter()) {
+            for &data in stabilizer.pauli_product().iter().flatten() {
+                partners
+                    .entry(data)
+                    .or_default()
+                    .push(index_to_sim[&stabilizer.ancilla]);
+                partners
+                    .entry(stabilizer.ancilla)
+                    .or_default()
+                    .push(index_to_sim[&data]);
+            }
+        }
+        let flip_rate = |qubit: (i32, i32), basis: Pauli| {
+            circuit_noise::round_flip_rate(
+                &noise_model,
+                index_to_sim[&qubit],
+                &partners[&qubit],
+                !data_qubit.contains(&qubit),
+                basis,
+            )
+        };
+        let measurement_graph_z = Self::gen_measurement_graph(
+            &measurement_qubit_z,
+            round,
+            distance,
+            'Z',
+            |data| flip_rate(data, Pauli::Z),
+            |ancilla| flip_rate(ancilla, Pauli::Z),
+            seed,
+        );
+        let measurement_graph_x = Self::gen_measurement_graph(
+            &measurement_qubit_x,
+            round,
+            distance,
+            'X',
+            |data| flip_rate(data, Pauli::X),
+            |ancilla| flip_rate(ancilla, Pauli::X),
+            seed,
+        );
+        // 最後のdata qubitの測定では, 測定エラーもdata qubitの反転として現れる
+        let single_round_measurement_graph_z = Self::gen_measurement_graph(
+            &measurement_qubit_z,
+            1,
+            distance,
+            'Z',
+            |data| UnGraph::merge_error_rate(flip_rate(data, Pauli::Z), p_m),
+            |_| 0.0,
+            seed,
+        );
 
         // make pauli frame
         let pauli_frame = PauliFrame::new_rotated_surface_code(distance);
 
-        // make stabilizers
-        let z_stabilizers = Self::gen_stabilizer(&measurement_qubit_z, &data_qubit, 'Z');
-        let x_stabilizers = Self::gen_stabilizer(&measurement_qubit_x, &data_qubit, 'X');
-
         // data qubit の測定結果を格納する行列
-        let classical_register = (0..distance)
-            .map(|_| (0..distance).map(|_| Rc::new(Cell::new(0))).collect())
-            .collect();
+        let classical_register = vec![vec![0; distance]; distance];
 
         Self {
             distance,
@@ -93,10 +221,14 @@ impl RotatedSurfaceCode {
             classical_register,
             measurement_graph_z,
             measurement_graph_x,
+            detector_z: Vec::new(),
+            detector_x: Vec::new(),
+            initial_measurement_x: Vec::new(),
+            data_measurement: Vec::new(),
             single_round_measurement_graph_z,
             pauli_frame,
+            noise_model,
             error_rate: p,
-            measurement_error_rate: p_m,
         }
     }
 
@@ -176,12 +308,14 @@ impl RotatedSurfaceCode {
     }
 
     /// generate measurement graph
+    /// edgeのweightはdata qubitごとの空間方向のエラーの確率p_space, ancillaごとの時間方向のエラーの確率p_timeから決める
     fn gen_measurement_graph(
         measurement_qubit: &Vec<(i32, i32)>,
         round: usize,
         distance: usize,
         mode: char,
-        p: f32,
+        p_space: impl Fn((i32, i32)) -> f32,
+        p_time: impl Fn((i32, i32)) -> f32,
         seed: u64,
     ) -> UnGraph {
         let mut network = if round == 1 {
@@ -248,73 +382,97 @@ impl RotatedSurfaceCode {
             }
         }
 
+        // 各edgeに対応するエラーの確率
+        // 同じ2点を反転させる独立なエラーは1つのedgeにまとめる (同じ時刻のboundary nodeは区別しない)
+        let mut mechanisms = Vec::new();
+        let mut mechanism_index = HashMap::new();
+        let mut add_mechanism = |(u, v): ((i32, i32, i32), (i32, i32, i32)), p: f32| {
+            let node = |n: (i32, i32, i32)| match boundary_node.contains(&(n.0, n.1)) {
+                true => (i32::MAX, i32::MAX, n.2),
+                false => n,
+            };
+            let key = (node(u).min(node(v)), node(u).max(node(v)));
+            match mechanism_index.get(&key) {
+                Some(&i) => {
+                    let (_, q): &mut (_, f32) = &mut mechanisms[i];
+                    *q = UnGraph::merge_error_rate(*q, p);
+                }
+                None => {
+                    mechanism_index.insert(key, mechanisms.len());
+                    mechanisms.push(((u, v), p));
+                }
+            }
+        };
+        // 1つのboundaryとみなすnode同士を結ぶweight 0のedge
+        let mut boundary_edges = Vec::new();
+
         for t in 0..round as i32 {
             // time boundary
             if round != 1 {
-                // 次のroundの同座標に対するedgeを追加
-                let mut time_edge = Vec::new();
+                // 次のroundの同座標に対するedge (測定エラー)
                 for &(x, y) in measurement_qubit.iter() {
-                    time_edge.push(((x, y, t), (x, y, t + 1)));
+                    add_mechanism(((x, y, t), (x, y, t + 1)), p_time((x, y)));
                 }
-                // 最後のroundでは、次の時間はboundaryなので、boundary同士をweight0のedgeで繋ぐ
+                // 最後のroundでは、次の時間はboundaryなので、boundary同士を繋ぐ
                 if t == (round as i32 - 1) {
-                    let time_boundary_edge: Vec<_> = measurement_qubit
-                        .iter()
-                        .tuple_windows()
-                        .map(|(&(u_x, u_y), &(v_x, v_y))| ((u_x, u_y, t + 1), (v_x, v_y, t + 1)))
-                        .collect();
-                    network.add_edges_from(&time_boundary_edge);
-                    network.set_edges_weight(&time_boundary_edge, 0.0);
+                    boundary_edges.extend(
+                        measurement_qubit
+                            .iter()
+                            .tuple_windows()
+                            .map(|(&(u_x, u_y), &(v_x, v_y))| ((u_x, u_y, t + 1), (v_x, v_y, t + 1))),
+                    );
 
                     // 時間のboundaryと空間のboundaryを繋ぐ
-                    let t_boundary_to_boundary = (
+                    boundary_edges.push((
                         (measurement_qubit[0].0, measurement_qubit[0].1, t + 1),
                         (boundary_node[0].0, boundary_node[0].1, t),
-                    );
-                    network.add_edge_from(&t_boundary_to_boundary);
-                    network.set_edge_weight(&t_boundary_to_boundary, p / 10.0);
+                    ));
                 }
-
-                network.add_edges_from(&time_edge);
-                network.set_edges_weight(&time_edge, p);
             }
 
+            // 空間方向のedge (data qubitのエラー)
             for &((u_x, u_y), (v_x, v_y)) in edges.iter() {
-                network.add_edge_from(&((u_x, u_y, t), (v_x, v_y, t)));
-                network.set_edge_weight(&((u_x, u_y, t), (v_x, v_y, t)), p);
+                let edge = ((u_x, u_y, t), (v_x, v_y, t));
+                add_mechanism(edge, p_space(UnGraph::edge_to_qubit(edge)));
             }
 
-            // 最後のround以外は次のboundary nodeとも繋ぐ(weightは0ではない)
+            // 最後のround以外は次のboundary nodeとも繋ぐ
             if t != (round as i32 - 1) {
                 for &(x, y) in boundary_node.iter() {
-                    let edge = ((x, y, t), (x, y, t + 1));
-                    network.add_edge_from(&edge);
-                    network.set_edge_weight(&edge, p / 10.0);
+                    boundary_edges.push(((x, y, t), (x, y, t + 1)));
                 }
             }
 
-            // boundary node 同士をweight0のedgeで繋ぐ
-            let boundary_edge: Vec<_> = boundary_node
-                .iter()
-                .tuple_windows()
-                .map(|(&u, &v)| ((u.0, u.1, t), (v.0, v.1, t)))
-                .collect();
-            network.add_edges_from(&boundary_edge);
-            network.set_edges_weight(&boundary_edge, 0.0);
+            // boundary node 同士を繋ぐ
+            boundary_edges.extend(
+                boundary_node
+                    .iter()
+                    .tuple_windows()
+                    .map(|(&u, &v)| ((u.0, u.1, t), (v.0, v.1, t))),
+            );
+        }
 
-            // boundaryかどうかとregisterを設定
+        for (edge, p) in mechanisms.iter() {
+            network.add_edge_from(edge);
+            network.set_edge_weight(edge, UnGraph::weight_from_error_rate(*p));
+        }
+        network.add_edges_from(&boundary_edges);
+        network.set_edges_weight(&boundary_edges, 0.0);
+
+        // boundaryかどうかとregisterを設定
+        for t in 0..round as i32 {
             for &(x, y) in measurement_qubit.iter() {
                 network.set_is_boundary((x, y, t), false);
-                network.set_classical_register((x, y, t), Rc::new(Cell::new(0))); // 順番が大事
+                network.set_classical_register((x, y, t), 0); // 順番が大事
                 if (t == (round as i32 - 1)) && (round != 1) {
                     // 最後のroundでは、時間方向のboundaryを設定
                     network.set_is_boundary((x, y, t + 1), true);
-                    network.set_classical_register((x, y, t + 1), Rc::new(Cell::new(0)));
+                    network.set_classical_register((x, y, t + 1), 0);
                 }
             }
             for &(x, y) in boundary_node.iter() {
                 network.set_is_boundary((x, y, t), true);
-                network.set_classical_register((x, y, t), Rc::new(Cell::new(0)));
+                network.set_classical_register((x, y, t), 0);
             }
         }
 
@@ -322,30 +480,40 @@ impl RotatedSurfaceCode {
     }
 
     /// syndrome measurement
+    /// 最後にdata qubitを測定し, logical Zを新しいobservable (最初の呼び出しでは0) とする
     pub fn syndrome_measurement(&mut self) {
+        let logical_z: Vec<_> = self.logical_z().into_iter().map(|(qubit, _)| qubit).collect();
+        let observable = self.circuit().observable_count();
         let Self {
             round,
             network,
             z_stabilizers,
             x_stabilizers,
-            measurement_graph_z,
-            measurement_graph_x,
+            detector_z,
+            detector_x,
+            initial_measurement_x,
+            data_measurement,
             data_qubit,
+            noise_model,
             ..
         } = self;
 
-        let noise_type = NoiseType::Depolarizing(network.error_rate());
-
-        for t in 0..*round as i32 {
-            // 仮 現象論的ノイズ
-            for c in data_qubit.iter() {
-                network.insert_noise(*c, noise_type);
-            }
-
-            // XスタビライザーにHゲートを作用させる
-            for Stabilizer { ancilla, .. } in x_stabilizers.iter() {
-                network.h(*ancilla);
-                // network.insert_noise(*ancilla, noise_type); // circuit noise
+        // ノイズはノイズのない回路を作った後にまとめて挿入する
+        let start = network.operation_count();
+        // 各ancillaの前のroundの測定結果のindex (Xスタビライザーの最初のroundはinitializeでの測定と比べる)
+        let mut last_measurement: HashMap<_, _> = initial_measurement_x.iter().copied().collect();
+        // 2round目以降は同じ回路なので, 2round目だけを作ってREPEATブロックにする
+        let mut repeat_start = None;
+
+        for t in 0..(*round).min(2) as i32 {
+            // Xスタビライザーのancillaを|+>に初期化 (以降のroundはMRXで|+>に戻る)
+            if t == 0 {
+                for Stabilizer { ancilla, .. } in x_stabilizers.iter() {
+                    network.reset_x(*ancilla);
+                }
+                network.tick();
+            } else {
+                repeat_start = Some(network.operation_count());
             }
 
             // CNOT
@@ -355,113 +523,309 @@ impl RotatedSurfaceCode {
                     match z_stab.pauli_product().get(i).unwrap() {
                         Some(data_coord) => {
                             network.cx(*data_coord, z_stab.ancilla);
-                            // network.insert_noise(z_stab.ancilla, noise_type); // circuit noise
-                            // network.insert_noise(*data_coord, noise_type);
                         }
                         None => (),
                     }
                     match x_stab.pauli_product().get(i).unwrap() {
                         Some(data_coord) => {
                             network.cx(x_stab.ancilla, *data_coord);
-                            // network.insert_noise(*data_coord, noise_type); // circuit noise
-                            // network.insert_noise(x_stab.ancilla, noise_type);
                         }
                         None => (),
                     }
                 }
-            }
-
-            // XスタビライザーにHゲートを作用させる
-            for Stabilizer { ancilla, .. } in x_stabilizers.iter() {
-                network.h(*ancilla);
-                // network.insert_noise(*ancilla, noise_type); // circuit noise
+                network.tick();
             }
 
             // measurement qubitの測定
             // Z
+            let mut measurement_z = Vec::new();
             for Stabilizer { ancilla, .. } in z_stabilizers.iter() {
-                network.measurement_and_reset(
-                    *ancilla,
-                    Rc::clone(
-                        measurement_graph_z
-                            .get_register(&(ancilla.0, ancilla.1, t))
-                            .unwrap(),
-                    ),
-                    self.measurement_error_rate,
-                );
+                let index = network.measurement_and_reset(*ancilla);
+                measurement_z.push((*ancilla, index));
             }
             // X
+            let mut measurement_x = Vec::new();
             for Stabilizer { ancilla, .. } in x_stabilizers.iter() {
-                network.measurement_and_reset(
-                    *ancilla,
-                    Rc::clone(
-                        measurement_graph_x
-                            .get_register(&(ancilla.0, ancilla.1, t))
-                            .unwrap(),
-                    ),
-                    self.measurement_error_rate,
-                );
+                let index = network.measurement_and_reset_x(*ancilla);
+                measurement_x.push((*ancilla, index));
             }
+
+            // detector (前のroundとのxor, 最初のroundはZは測定結果そのもの, Xはinitializeでの測定とのxor)
+            for (measurement, detector) in [
+                (measurement_z, &mut *detector_z),
+                (measurement_x, &mut *detector_x),
+            ] {
+                for ((x, y), index) in measurement {
+                    let count = network.measurement_count();
+                    let lookback = std::iter::once(index)
+                        .chain(last_measurement.insert((x, y), index))
+                        .map(|i| count - i)
+                        .collect();
+                    let id = network.detector(vec![x as f32, y as f32, t as f32], lookback);
+                    detector.push(((x, y, t), id));
+                }
+            }
+            network.tick();
+        }
+
+        if let Some(start) = repeat_start {
+            network.repeat_from(start, *round - 1);
+            // 繰り返したroundのdetectorのindexは1roundのdetectorの数ずつずれる
+            let per_round = z_stabilizers.len() + x_stabilizers.len();
+            for detector in [&mut *detector_z, &mut *detector_x] {
+                let second_round: Vec<_> = detector
+                    .iter()
+                    .filter(|((_, _, t), _)| *t == 1)
+                    .copied()
+                    .collect();
+                for t in 2..*round as i32 {
+                    detector.extend(second_round.iter().map(|&((x, y, _), id)| {
+                        ((x, y, t), id + (t as usize - 1) * per_round)
+                    }));
+                }
+            }
+        }
+
+        // data qubitの測定
+        data_measurement.clear();
+        for &qubit in data_qubit.iter() {
+            let index = network.measurement(qubit);
+            data_measurement.push((qubit, index));
         }
+        let count = network.measurement_count();
+        let lookback = data_measurement
+            .iter()
+            .filter(|(qubit, _)| logical_z.contains(qubit))
+            .map(|(_, index)| count - index)
+            .collect();
+        network.observable_include(observable, lookback);
+
+        network.insert_noise_model(noise_model, data_qubit, start);
+        self.set_weights_from_error_model();
     }
 
-    /// encoding logical one
-    pub fn initialize(&mut self) {
+    /// 回路のdetector error modelからsyndrome graphのedgeのweightを求め直す
+    /// (CXで伝搬したエラーによる時間方向・斜め方向の相関も含む)
+    ///
+    /// 2round目以降は同じ回路なので, REPEATブロックをTRUNCATED_REPEAT回に縮めた回路から求める.
+    /// 最初と最後のroundに関わるエラーはそのまま, それ以外は間の全てのroundにずらして使う
+    fn set_weights_from_error_model(&mut self) {
+        const TRUNCATED_REPEAT: usize = 3;
+        let truncated: Circuit = self
+            .circuit()
+            .iter()
+            .map(|op| match op {
+                Operation::Repeat(n, body) => {
+                    Operation::Repeat((*n).min(TRUNCATED_REPEAT), body.clone())
+                }
+                op => op.clone(),
+            })
+            .collect();
+        let model =
+            frame_simulator::detector_error_model(self.network.index_to_sim().len(), &truncated);
+        let last = self.round as i32 - 1;
+        let truncated_last = last.min(TRUNCATED_REPEAT as i32);
         let Self {
-            network,
-            x_stabilizers,
+            measurement_graph_z,
+            measurement_graph_x,
+            detector_z,
+            detector_x,
             ..
         } = self;
 
-        // XスタビライザーにHゲートを作用させる
-        for Stabilizer { ancilla, .. } in x_stabilizers.iter() {
-            network.h(*ancilla);
+        for (graph, detectors) in [
+            (measurement_graph_z, &*detector_z),
+            (measurement_graph_x, &*detector_x),
+        ] {
+            // 縮めた回路でのdetectorのindexは最後のroundまで元の回路と同じ
+            let node: HashMap<usize, Node> = detectors
+                .iter()
+                .filter(|((_, _, t), _)| *t <= truncated_last)
+                .map(|&(coord, id)| (id, coord))
+                .collect();
+
+            // 各edgeに対応するエラーをまとめたerror rate
+            let mut error_rate: HashMap<(Node, Node), f32> = HashMap::new();
+            for mechanism in model.iter() {
+                // もう一方のgraphのdetectorは, そちらのgraphで独立に扱う
+                let nodes: Vec<_> = mechanism
+                    .detectors
+                    .iter()
+                    .filter_map(|id| node.get(id).copied())
+                    .collect();
+                let (Some(first), Some(end)) = (
+                    nodes.iter().map(|n| n.2).min(),
+                    nodes.iter().map(|n| n.2).max(),
+                ) else {
+                    continue;
+                };
+                let shifts = match (first, end) {
+                    _ if truncated_last == last => 0..=0,
+                    (0, _) | (1, 1) => 0..=0,
+                    (_, end) if end == truncated_last => last - truncated_last..=last - truncated_last,
+                    _ => 0..=last - truncated_last,
+                };
+                for shift in shifts {
+                    let shifted: Vec<_> = nodes.iter().map(|&(x, y, t)| (x, y, t + shift)).collect();
+                    for edge in Self::decompose_error(graph, &shifted).into_iter().flatten() {
+                        let p = error_rate.entry(edge).or_insert(0.0);
+                        *p = UnGraph::merge_error_rate(*p, mechanism.probability);
+                    }
+                }
+            }
+
+            for &edge in error_rate.keys() {
+                if graph.edge_weight(&edge).is_none() {
+                    graph.add_edge_from(&edge);
+                }
+            }
+            let edges: Vec<_> = graph
+                .nodes()
+                .flat_map(|&u| graph.neighbors(&u).unwrap().iter().map(move |&v| (u, v)))
+                .filter(|&(u, v)| u < v)
+                .filter(|(u, v)| !(graph.is_boundary(u).unwrap() && graph.is_boundary(v).unwrap()))
+                .collect();
+            for edge in edges {
+                let p = error_rate.get(&edge).copied().unwrap_or(0.0);
+                graph.set_edge_weight(&edge, UnGraph::weight_from_error_rate(p));
+            }
         }
-        // CNOT
-        for x_stab in x_stabilizers.iter() {
-            for x_data_coord in x_stab.pauli_product().iter() {
-                // data bitが存在するときのみCNOT
-                match x_data_coord {
-                    Some(data_coord) => {
-                        network.cx(x_stab.ancilla, *data_coord);
+    }
+
+    /// エラーが反転させるsyndrome graphのnodeを, 隣り合うnodeを結ぶedgeとboundaryへのedgeに分ける
+    ///
+    /// 隣り合うnodeは同じroundの斜め, 前後のroundの同じ座標と斜めのnode.
+    /// 組にならなかったnodeはboundary (空間方向と時間方向) へのedge全てに対応させ, boundaryがなければNone
+    fn decompose_error(graph: &UnGraph, nodes: &[Node]) -> Option<Vec<(Node, Node)>> {
+        let adjacent = |u: Node, v: Node| {
+            let (dx, dy, dt) = ((u.0 - v.0).abs(), (u.1 - v.1).abs(), (u.2 - v.2).abs());
+            dt <= 1 && ((dx, dy) == (2, 2) || (dx, dy, dt) == (0, 0, 1))
+        };
+        let edge = |u: Node, v: Node| (u.min(v), u.max(v));
+
+        let mut remaining = nodes.to_vec();
+        let mut edges = Vec::new();
+        while let Some(u) = remaining.pop() {
+            match remaining.iter().position(|&v| adjacent(u, v)) {
+                Some(i) => edges.push(edge(u, remaining.remove(i))),
+                None => {
+                    let boundary: Vec<_> = graph
+                        .neighbors(&u)?
+                        .iter()
+                        .filter(|v| graph.is_boundary(v) == Some(true))
+                        .map(|&v| edge(u, v))
+                        .collect();
+                    if boundary.is_empty() {
+                        return None;
                     }
-                    None => (),
+                    edges.extend(boundary);
                 }
             }
         }
+        Some(edges)
+    }
 
-        // XスタビライザーにHゲートを作用させる
-        for Stabilizer { ancilla, .. } in x_stabilizers.iter() {
-            network.h(*ancilla);
+    /// encoding logical zero
+    /// data qubitを|0>にリセットし, Xスタビライザーを測定して固有状態に射影する
+    /// (Xスタビライザーの符号は測定結果で決まり, 最初のroundのdetectorはその結果と比べる)
+    pub fn initialize(&mut self) {
+        let Self {
+            network,
+            x_stabilizers,
+            data_qubit,
+            initial_measurement_x,
+            ..
+        } = self;
+
+        for &qubit in data_qubit.iter() {
+            network.reset_z(qubit);
         }
+        network.tick();
 
-        // ancilla qubit の測定 (強制的に固有値+1に射影する)
-        for Stabilizer { ancilla, .. } in x_stabilizers.iter() {
-            network.measurement_to_zero(*ancilla);
+        initial_measurement_x.clear();
+        for x_stab in x_stabilizers.iter() {
+            let product: Vec<_> = x_stab
+                .pauli_product()
+                .iter()
+                .flatten()
+                .map(|&data| (data, Pauli::X))
+                .collect();
+            let index = network.noiseless_measurement_pauli_product(&product);
+            initial_measurement_x.push((x_stab.ancilla, index));
         }
+        network.tick();
     }
 
-    /// logical z measurement
+    /// 最後のdata qubitの測定結果をmeasurement recordから読み出す
     fn logical_measurement(&mut self) {
         let Self {
             network,
-            data_qubit,
+            data_measurement,
             classical_register,
             ..
         } = self;
 
-        for &(x, y) in data_qubit.iter() {
+        let record = network.measurement_record();
+        for &((x, y), index) in data_measurement.iter() {
             debug_assert!(x >= 0, "data coord must not be negative number");
             debug_assert!(y >= 0, "data coord must not be negative number");
 
-            network.measurement_direct(
-                (x, y),
-                Rc::clone(&classical_register[(x / 2) as usize][(y / 2) as usize]),
-                self.measurement_error_rate,
-            );
+            classical_register[(x / 2) as usize][(y / 2) as usize] = record.get(index);
         }
     }
 
+    /// logical Zの上のdata qubitの測定結果のparity
+    fn logical_z_parity(&self) -> u8 {
+        self.logical_z()
+            .iter()
+            .fold(0, |parity, &((x, y), _)| {
+                parity ^ self.classical_register[(x / 2) as usize][(y / 2) as usize]
+            })
+    }
+
+    /// logical Z operator (Z on a row of data qubits)
+    pub fn logical_z(&self) -> Vec<((i32, i32), Pauli)> {
+        (0..self.distance as i32 * 2)
+            .step_by(2)
+            .map(|x| ((x, 0), Pauli::Z))
+            .collect()
+    }
+
+    /// logical X operator (X on a column of data qubits)
+    pub fn logical_x(&self) -> Vec<((i32, i32), Pauli)> {
+        (0..self.distance as i32 * 2)
+            .step_by(2)
+            .map(|y| ((0, y), Pauli::X))
+            .collect()
+    }
+
+    /// Z stabilizer, X stabilizerの順に並べたPauli積
+    pub fn stabilizer_products(&self) -> Vec<Vec<((i32, i32), Pauli)>> {
+        [(&self.z_stabilizers, Pauli::Z), (&self.x_stabilizers, Pauli::X)]
+            .iter()
+            .flat_map(|(stabilizers, pauli)| {
+                stabilizers.iter().map(move |stab| {
+                    stab.pauli_product()
+                        .iter()
+                        .filter_map(|n| n.map(|coord| (coord, *pauli)))
+                        .collect()
+                })
+            })
+            .collect()
+    }
+
+    /// 最後に実行した後の状態でのPauli積の期待値 (+1, -1, ランダムなら0) を状態を変えずに求める
+    /// (FrameSimulatorではNone)
+    pub fn peek_observable(&mut self, product: &[((i32, i32), Pauli)]) -> Option<i8> {
+        self.network.peek_observable(product)
+    }
+
+    /// measure a logical operator in one shot by Pauli product measurement
+    /// return the index of the outcome in the measurement record
+    pub fn logical_pauli_measurement(&mut self, logical: &[((i32, i32), Pauli)]) -> usize {
+        self.network.measurement_pauli_product(logical)
+    }
+
     /// correct z error
     fn correct_z_error(&mut self) {
         let Self {
@@ -473,12 +837,12 @@ impl RotatedSurfaceCode {
         pauli_frame
             .x_frame_mut()
             .iter()
-            .zip(classical_register.iter().flatten())
-            .for_each(|(&frame, register)| register.set(register.get() ^ frame));
+            .zip(classical_register.iter_mut().flatten())
+            .for_each(|(&frame, register)| *register ^= frame);
     }
 
     /// decode logical value
-    fn decode_logical_value(&mut self) {
+    fn decode_logical_value(&mut self, decoder: &mut dyn Decoder) {
         // single_round_measurement_graph_xにparityの情報を書き込む
         for z_stab in self.z_stabilizers.iter() {
             let parity =
@@ -487,86 +851,71 @@ impl RotatedSurfaceCode {
                     .iter()
                     .filter_map(|n| *n)
                     .fold(0, |parity, (x, y)| {
-                        parity ^ self.classical_register[x as usize / 2][y as usize / 2].get()
+                        parity ^ self.classical_register[x as usize / 2][y as usize / 2]
                     });
 
             let ancilla = z_stab.ancilla;
             self.single_round_measurement_graph_z
-                .get_register(&(ancilla.0, ancilla.1, 0))
-                .unwrap()
-                .set(parity);
+                .flip_classical_register(&(ancilla.0, ancilla.1, 0), parity);
         }
 
         if cfg!(debug_assertions) {
             self.single_round_measurement_graph_z.show_all_defect();
         }
 
-        Self::flip_defect(1, &mut self.single_round_measurement_graph_z);
-        let correction_qubit_x = mwpm::decode(&self.single_round_measurement_graph_z, 10);
+        self.single_round_measurement_graph_z.flip_boundary_defect();
+        let correction_qubit_x = decoder.decode(&self.single_round_measurement_graph_z);
         if cfg!(debug_assertions) {
             println!("correction_qubit_x {:?}", correction_qubit_x);
         }
         // correction
         for (x, y) in correction_qubit_x.into_iter() {
-            let register = &self.classical_register[x as usize / 2][y as usize / 2];
-            register.set(register.get() ^ 1);
+            self.classical_register[x as usize / 2][y as usize / 2] ^= 1;
         }
     }
 
     /// return logical value
     pub fn logical_value(&mut self) -> u8 {
+        self.logical_value_with(&mut Mwpm::new(10))
+    }
+
+    /// 最後のdata qubitの測定結果を指定したdecoderでdecodeしてlogical valueを返す
+    /// (最後のobservableの反転に, logical Zの上の訂正のparityを加えたもの)
+    pub fn logical_value_with(&mut self, decoder: &mut dyn Decoder) -> u8 {
+        let observable = *self
+            .network
+            .observable_flips()
+            .last()
+            .expect("syndrome_measurement must be called before logical_value");
+
         self.logical_measurement();
+        let measured = self.logical_z_parity();
         self.correct_z_error();
         if cfg!(debug_assertions) {
             println!("start logical decode");
         }
-        self.decode_logical_value();
-
-        let result = self.classical_register();
-
-        // Rc<Cell<u8>>をほどいて縦方向に足す
-        let result_vec = result
-            .iter()
-            .map(|row| row.iter().map(|value| value.get()).collect::<Vec<u8>>())
-            .reduce(|row_a, row_b| {
-                row_a
-                    .iter()
-                    .zip(row_b.iter())
-                    .map(|(&a, &b)| a + b)
-                    .collect()
-            })
-            .unwrap();
-
-        let logical_value: usize = result_vec.into_iter().map(|v| (v % 2) as usize).sum();
+        self.decode_logical_value(decoder);
+        let corrected = self.logical_z_parity();
 
-        match logical_value {
-            n if n == self.distance => 1,
-            0 => 0,
-            _ => u8::MAX,
-        }
+        observable ^ measured ^ corrected
     }
 
     /// decode by mwpm
     pub fn decode_mwpm(&mut self, m: usize) {
-        if cfg!(debug_assertions) {
-            print!("before xor z: ");
-            self.measurement_graph_z.show_all_defect();
-            print!("before xor x: ");
-            self.measurement_graph_x.show_all_defect();
-        }
-
-        self.measurement_graph_z.xor_to_last_time();
-        self.measurement_graph_x.xor_to_last_time();
+        self.decode_with(&mut Mwpm::new(m));
+    }
 
+    /// syndrome graphを指定したdecoderでdecodeしてpauli frameに設定する
+    pub fn decode_with(&mut self, decoder: &mut dyn Decoder) {
         if cfg!(debug_assertions) {
-            print!("after xor z: ");
+            print!("detection events z: ");
             self.measurement_graph_z.show_all_defect();
-            print!("after xor x: ");
+            print!("detection events x: ");
             self.measurement_graph_x.show_all_defect();
         }
 
-        Self::flip_defect(self.round, &mut self.measurement_graph_z);
-        Self::flip_defect(self.round, &mut self.measurement_graph_x);
+        self.measurement_graph_z.flip_boundary_defect();
+        self.measurement_graph_x.flip_boundary_defect();
 
         if cfg!(debug_assertions) {
             print!("after flip z: ");
@@ -575,8 +924,8 @@ impl RotatedSurfaceCode {
             self.measurement_graph_x.show_all_defect();
         }
 
-        let correction_qubit_z = mwpm::decode(&self.measurement_graph_x, m);
-        let correction_qubit_x = mwpm::decode(&self.measurement_graph_z, m);
+        let correction_qubit_z = decoder.decode(&self.measurement_graph_x);
+        let correction_qubit_x = decoder.decode(&self.measurement_graph_z);
 
         if cfg!(debug_assertions) {
             println!("correction_qubit_x {:?}", correction_qubit_x);
@@ -614,33 +963,20 @@ impl RotatedSurfaceCode {
         }
     }
 
-    /// 必要に応じてboundaryを反転させる
-    fn flip_defect(round: usize, measurement_graph: &mut UnGraph) {
-        for t in 0..round {
-            let defect_num = measurement_graph.classical_register()[t]
-                .iter()
-                .filter(|defect| defect.get() == 1)
-                .count();
-            let index_to_coord = measurement_graph.index_to_coord();
-            if defect_num % 2 == 1 {
-                for (coord, defect) in measurement_graph.classical_register()[t]
-                    .iter()
-                    .enumerate()
-                    .map(|(index, defect)| (index_to_coord.get(&(index, t)).unwrap(), defect))
-                    .filter(|&(coord, _)| measurement_graph.is_boundary(coord).unwrap())
-                {
-                    if defect.get() == 0 {
-                        measurement_graph.flip_classical_register(coord, 1);
-                        break;
-                    }
-                }
-            }
-        }
-    }
-
     /// run circuit
+    /// detection eventをsyndrome graphに書き込む
     pub fn run(&mut self) {
         self.network.run();
+
+        let events = self.network.detection_events();
+        for &(coord, id) in self.detector_z.iter() {
+            self.measurement_graph_z
+                .flip_classical_register(&coord, events[id]);
+        }
+        for &(coord, id) in self.detector_x.iter() {
+            self.measurement_graph_x
+                .flip_classical_register(&coord, events[id]);
+        }
     }
 
     /// reset code
@@ -652,13 +988,26 @@ impl RotatedSurfaceCode {
         self.network.reset();
     }
 
-    pub fn classical_register(&self) -> &Vec<Vec<Rc<Cell<u8>>>> {
+    pub fn classical_register(&self) -> &Vec<Vec<u8>> {
         &self.classical_register
     }
 
+    pub fn measurement_record(&self) -> &MeasurementRecord {
+        self.network.measurement_record()
+    }
+
+    /// circuit built so far (qubit index is that of index_to_sim)
+    pub fn circuit(&self) -> &Circuit {
+        self.network.circuit()
+    }
+
     pub fn index_to_sim(&self) -> &HashMap<(i32, i32), usize> {
         &self.network.index_to_sim()
     }
+
+    pub fn noise_model(&self) -> &NoiseModel {
+        &self.noise_model
+    }
 }
 
 mod test {
@@ -673,4 +1022,103 @@ mod test {
             )
         }
     }
+    #[test]
+    fn measure_stabilizer_and_logical_by_pauli_product() {
+        let distance = 3;
+        let mut logical_z_flipped = false;
+
+        for seed in 0..20 {
+            let mut code = super::RotatedSurfaceCode::new(distance, 1, 0.0, 0.0, seed);
+            code.initialize();
+
+            let stabilizers = code.stabilizer_products();
+
+            let before: Vec<_> = stabilizers
+                .iter()
+                .map(|stab| code.logical_pauli_measurement(stab))
+                .collect();
+            let logical_z = code.logical_pauli_measurement(&code.logical_z());
+            // logical Xを測定しても、stabilizerは変化しない
+            code.logical_pauli_measurement(&code.logical_x());
+            let after: Vec<_> = stabilizers
+                .iter()
+                .map(|stab| code.logical_pauli_measurement(stab))
+                .collect();
+            let logical_z_after_x = code.logical_pauli_measurement(&code.logical_z());
+            code.run();
+
+            let record = code.measurement_record();
+            // Zスタビライザーは+1, Xスタビライザーはinitializeでの測定結果の符号を持つ
+            let expected: Vec<u8> = code
+                .z_stabilizers
+                .iter()
+                .map(|_| 0)
+                .chain(code.initial_measurement_x.iter().map(|&(_, index)| record.get(index)))
+                .collect();
+            for ((&b, &a), &e) in before.iter().zip(after.iter()).zip(expected.iter()) {
+                assert_eq!(record.get(b), e);
+                assert_eq!(record.get(a), e);
+            }
+            assert_eq!(record.get(logical_z), 0);
+            logical_z_flipped |= record.get(logical_z_after_x) == 1;
+        }
+
+        assert!(logical_z_flipped);
+    }
+
+    /// stabilizer_productsの順に並べたstabilizerの期待値 (Xスタビライザーはinitializeでの測定結果で決まる)
+    #[cfg(test)]
+    fn expected_stabilizers(code: &super::RotatedSurfaceCode) -> Vec<Option<i8>> {
+        let record = code.measurement_record();
+        code.z_stabilizers
+            .iter()
+            .map(|_| Some(1))
+            .chain(
+                code.initial_measurement_x
+                    .iter()
+                    .map(|&(_, index)| Some(1 - 2 * record.get(index) as i8)),
+            )
+            .collect()
+    }
+
+    #[test]
+    fn initialize_prepares_stabilizer_eigenstate() {
+        for seed in 0..5 {
+            let mut code = super::RotatedSurfaceCode::new(5, 1, 0.0, 0.0, seed);
+            code.initialize();
+            code.run();
+
+            let expected = expected_stabilizers(&code);
+            for (stab, expected) in code.stabilizer_products().iter().zip(expected) {
+                assert_eq!(code.peek_observable(stab), expected, "{:?}", stab);
+            }
+            assert_eq!(code.peek_observable(&code.logical_z()), Some(1));
+            // logical Xは決まらない
+            assert_eq!(code.peek_observable(&code.logical_x()), Some(0));
+        }
+
+        // 状態ベクトルでも同じ期待値になり, 状態を持たないFrameSimulatorではNone
+        let noise_model = super::NoiseModel::Phenomenological(0.0, 0.0);
+        let mut code = super::RotatedSurfaceCode::new_with_simulator(
+            3,
+            1,
+            noise_model.clone(),
+            super::Type::StateVectorSimulator,
+            0,
+        );
+        code.initialize();
+        code.run();
+        let expected = expected_stabilizers(&code);
+        for (stab, expected) in code.stabilizer_products().iter().zip(expected) {
+            assert_eq!(code.peek_observable(stab), expected, "{:?}", stab);
+        }
+        assert_eq!(code.peek_observable(&code.logical_z()), Some(1));
+        assert_eq!(code.peek_observable(&code.logical_x()), Some(0));
+
+        let mut code =
+            super::RotatedSurfaceCode::new_with_simulator(3, 1, noise_model, super::Type::FrameSimulator, 0);
+        code.initialize();
+        code.run();
+        assert_eq!(code.peek_observable(&code.logical_z()), None);
+    }
 }
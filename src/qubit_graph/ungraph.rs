@@ -1,14 +1,12 @@
 use itertools::Itertools;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
-use std::cell::Cell;
 use std::collections::HashMap;
-use std::rc::Rc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct ClassicalRegister {
     coord_to_index: HashMap<(i32, i32, i32), usize>,
     index_to_coord: HashMap<(usize, usize), (i32, i32, i32)>,
-    register: Vec<Vec<Rc<Cell<u8>>>>,
+    register: Vec<Vec<u8>>,
 }
 
 impl ClassicalRegister {
@@ -24,8 +22,8 @@ impl ClassicalRegister {
         }
     }
 
-    /// set Rc<Cell<u8>>
-    fn set_classical_register(&mut self, node: (i32, i32, i32), classical_register: Rc<Cell<u8>>) {
+    /// set register
+    fn set_classical_register(&mut self, node: (i32, i32, i32), classical_register: u8) {
         self.index_to_coord
             .insert((self.register[node.2 as usize].len(), node.2 as usize), node);
         self.coord_to_index
@@ -34,14 +32,21 @@ impl ClassicalRegister {
     }
 
     /// get register
-    fn register_from_coord(&self, node: &(i32, i32, i32)) -> Option<&Rc<Cell<u8>>> {
+    fn register_from_coord(&self, node: &(i32, i32, i32)) -> Option<u8> {
         self.register
             .get(node.2 as usize)?
             .get(self.coord_to_index.get(node)?.clone())
+            .copied()
+    }
+
+    /// get register mut
+    fn register_from_coord_mut(&mut self, node: &(i32, i32, i32)) -> Option<&mut u8> {
+        let index = *self.coord_to_index.get(node)?;
+        self.register.get_mut(node.2 as usize)?.get_mut(index)
     }
 
     /// get register vec mut
-    fn register_mut(&mut self) -> &mut Vec<Vec<Rc<Cell<u8>>>> {
+    fn register_mut(&mut self) -> &mut Vec<Vec<u8>> {
         &mut self.register
     }
 
@@ -54,12 +59,12 @@ impl ClassicalRegister {
     }
 
     /// iterate only value
-    fn iter_value(&self) -> core::slice::Iter<Vec<Rc<Cell<u8>>>> {
-        self.register.iter()
+    fn iter_value_mut(&mut self) -> core::slice::IterMut<'_, Vec<u8>> {
+        self.register.iter_mut()
     }
 
     /// return register
-    fn register(&self) -> &Vec<Vec<Rc<Cell<u8>>>> {
+    fn register(&self) -> &Vec<Vec<u8>> {
         &self.register
     }
 
@@ -70,12 +75,12 @@ impl ClassicalRegister {
 }
 
 pub struct Iter<'a> {
-    register: &'a Vec<Vec<Rc<Cell<u8>>>>,
+    register: &'a Vec<Vec<u8>>,
     hash_iter: std::collections::hash_map::Iter<'a, (i32, i32, i32), usize>,
 }
 
 impl<'a> Iterator for Iter<'a> {
-    type Item = (&'a (i32, i32, i32), &'a Rc<Cell<u8>>);
+    type Item = (&'a (i32, i32, i32), &'a u8);
 
     #[cfg_attr(feature = "inline-more", inline)]
     fn next(&mut self) -> Option<Self::Item> {
@@ -89,7 +94,7 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UnGraph {
     network: HashMap<(i32, i32, i32), Vec<(i32, i32, i32)>>,
     edge_weight: HashMap<((i32, i32, i32), (i32, i32, i32)), f32>,
@@ -209,17 +214,17 @@ impl UnGraph {
     }
 
     /// set classical register
-    pub fn set_classical_register(&mut self, node: (i32, i32, i32), register: Rc<Cell<u8>>) {
+    pub fn set_classical_register(&mut self, node: (i32, i32, i32), register: u8) {
         self.classical_register
             .set_classical_register(node, register);
     }
 
     /// flip classical register
-    pub fn flip_classical_register(&self, coord: &(i32, i32, i32), value: u8) {
-        self.classical_register
-            .register_from_coord(coord)
-            .unwrap()
-            .set(value);
+    pub fn flip_classical_register(&mut self, coord: &(i32, i32, i32), value: u8) {
+        *self
+            .classical_register
+            .register_from_coord_mut(coord)
+            .unwrap_or_else(|| panic!("{:?} isn't exist", coord)) = value;
     }
 
     /// iterate classical register
@@ -228,17 +233,17 @@ impl UnGraph {
     }
 
     /// return classical register
-    pub fn get_register(&self, node: &(i32, i32, i32)) -> Option<&Rc<Cell<u8>>> {
+    pub fn get_register(&self, node: &(i32, i32, i32)) -> Option<u8> {
         self.classical_register.register_from_coord(node)
     }
 
     /// get classical register mut
-    pub fn classical_register_mut(&mut self) -> &mut Vec<Vec<Rc<Cell<u8>>>> {
+    pub fn classical_register_mut(&mut self) -> &mut Vec<Vec<u8>> {
         self.classical_register.register_mut()
     }
 
     /// get classical register 
-    pub fn classical_register(&self) -> &Vec<Vec<Rc<Cell<u8>>>> {
+    pub fn classical_register(&self) -> &Vec<Vec<u8>> {
         self.classical_register.register()
     }
 
@@ -287,21 +292,33 @@ impl UnGraph {
                 .iter()
                 .zip(back.iter())
                 .zip(temp_vec.iter_mut())
-                .for_each(|((f, b), temp)| *temp = f.get() ^ b.get());
+                .for_each(|((f, b), temp)| *temp = f ^ b);
         }
 
         // replace
         for (register, temp) in self
             .classical_register_mut()
-            .iter()
+            .iter_mut()
             .skip(1)
             .flatten()
             .zip(temp_mat.into_iter().flatten())
         {
-            register.set(temp);
+            *register = temp;
         }
     }
 
+    /// error rate pのエラーに対応するedgeのweight ln((1-p)/p)
+    pub fn weight_from_error_rate(p: f32) -> f32 {
+        // p = 0で無限大, p > 1/2で負のweightにならないようにする
+        let p = p.clamp(f32::MIN_POSITIVE, 0.5);
+        ((1.0 - p) / p).ln()
+    }
+
+    /// 同じedgeに対応する独立な2つのエラーを合わせたerror rate (どちらか一方だけが起こる確率)
+    pub fn merge_error_rate(p: f32, q: f32) -> f32 {
+        p * (1.0 - q) + q * (1.0 - p)
+    }
+
     /// get edge and return qubit coord
     pub fn edge_to_qubit(edge: ((i32, i32, i32), (i32, i32, i32))) -> (i32, i32) {
         let u = edge.0;
@@ -317,17 +334,39 @@ impl UnGraph {
     pub fn show_all_defect(&self) {
         print!("defect");
         for (coord, register) in self.classical_register.iter() {
-            if register.get() == 1 {
+            if *register == 1 {
                 print!("{:?}, ", coord);
             }
         }
         println!("");
     }
 
+    /// defectの数が奇数の時刻では, boundaryのregisterを1にしてdefectを偶数にする
+    pub fn flip_boundary_defect(&mut self) {
+        for t in 0..self.classical_register().len() {
+            let defect_num = self.classical_register()[t]
+                .iter()
+                .filter(|&&defect| defect == 1)
+                .count();
+            if defect_num % 2 == 0 {
+                continue;
+            }
+            let boundary = self.classical_register()[t]
+                .iter()
+                .enumerate()
+                .map(|(index, defect)| (self.index_to_coord().get(&(index, t)).unwrap(), defect))
+                .find(|&(coord, defect)| *defect == 0 && self.is_boundary(coord).unwrap())
+                .map(|(coord, _)| *coord);
+            if let Some(coord) = boundary {
+                self.flip_classical_register(&coord, 1);
+            }
+        }
+    }
+
     /// reset all register
-    pub fn reset_register(&self) {
-        for value in self.classical_register.iter_value().flatten() {
-            value.set(0);
+    pub fn reset_register(&mut self) {
+        for value in self.classical_register.iter_value_mut().flatten() {
+            *value = 0;
         }
     }
 
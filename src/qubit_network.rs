@@ -1,19 +1,25 @@
 use rand::{rngs::SmallRng, Rng, SeedableRng};
-use std::cell::Cell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::io;
 
-use crate::simulator::{self, SimulatorInterface, SimulatorWrapper, Type};
-use crate::noise::noise_model::NoiseType;
+use crate::simulator::{
+    self, measurement_record::MeasurementRecord, pauli::Pauli, SimulatorInterface,
+    SimulatorWrapper,
+};
+use crate::circuit::Circuit;
+use crate::noise::calibration::{self, Calibration};
+use crate::noise::noise_model::{DeviceErrorMap, NoiseModel, NoiseType};
 
 pub struct QubitNetwork {
     network: HashMap<(i32, i32), Vec<(i32, i32)>>,
     bit_error_map: HashMap<(i32, i32), f32>,
     connection_error_map: HashMap<((i32, i32), (i32, i32)), f32>,
+    measurement_error_map: HashMap<(i32, i32), f32>,
+    reset_error_map: HashMap<(i32, i32), f32>,
+    idle_error_map: HashMap<(i32, i32), NoiseType>,
     index_to_sim: HashMap<(i32, i32), usize>,
     sim: SimulatorWrapper,
     rng: rand::rngs::SmallRng,
-    error_rate: f32,
 }
 
 impl QubitNetwork {
@@ -94,17 +100,22 @@ impl QubitNetwork {
                 connection_error_map.insert((u, v), p);
             }
         }
+        // 測定, リセット, idleのerror rate dictを作成
+        let mut measurement_error_map = HashMap::new();
+        let mut reset_error_map = HashMap::new();
+        let mut idle_error_map = HashMap::new();
+        for &qubit in qubit_index.iter() {
+            measurement_error_map.insert(qubit, p);
+            reset_error_map.insert(qubit, p);
+            idle_error_map.insert(qubit, NoiseType::Depolarizing(p));
+        }
 
         // simulatorを生成
         // 乱数発生器は仮
         let mut rng = SmallRng::seed_from_u64(seed);
         let mut rng_sim = SmallRng::seed_from_u64(seed + 1);
 
-        let sim = match sim_type {
-            Type::CHPSimulator => simulator::SimulatorWrapper::CHPSimulator(
-                simulator::chp_simulator::CHPSimulator::new(qubit_index.len(), rng_sim),
-            ),
-        };
+        let sim = SimulatorWrapper::new(sim_type, qubit_index.len(), rng_sim);
         // (i32, i32)のindexからsimulatorのindexのusizeへのmap
         let mut index_to_sim = HashMap::new();
         for (i, &coord) in qubit_index.iter().enumerate() {
@@ -114,10 +125,12 @@ impl QubitNetwork {
             network,
             bit_error_map,
             connection_error_map,
+            measurement_error_map,
+            reset_error_map,
+            idle_error_map,
             index_to_sim,
             sim,
             rng,
-            error_rate: p,
         }
     }
 
@@ -139,7 +152,148 @@ impl QubitNetwork {
         p.clone()
     }
 
+    /// 指定したqubitの測定エラー率を返す
+    pub fn measurement_error_rate(&self, index: (i32, i32)) -> f32 {
+        *self
+            .measurement_error_map
+            .get(&index)
+            .expect("index does not exist")
+    }
+
+    /// 指定したqubitのリセットエラー率を返す
+    pub fn reset_error_rate(&self, index: (i32, i32)) -> f32 {
+        *self
+            .reset_error_map
+            .get(&index)
+            .expect("index does not exist")
+    }
+
+    /// 指定したqubitのidleノイズを返す
+    pub fn idle_noise(&self, index: (i32, i32)) -> NoiseType {
+        *self.idle_error_map.get(&index).expect("index does not exist")
+    }
+
+    /// 指定したqubitのerror rateを設定する
+    pub fn set_qubit_error_rate(&mut self, index: (i32, i32), p: f32) {
+        *self
+            .bit_error_map
+            .get_mut(&index)
+            .expect("index does not exist") = p;
+    }
+
+    /// 指定したconnectionのerror rateを設定する (両方の向きに設定される)
+    pub fn set_connection_error_rate(&mut self, index: ((i32, i32), (i32, i32)), p: f32) {
+        let (u, v) = index;
+        for key in [(u, v), (v, u)] {
+            *self
+                .connection_error_map
+                .get_mut(&key)
+                .expect("index does not exist") = p;
+        }
+    }
+
+    /// 指定したqubitの測定エラー率を設定する
+    pub fn set_measurement_error_rate(&mut self, index: (i32, i32), p: f32) {
+        *self
+            .measurement_error_map
+            .get_mut(&index)
+            .expect("index does not exist") = p;
+    }
+
+    /// 指定したqubitのリセットエラー率を設定する
+    pub fn set_reset_error_rate(&mut self, index: (i32, i32), p: f32) {
+        *self
+            .reset_error_map
+            .get_mut(&index)
+            .expect("index does not exist") = p;
+    }
+
+    /// 指定したqubitのidleノイズを設定する
+    pub fn set_idle_noise(&mut self, index: (i32, i32), noise_type: NoiseType) {
+        *self
+            .idle_error_map
+            .get_mut(&index)
+            .expect("index does not exist") = noise_type;
+    }
+
+    /// キャリブレーションデータからerror rateを設定する
+    /// networkにないqubit, couplerがあればerror rateを変更せずにエラーを返す
+    pub fn load_calibration(&mut self, calibration: &Calibration) -> io::Result<()> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        for index in calibration.qubit.keys() {
+            if !self.index_to_sim.contains_key(index) {
+                return Err(invalid(format!("qubit {:?} does not exist", index)));
+            }
+        }
+        for (u, v) in calibration.coupler.keys() {
+            if !self
+                .network
+                .get(u)
+                .is_some_and(|neighbors| neighbors.contains(v))
+            {
+                return Err(invalid(format!("coupler {:?} does not exist", (u, v))));
+            }
+        }
+
+        for (&index, qubit) in calibration.qubit.iter() {
+            self.set_measurement_error_rate(index, qubit.readout_error);
+            // キャリブレーションデータにはリセットエラーがないので, 読み出しエラーと同じにする
+            self.set_reset_error_rate(index, qubit.readout_error);
+            self.set_idle_noise(
+                index,
+                calibration::idle_noise(calibration.moment_time, qubit.t1, qubit.t2),
+            );
+            if let Some(fidelity) = qubit.gate_1q_fidelity {
+                self.set_qubit_error_rate(index, calibration::gate_1q_error_rate(fidelity));
+            }
+        }
+        for (&index, &fidelity) in calibration.coupler.iter() {
+            self.set_connection_error_rate(index, calibration::gate_2q_error_rate(fidelity));
+        }
+        Ok(())
+    }
+
+    /// error mapをsimulatorのindexで表したもの (2q gateは隣接するqubitのみ)
+    pub fn device_error_map(&self) -> DeviceErrorMap {
+        let sim_index = |a: &(i32, i32)| *self.index_to_sim.get(a).expect("index does not exist");
+
+        let mut gate_2q = HashMap::new();
+        for (u, neighbors) in self.network.iter() {
+            for v in neighbors.iter() {
+                gate_2q.insert(
+                    (sim_index(u), sim_index(v)),
+                    self.connection_error_rate((*u, *v)),
+                );
+            }
+        }
+
+        DeviceErrorMap {
+            gate_1q: self
+                .bit_error_map
+                .iter()
+                .map(|(a, &p)| (sim_index(a), p))
+                .collect(),
+            gate_2q,
+            idle: self
+                .idle_error_map
+                .iter()
+                .map(|(a, &noise_type)| (sim_index(a), noise_type))
+                .collect(),
+            measurement: self
+                .measurement_error_map
+                .iter()
+                .map(|(a, &p)| (sim_index(a), p))
+                .collect(),
+            reset: self
+                .reset_error_map
+                .iter()
+                .map(|(a, &p)| (sim_index(a), p))
+                .collect(),
+        }
+    }
+
     /// ゲート操作を追加する
+    /// gateとリセットは無ノイズで追加され, そのノイズはdevice_error_map()によるinsert_device_noiseで挿入する
     /// CNOT gate
     pub fn cx(&mut self, a: (i32, i32), b: (i32, i32)) {
         debug_assert!(self.connection_error_map.contains_key(&(a, b)));
@@ -173,22 +327,157 @@ impl QubitNetwork {
             .add_z(*self.index_to_sim.get(&a).expect("index does not exist"));
     }
 
-    /// measurement
-    pub fn measurement(&mut self, a: (i32, i32), register: Rc<Cell<u8>>, error_rate: f32) {
+    /// Y gate
+    pub fn y(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_y(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// S dagger gate
+    pub fn s_dag(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_s_dag(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// sqrt(X) gate
+    pub fn sqrt_x(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_sqrt_x(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// sqrt(X) dagger gate
+    pub fn sqrt_x_dag(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_sqrt_x_dag(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// sqrt(Y) gate
+    pub fn sqrt_y(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_sqrt_y(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// sqrt(Y) dagger gate
+    pub fn sqrt_y_dag(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_sqrt_y_dag(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// controlled Z gate
+    pub fn cz(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_cz(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// controlled Y gate
+    pub fn cy(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_cy(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// SWAP gate
+    pub fn swap(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_swap(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// iSWAP gate
+    pub fn iswap(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_iswap(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// iSWAP dagger gate
+    pub fn iswap_dag(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_iswap_dag(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// X-controlled X gate
+    pub fn xcx(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_xcx(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// X-controlled Y gate
+    pub fn xcy(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_xcy(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// X-controlled Z gate
+    pub fn xcz(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_xcz(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// Y-controlled X gate
+    pub fn ycx(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_ycx(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// Y-controlled Y gate
+    pub fn ycy(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_ycy(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// Y-controlled Z gate
+    pub fn ycz(&mut self, a: (i32, i32), b: (i32, i32)) {
+        debug_assert!(self.connection_error_map.contains_key(&(a, b)));
+        self.sim.add_ycz(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+        );
+    }
+
+    /// measurement (測定エラーはそのqubitのmeasurement_error_rate)
+    pub fn measurement(&mut self, a: (i32, i32)) -> usize {
+        let error_rate = self.measurement_error_rate(a);
         self.sim.add_measurement(
             *self.index_to_sim.get(&a).expect("index does not exist"),
-            register,
             error_rate,
-        );
+        )
     }
 
     ///measurement direct
-    pub fn measurement_direct(&mut self, a: (i32, i32), register: Rc<Cell<u8>>, error_rate: f32) {
+    pub fn measurement_direct(&mut self, a: (i32, i32)) -> u8 {
+        let error_rate = self.measurement_error_rate(a);
         self.sim.measurement(
             *self.index_to_sim.get(&a).expect("index does not exist"),
-            register,
             error_rate,
-        );
+        )
     }
 
     /// measurement to zero
@@ -198,11 +487,100 @@ impl QubitNetwork {
     }
 
     /// measurement and reset
-    pub fn measurement_and_reset(&mut self, a: (i32, i32), register: Rc<Cell<u8>>, error_rate: f32) {
+    pub fn measurement_and_reset(&mut self, a: (i32, i32)) -> usize {
+        let error_rate = self.measurement_error_rate(a);
         self.sim.add_measurement_and_reset(
             *self.index_to_sim.get(&a).expect("index does not exist"),
-            register,
             error_rate,
+        )
+    }
+
+    /// measurement in X basis
+    pub fn measurement_x(&mut self, a: (i32, i32)) -> usize {
+        let error_rate = self.measurement_error_rate(a);
+        self.sim.add_measurement_x(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            error_rate,
+        )
+    }
+
+    /// measurement in Y basis
+    pub fn measurement_y(&mut self, a: (i32, i32)) -> usize {
+        let error_rate = self.measurement_error_rate(a);
+        self.sim.add_measurement_y(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            error_rate,
+        )
+    }
+
+    /// reset to |0>
+    pub fn reset_z(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_reset_z(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// reset to |+>
+    pub fn reset_x(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_reset_x(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// reset to |+i>
+    pub fn reset_y(&mut self, a: (i32, i32)) {
+        self.sim
+            .add_reset_y(*self.index_to_sim.get(&a).expect("index does not exist"));
+    }
+
+    /// measurement and reset in X basis
+    pub fn measurement_and_reset_x(&mut self, a: (i32, i32)) -> usize {
+        let error_rate = self.measurement_error_rate(a);
+        self.sim.add_measurement_and_reset_x(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            error_rate,
+        )
+    }
+
+    /// measurement and reset in Y basis
+    pub fn measurement_and_reset_y(&mut self, a: (i32, i32)) -> usize {
+        let error_rate = self.measurement_error_rate(a);
+        self.sim.add_measurement_and_reset_y(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            error_rate,
+        )
+    }
+
+    /// measurement of Pauli product (測定エラーは各qubitのうち最大のもの)
+    pub fn measurement_pauli_product(&mut self, product: &[((i32, i32), Pauli)]) -> usize {
+        let error_rate = product
+            .iter()
+            .map(|(a, _)| self.measurement_error_rate(*a))
+            .fold(0.0, f32::max);
+        self.add_measurement_pauli_product(product, error_rate)
+    }
+
+    /// 測定エラーのないPauli積の測定 (符号の初期化などに用いる)
+    pub fn noiseless_measurement_pauli_product(&mut self, product: &[((i32, i32), Pauli)]) -> usize {
+        self.add_measurement_pauli_product(product, 0.0)
+    }
+
+    fn add_measurement_pauli_product(
+        &mut self,
+        product: &[((i32, i32), Pauli)],
+        error_rate: f32,
+    ) -> usize {
+        let product = product
+            .iter()
+            .map(|(a, pauli)| (*self.index_to_sim.get(a).expect("index does not exist"), *pauli))
+            .collect();
+        self.sim.add_measurement_pauli_product(product, error_rate)
+    }
+
+    /// Pauli controlled by the measurement result rec[-k] (k = lookback)
+    pub fn feedback(&mut self, pauli: Pauli, lookback: usize, a: (i32, i32)) {
+        self.sim.add_feedback(
+            pauli,
+            lookback,
+            *self.index_to_sim.get(&a).expect("index does not exist"),
         );
     }
 
@@ -211,11 +589,99 @@ impl QubitNetwork {
             .add_noise(*self.index_to_sim.get(&a).expect("index does not exist"), noise_type)
     }
 
+    /// 2量子ビットのノイズを挿入する
+    pub fn insert_noise2(&mut self, a: (i32, i32), b: (i32, i32), noise_type: NoiseType) {
+        self.sim.add_noise2(
+            *self.index_to_sim.get(&a).expect("index does not exist"),
+            *self.index_to_sim.get(&b).expect("index does not exist"),
+            noise_type,
+        )
+    }
+
+    /// これまでに追加されたオペレーションの数
+    pub fn operation_count(&self) -> usize {
+        self.sim.operation_count()
+    }
+
+    /// 並列に実行するgateの層の区切り (TICK) を追加する
+    pub fn tick(&mut self) {
+        self.sim.add_tick();
+    }
+
+    /// start以降に追加されたオペレーションをrepetitions回繰り返すREPEATブロックにする
+    pub fn repeat_from(&mut self, start: usize, repetitions: usize) {
+        self.sim.repeat_from(start, repetitions);
+    }
+
+    /// start以降のオペレーションにqubit, couplerごとのerror rate (device_error_map) でノイズを挿入する
+    pub fn insert_device_noise(&mut self, data_qubit: &[(i32, i32)], start: usize) {
+        let model = NoiseModel::Device(Box::new(self.device_error_map()));
+        self.insert_noise_model(&model, data_qubit, start);
+    }
+
+    /// start以降のオペレーションにノイズモデルに従ってノイズを挿入する
+    pub fn insert_noise_model(&mut self, model: &NoiseModel, data_qubit: &[(i32, i32)], start: usize) {
+        let data_qubit: Vec<_> = data_qubit
+            .iter()
+            .map(|a| *self.index_to_sim.get(a).expect("index does not exist"))
+            .collect();
+        self.sim.insert_noise_model(model, &data_qubit, start);
+    }
+
     /// 指定された座標がネットワークに存在するかを判定する
     pub fn check_contains(&self, a: (i32, i32)) -> bool {
         self.network.contains_key(&a)
     }
 
+    /// これまでに追加された測定の数
+    pub fn measurement_count(&self) -> usize {
+        self.sim.measurement_count()
+    }
+
+    /// 最後に実行した回路のmeasurement record
+    pub fn measurement_record(&self) -> &MeasurementRecord {
+        self.sim.measurement_record()
+    }
+
+    /// detectorを追加し, そのindexを返す (lookbackは参照する測定結果rec[-k]のk)
+    pub fn detector(&mut self, coords: Vec<f32>, lookback: Vec<usize>) -> usize {
+        self.sim.add_detector(coords, lookback)
+    }
+
+    /// logical observableに測定結果rec[-k]を加える
+    pub fn observable_include(&mut self, observable: usize, lookback: Vec<usize>) {
+        self.sim.add_observable_include(observable, lookback);
+    }
+
+    /// 最後に実行した回路のdetection event
+    pub fn detection_events(&self) -> &[u8] {
+        self.sim.detection_events()
+    }
+
+    /// 最後に実行した回路のlogical observableの反転
+    pub fn observable_flips(&self) -> &[u8] {
+        self.sim.observable_flips()
+    }
+
+    /// Pauli積の期待値 (+1, -1, ランダムなら0) を状態を変えずに求める
+    /// (状態を持たないFrameSimulatorではNone)
+    pub fn peek_observable(&mut self, product: &[((i32, i32), Pauli)]) -> Option<i8> {
+        let product: Vec<_> = product
+            .iter()
+            .map(|(a, pauli)| (*self.index_to_sim.get(a).expect("index does not exist"), *pauli))
+            .collect();
+        match self.sim {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => Some(sim.peek_observable(&product)),
+            SimulatorWrapper::StateVectorSimulator(ref sim) => Some(sim.peek_observable(&product)),
+            SimulatorWrapper::FrameSimulator(_) => None,
+        }
+    }
+
+    /// これまでに追加された回路
+    pub fn circuit(&self) -> &Circuit {
+        self.sim.circuit()
+    }
+
     /// 回路を実行する
     pub fn run(&mut self) {
         self.sim.run();
@@ -226,11 +692,6 @@ impl QubitNetwork {
         &self.index_to_sim
     }
 
-    /// return error rate
-    pub fn error_rate(&self) -> f32 {
-        self.error_rate
-    }
-
     /// reset tableau
     pub fn reset(&mut self) {
         self.sim.reset();
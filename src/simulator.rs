@@ -1,11 +1,22 @@
 use self::chp_simulator::CHPSimulator;
-use crate::noise::noise_model::NoiseType;
-use std::cell::Cell;
-use std::rc::Rc;
+use self::frame_simulator::FrameSimulator;
+use self::measurement_record::MeasurementRecord;
+use self::pauli::{combine_pauli_product, Pauli};
+use self::state_vector_simulator::StateVectorSimulator;
+use crate::circuit::Circuit;
+use crate::noise::noise_model::{NoiseModel, NoiseType};
+use std::collections::BTreeSet;
+use rand::rngs::SmallRng;
 
 pub mod chp_simulator;
 pub mod core;
 pub mod frame;
+pub mod frame_simulator;
+pub mod measurement_record;
+pub mod pauli;
+pub mod stabilizer_tableau;
+pub mod state_vector_simulator;
+pub mod tableau;
 
 /// シミュレータの外部からもアクセスできるオペレーション
 pub trait SimulatorInterface {
@@ -24,8 +35,70 @@ pub trait SimulatorInterface {
     /// add Z gate
     fn add_z(&mut self, a: usize);
 
+    /// add Y gate
+    fn add_y(&mut self, a: usize);
+
+    /// add S dagger gate
+    fn add_s_dag(&mut self, a: usize);
+
+    /// add sqrt(X) gate
+    fn add_sqrt_x(&mut self, a: usize);
+
+    /// add sqrt(X) dagger gate
+    fn add_sqrt_x_dag(&mut self, a: usize);
+
+    /// add sqrt(Y) gate
+    fn add_sqrt_y(&mut self, a: usize);
+
+    /// add sqrt(Y) dagger gate
+    fn add_sqrt_y_dag(&mut self, a: usize);
+
+    /// add controlled Z gate
+    fn add_cz(&mut self, a: usize, b: usize);
+
+    /// add controlled Y gate
+    fn add_cy(&mut self, a: usize, b: usize);
+
+    /// add SWAP gate
+    fn add_swap(&mut self, a: usize, b: usize);
+
+    /// add iSWAP gate
+    fn add_iswap(&mut self, a: usize, b: usize);
+
+    /// add iSWAP dagger gate
+    fn add_iswap_dag(&mut self, a: usize, b: usize);
+
+    /// add X-controlled X gate
+    fn add_xcx(&mut self, a: usize, b: usize);
+
+    /// add X-controlled Y gate
+    fn add_xcy(&mut self, a: usize, b: usize);
+
+    /// add X-controlled Z gate
+    fn add_xcz(&mut self, a: usize, b: usize);
+
+    /// add Y-controlled X gate
+    fn add_ycx(&mut self, a: usize, b: usize);
+
+    /// add Y-controlled Y gate
+    fn add_ycy(&mut self, a: usize, b: usize);
+
+    /// add Y-controlled Z gate
+    fn add_ycz(&mut self, a: usize, b: usize);
+
+    /// add T gate (non-Clifford, StateVectorSimulatorのみ)
+    fn add_t(&mut self, a: usize) {
+        panic!("T gate on qubit {} is not supported by this simulator", a);
+    }
+
+    /// add T dagger gate (non-Clifford, StateVectorSimulatorのみ)
+    fn add_t_dag(&mut self, a: usize) {
+        panic!("T dagger gate on qubit {} is not supported by this simulator", a);
+    }
+
     /// add measurement
-    fn add_measurement(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32);
+    /// return the index of the outcome in the measurement record
+    fn add_measurement(&mut self, a: usize, error_rate: f32) -> usize;
 
     // add measurement as once
     //fn add_measurement_at_once(&mut self, a: Vec<usize>, register: &mut Array3<u8>);
@@ -34,114 +107,881 @@ pub trait SimulatorInterface {
     fn add_measurement_to_zero(&mut self, a: usize);
 
     /// add measurement_and_reset
-    fn add_measurement_and_reset(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32);
+    fn add_measurement_and_reset(&mut self, a: usize, error_rate: f32) -> usize;
+
+    /// add measurement in X basis
+    fn add_measurement_x(&mut self, a: usize, error_rate: f32) -> usize;
+
+    /// add measurement in Y basis
+    fn add_measurement_y(&mut self, a: usize, error_rate: f32) -> usize;
+
+    /// add reset to |0>
+    fn add_reset_z(&mut self, a: usize);
+
+    /// add reset to |+>
+    fn add_reset_x(&mut self, a: usize);
+
+    /// add reset to |+i>
+    fn add_reset_y(&mut self, a: usize);
+
+    /// add measurement_and_reset in X basis
+    fn add_measurement_and_reset_x(&mut self, a: usize, error_rate: f32) -> usize;
+
+    /// add measurement_and_reset in Y basis
+    fn add_measurement_and_reset_y(&mut self, a: usize, error_rate: f32) -> usize;
+
+    /// add measurement of Pauli product such as X0*Z3*Y5
+    /// (同じqubitのPauliはまとめ, 反エルミートな積は追加する時点でpanicする)
+    fn add_measurement_pauli_product(
+        &mut self,
+        product: Vec<(usize, Pauli)>,
+        error_rate: f32,
+    ) -> usize {
+        self.add_measurement_signed_pauli_product(product, 0, error_rate)
+    }
+
+    /// add measurement of (-1)^sign * Pauli product
+    fn add_measurement_signed_pauli_product(
+        &mut self,
+        product: Vec<(usize, Pauli)>,
+        sign: u8,
+        error_rate: f32,
+    ) -> usize;
+
+    /// add Pauli on qubit a controlled by the measurement result rec[-k] (k = lookback)
+    fn add_feedback(&mut self, pauli: Pauli, lookback: usize, a: usize);
 
     /// add noise
     fn add_noise(&mut self, a: usize, noise_type: NoiseType);
 
+    /// add two qubit noise
+    fn add_noise2(&mut self, a: usize, b: usize, noise_type: NoiseType);
+
+    /// number of operations added so far
+    fn operation_count(&self) -> usize;
+
+    /// insert noise into the operations from start according to the noise model
+    fn insert_noise_model(&mut self, model: &NoiseModel, data_qubit: &[usize], start: usize);
+
     /// add Reset stabilizer tableau
     fn reset(&mut self);
 
     /// add run circuit
     fn run(&mut self);
 
-    /// direct measurement
-    fn measurement(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32);
+    /// direct measurement (the outcome is also appended to the measurement record)
+    fn measurement(&mut self, a: usize, error_rate: f32) -> u8;
+
+    /// number of measurements added so far
+    fn measurement_count(&self) -> usize;
+
+    /// measurement record of the last run
+    fn measurement_record(&self) -> &MeasurementRecord;
+
+    /// add detector (coords, rec[-k] for k in lookback)
+    /// return the index of the detector
+    fn add_detector(&mut self, coords: Vec<f32>, lookback: Vec<usize>) -> usize;
 
+    /// add rec[-k] for k in lookback to the logical observable
+    fn add_observable_include(&mut self, observable: usize, lookback: Vec<usize>);
+
+    /// number of detectors added so far
+    fn detector_count(&self) -> usize;
+
+    /// detection events of the last run (1 if the parity differs from the noiseless circuit)
+    fn detection_events(&self) -> &[u8];
+
+    /// logical observable flips of the last run
+    fn observable_flips(&self) -> &[u8];
+
+    /// circuit added so far
+    fn circuit(&self) -> &Circuit;
+
+    /// add an operation
+    fn add_operation(&mut self, operation: Operation) {
+        match operation {
+            Operation::CX(a, b) => self.add_cx(a, b),
+            Operation::H(a) => self.add_h(a),
+            Operation::S(a) => self.add_s(a),
+            Operation::X(a) => self.add_x(a),
+            Operation::Z(a) => self.add_z(a),
+            Operation::Y(a) => self.add_y(a),
+            Operation::SDag(a) => self.add_s_dag(a),
+            Operation::SqrtX(a) => self.add_sqrt_x(a),
+            Operation::SqrtXDag(a) => self.add_sqrt_x_dag(a),
+            Operation::SqrtY(a) => self.add_sqrt_y(a),
+            Operation::SqrtYDag(a) => self.add_sqrt_y_dag(a),
+            Operation::T(a) => self.add_t(a),
+            Operation::TDag(a) => self.add_t_dag(a),
+            Operation::CZ(a, b) => self.add_cz(a, b),
+            Operation::CY(a, b) => self.add_cy(a, b),
+            Operation::Swap(a, b) => self.add_swap(a, b),
+            Operation::ISwap(a, b) => self.add_iswap(a, b),
+            Operation::ISwapDag(a, b) => self.add_iswap_dag(a, b),
+            Operation::XCX(a, b) => self.add_xcx(a, b),
+            Operation::XCY(a, b) => self.add_xcy(a, b),
+            Operation::XCZ(a, b) => self.add_xcz(a, b),
+            Operation::YCX(a, b) => self.add_ycx(a, b),
+            Operation::YCY(a, b) => self.add_ycy(a, b),
+            Operation::YCZ(a, b) => self.add_ycz(a, b),
+            Operation::M(a, p) => {
+                self.add_measurement(a, p);
+            }
+            Operation::MR(a, p) => {
+                self.add_measurement_and_reset(a, p);
+            }
+            Operation::MX(a, p) => {
+                self.add_measurement_x(a, p);
+            }
+            Operation::MY(a, p) => {
+                self.add_measurement_y(a, p);
+            }
+            Operation::R(a) => self.add_reset_z(a),
+            Operation::RX(a) => self.add_reset_x(a),
+            Operation::RY(a) => self.add_reset_y(a),
+            Operation::MRX(a, p) => {
+                self.add_measurement_and_reset_x(a, p);
+            }
+            Operation::MRY(a, p) => {
+                self.add_measurement_and_reset_y(a, p);
+            }
+            Operation::MPP(product, sign, p) => {
+                self.add_measurement_signed_pauli_product(product, sign, p);
+            }
+            Operation::MToZero(a) => self.add_measurement_to_zero(a),
+            Operation::Feedback(pauli, lookback, a) => self.add_feedback(pauli, lookback, a),
+            Operation::Depolarizing(a, p) => self.add_noise(a, NoiseType::Depolarizing(p)),
+            Operation::PauliChannel1(a, px, py, pz) => {
+                self.add_noise(a, NoiseType::PauliChannel1(px, py, pz))
+            }
+            Operation::Depolarizing2(a, b, p) => {
+                self.add_noise2(a, b, NoiseType::Depolarizing2(p))
+            }
+            Operation::PauliChannel2(a, b, p) => self.add_noise2(a, b, NoiseType::PauliChannel2(p)),
+            Operation::Detector(coords, lookback) => {
+                self.add_detector(coords, lookback);
+            }
+            Operation::ObservableInclude(observable, lookback) => {
+                self.add_observable_include(observable, lookback)
+            }
+            Operation::Tick => self.add_tick(),
+            Operation::Repeat(n, body) => self.add_repeat(n, &body),
+        }
+    }
+
+    /// add TICK (区切りとなる層の境界)
+    fn add_tick(&mut self);
+
+    /// start以降のオペレーションをrepetitions回繰り返すREPEATブロックにまとめる
+    fn repeat_from(&mut self, start: usize, repetitions: usize);
+
+    /// add REPEAT block (bodyをrepetitions回繰り返す)
+    fn add_repeat(&mut self, repetitions: usize, body: &Circuit) {
+        let start = self.operation_count();
+        self.add_circuit(body);
+        self.repeat_from(start, repetitions);
+    }
+
+    /// add all operations of a circuit
+    fn add_circuit(&mut self, circuit: &Circuit) {
+        for op in circuit.iter() {
+            self.add_operation(op.clone());
+        }
+    }
 }
 
 pub enum SimulatorWrapper {
     CHPSimulator(CHPSimulator),
+    FrameSimulator(FrameSimulator),
+    StateVectorSimulator(StateVectorSimulator),
+}
+
+impl SimulatorWrapper {
+    /// sim_typeのsimulatorを生成する
+    pub fn new(sim_type: Type, qubit_num: usize, rng: SmallRng) -> Self {
+        match sim_type {
+            Type::CHPSimulator => SimulatorWrapper::CHPSimulator(CHPSimulator::new(qubit_num, rng)),
+            Type::FrameSimulator => {
+                SimulatorWrapper::FrameSimulator(FrameSimulator::new(qubit_num, rng))
+            }
+            Type::StateVectorSimulator => {
+                SimulatorWrapper::StateVectorSimulator(StateVectorSimulator::new(qubit_num, rng))
+            }
+        }
+    }
 }
 
 impl SimulatorInterface for SimulatorWrapper {
     fn add_cx(&mut self, a: usize, b: usize) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_cx(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_cx(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_cx(a, b),
         };
     }
 
     fn add_h(&mut self, a: usize) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_h(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_h(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_h(a),
         };
     }
 
     fn add_s(&mut self, a: usize) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_s(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_s(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_s(a),
         };
     }
 
     fn add_x(&mut self, a: usize) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_x(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_x(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_x(a),
         };
     }
 
     fn add_z(&mut self, a: usize) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_z(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_z(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_z(a),
+        };
+    }
+
+    fn add_y(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_y(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_y(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_y(a),
+        };
+    }
+
+    fn add_s_dag(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_s_dag(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_s_dag(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_s_dag(a),
+        };
+    }
+
+    fn add_sqrt_x(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_sqrt_x(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_sqrt_x(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_sqrt_x(a),
+        };
+    }
+
+    fn add_sqrt_x_dag(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_sqrt_x_dag(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_sqrt_x_dag(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_sqrt_x_dag(a),
+        };
+    }
+
+    fn add_sqrt_y(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_sqrt_y(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_sqrt_y(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_sqrt_y(a),
+        };
+    }
+
+    fn add_sqrt_y_dag(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_sqrt_y_dag(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_sqrt_y_dag(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_sqrt_y_dag(a),
+        };
+    }
+
+    fn add_cz(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_cz(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_cz(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_cz(a, b),
+        };
+    }
+
+    fn add_cy(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_cy(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_cy(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_cy(a, b),
+        };
+    }
+
+    fn add_swap(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_swap(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_swap(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_swap(a, b),
+        };
+    }
+
+    fn add_iswap(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_iswap(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_iswap(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_iswap(a, b),
+        };
+    }
+
+    fn add_iswap_dag(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_iswap_dag(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_iswap_dag(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_iswap_dag(a, b),
+        };
+    }
+
+    fn add_xcx(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_xcx(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_xcx(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_xcx(a, b),
+        };
+    }
+
+    fn add_xcy(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_xcy(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_xcy(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_xcy(a, b),
+        };
+    }
+
+    fn add_xcz(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_xcz(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_xcz(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_xcz(a, b),
+        };
+    }
+
+    fn add_ycx(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_ycx(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_ycx(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_ycx(a, b),
+        };
+    }
+
+    fn add_ycy(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_ycy(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_ycy(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_ycy(a, b),
+        };
+    }
+
+    fn add_ycz(&mut self, a: usize, b: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_ycz(a, b),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_ycz(a, b),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_ycz(a, b),
         };
     }
 
-    fn add_measurement(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32) {
+    fn add_t(&mut self, a: usize) {
         match *self {
-            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement(a, register, error_rate),
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_t(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_t(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_t(a),
+        }
+    }
+
+    fn add_t_dag(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_t_dag(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_t_dag(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_t_dag(a),
+        }
+    }
+
+    fn add_measurement(&mut self, a: usize, error_rate: f32) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement(a, error_rate),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_measurement(a, error_rate),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_measurement(a, error_rate),
         }
     }
 
     fn add_measurement_to_zero(&mut self, a: usize) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement_to_zero(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_measurement_to_zero(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_measurement_to_zero(a),
+        }
+    }
+
+    fn add_measurement_and_reset(&mut self, a: usize, error_rate: f32) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement_and_reset(a, error_rate),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_measurement_and_reset(a, error_rate),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_measurement_and_reset(a, error_rate),
+        }
+    }
+
+    fn add_measurement_x(&mut self, a: usize, error_rate: f32) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement_x(a, error_rate),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_measurement_x(a, error_rate),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_measurement_x(a, error_rate),
+        }
+    }
+
+    fn add_measurement_y(&mut self, a: usize, error_rate: f32) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement_y(a, error_rate),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_measurement_y(a, error_rate),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_measurement_y(a, error_rate),
+        }
+    }
+
+    fn add_reset_z(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_reset_z(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_reset_z(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_reset_z(a),
+        }
+    }
+
+    fn add_reset_x(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_reset_x(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_reset_x(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_reset_x(a),
+        }
+    }
+
+    fn add_reset_y(&mut self, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_reset_y(a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_reset_y(a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_reset_y(a),
+        }
+    }
+
+    fn add_measurement_and_reset_x(&mut self, a: usize, error_rate: f32) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement_and_reset_x(a, error_rate),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_measurement_and_reset_x(a, error_rate),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_measurement_and_reset_x(a, error_rate),
+        }
+    }
+
+    fn add_measurement_and_reset_y(&mut self, a: usize, error_rate: f32) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement_and_reset_y(a, error_rate),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_measurement_and_reset_y(a, error_rate),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_measurement_and_reset_y(a, error_rate),
+        }
+    }
+
+    fn add_measurement_signed_pauli_product(
+        &mut self,
+        product: Vec<(usize, Pauli)>,
+        sign: u8,
+        error_rate: f32,
+    ) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement_signed_pauli_product(product, sign, error_rate),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_measurement_signed_pauli_product(product, sign, error_rate),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_measurement_signed_pauli_product(product, sign, error_rate),
+        }
+    }
+
+    fn add_feedback(&mut self, pauli: Pauli, lookback: usize, a: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_feedback(pauli, lookback, a),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_feedback(pauli, lookback, a),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => {
+                sim.add_feedback(pauli, lookback, a)
+            }
+        }
+    }
+
+    fn measurement(&mut self, a: usize, error_rate: f32) -> u8 {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.measurement(a, error_rate),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.measurement(a, error_rate),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.measurement(a, error_rate),
+        }
+    }
+
+    fn measurement_count(&self) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref sim) => sim.measurement_count(),
+            SimulatorWrapper::FrameSimulator(ref sim) => sim.measurement_count(),
+            SimulatorWrapper::StateVectorSimulator(ref sim) => sim.measurement_count(),
+        }
+    }
+
+    fn measurement_record(&self) -> &MeasurementRecord {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref sim) => sim.measurement_record(),
+            SimulatorWrapper::FrameSimulator(ref sim) => sim.measurement_record(),
+            SimulatorWrapper::StateVectorSimulator(ref sim) => sim.measurement_record(),
+        }
+    }
+
+    fn add_detector(&mut self, coords: Vec<f32>, lookback: Vec<usize>) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_detector(coords, lookback),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_detector(coords, lookback),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_detector(coords, lookback),
+        }
+    }
+
+    fn add_observable_include(&mut self, observable: usize, lookback: Vec<usize>) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => {
+                sim.add_observable_include(observable, lookback)
+            }
+            SimulatorWrapper::FrameSimulator(ref mut sim) => {
+                sim.add_observable_include(observable, lookback)
+            }
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => {
+                sim.add_observable_include(observable, lookback)
+            }
         }
     }
 
-    fn add_measurement_and_reset(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32) {
+    fn detector_count(&self) -> usize {
         match *self {
-            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_measurement_and_reset(a, register, error_rate),
+            SimulatorWrapper::CHPSimulator(ref sim) => sim.detector_count(),
+            SimulatorWrapper::FrameSimulator(ref sim) => sim.detector_count(),
+            SimulatorWrapper::StateVectorSimulator(ref sim) => sim.detector_count(),
         }
     }
 
-    fn measurement(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32) {
+    fn detection_events(&self) -> &[u8] {
         match *self {
-            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.measurement(a, register, error_rate),
+            SimulatorWrapper::CHPSimulator(ref sim) => sim.detection_events(),
+            SimulatorWrapper::FrameSimulator(ref sim) => sim.detection_events(),
+            SimulatorWrapper::StateVectorSimulator(ref sim) => sim.detection_events(),
+        }
+    }
+
+    fn observable_flips(&self) -> &[u8] {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref sim) => sim.observable_flips(),
+            SimulatorWrapper::FrameSimulator(ref sim) => sim.observable_flips(),
+            SimulatorWrapper::StateVectorSimulator(ref sim) => sim.observable_flips(),
+        }
+    }
+
+    fn circuit(&self) -> &Circuit {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref sim) => sim.circuit(),
+            SimulatorWrapper::FrameSimulator(ref sim) => sim.circuit(),
+            SimulatorWrapper::StateVectorSimulator(ref sim) => sim.circuit(),
+        }
+    }
+
+    fn add_tick(&mut self) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_tick(),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_tick(),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_tick(),
+        }
+    }
+
+    fn repeat_from(&mut self, start: usize, repetitions: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.repeat_from(start, repetitions),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.repeat_from(start, repetitions),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => {
+                sim.repeat_from(start, repetitions)
+            }
         }
     }
 
     fn add_noise(&mut self, a: usize, noise_type: NoiseType) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_noise(a, noise_type),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_noise(a, noise_type),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_noise(a, noise_type),
+        }
+    }
+
+    fn add_noise2(&mut self, a: usize, b: usize, noise_type: NoiseType) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => sim.add_noise2(a, b, noise_type),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.add_noise2(a, b, noise_type),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.add_noise2(a, b, noise_type),
+        }
+    }
+
+    fn operation_count(&self) -> usize {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref sim) => sim.operation_count(),
+            SimulatorWrapper::FrameSimulator(ref sim) => sim.operation_count(),
+            SimulatorWrapper::StateVectorSimulator(ref sim) => sim.operation_count(),
+        }
+    }
+
+    fn insert_noise_model(&mut self, model: &NoiseModel, data_qubit: &[usize], start: usize) {
+        match *self {
+            SimulatorWrapper::CHPSimulator(ref mut sim) => {
+                sim.insert_noise_model(model, data_qubit, start)
+            }
+            SimulatorWrapper::FrameSimulator(ref mut sim) => {
+                sim.insert_noise_model(model, data_qubit, start)
+            }
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => {
+                sim.insert_noise_model(model, data_qubit, start)
+            }
         }
     }
 
     fn reset(&mut self) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.reset(),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.reset(),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.reset(),
         };
     }
 
     fn run(&mut self) {
         match *self {
             SimulatorWrapper::CHPSimulator(ref mut sim) => sim.run(),
+            SimulatorWrapper::FrameSimulator(ref mut sim) => sim.run(),
+            SimulatorWrapper::StateVectorSimulator(ref mut sim) => sim.run(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Operation {
     CX(usize, usize),
     H(usize),
     S(usize),
     X(usize),
     Z(usize),
-    M(usize, Rc<Cell<u8>>, f32),
-    MR(usize, Rc<Cell<u8>>, f32),
+    Y(usize),
+    SDag(usize),
+    SqrtX(usize),
+    SqrtXDag(usize),
+    SqrtY(usize),
+    SqrtYDag(usize),
+    /// T gate (non-Clifford, StateVectorSimulatorのみ)
+    T(usize),
+    TDag(usize),
+    CZ(usize, usize),
+    CY(usize, usize),
+    Swap(usize, usize),
+    ISwap(usize, usize),
+    ISwapDag(usize, usize),
+    XCX(usize, usize),
+    XCY(usize, usize),
+    XCZ(usize, usize),
+    YCX(usize, usize),
+    YCY(usize, usize),
+    YCZ(usize, usize),
+    M(usize, f32),
+    MR(usize, f32),
+    MX(usize, f32),
+    MY(usize, f32),
+    R(usize),
+    RX(usize),
+    RY(usize),
+    MRX(usize, f32),
+    MRY(usize, f32),
+    /// (-1)^sign * Pauli積の測定 (Pauli積, sign, error rate). 同じqubitは1回だけ現れる
+    MPP(Vec<(usize, Pauli)>, u8, f32),
     MToZero(usize),
+    /// 測定結果rec[-k]が1のときqubitにPauliを作用させる (Pauli, k, qubit)
+    Feedback(Pauli, usize, usize),
     Depolarizing(usize, f32),
+    PauliChannel1(usize, f32, f32, f32),
+    Depolarizing2(usize, usize, f32),
+    PauliChannel2(usize, usize, [f32; 15]),
+    /// detector (座標, 参照する測定結果rec[-k]のk)
+    Detector(Vec<f32>, Vec<usize>),
+    /// logical observableに測定結果rec[-k]を加える
+    ObservableInclude(usize, Vec<usize>),
+    /// 並列に実行されるgateの層の区切り
+    Tick,
+    /// 回路をn回繰り返すブロック (展開せずに実行する)
+    Repeat(usize, Circuit),
     //MAll(char)
 }
 
+impl Operation {
+    /// (-1)^sign * Pauli積の測定. 同じqubitのPauliはまとめ, 反エルミートな積はErr
+    pub fn measurement_pauli_product(
+        product: &[(usize, Pauli)],
+        sign: u8,
+        error_rate: f32,
+    ) -> Result<Operation, String> {
+        let (combined_sign, product) = combine_pauli_product(product)?;
+        Ok(Operation::MPP(product, sign ^ combined_sign, error_rate))
+    }
+
+    /// オペレーションが作用するqubitの一覧
+    pub fn qubits(&self) -> Vec<usize> {
+        match self {
+            Operation::H(a)
+            | Operation::S(a)
+            | Operation::X(a)
+            | Operation::Z(a)
+            | Operation::Y(a)
+            | Operation::SDag(a)
+            | Operation::SqrtX(a)
+            | Operation::SqrtXDag(a)
+            | Operation::SqrtY(a)
+            | Operation::SqrtYDag(a)
+            | Operation::T(a)
+            | Operation::TDag(a)
+            | Operation::M(a, _)
+            | Operation::MR(a, _)
+            | Operation::MX(a, _)
+            | Operation::MY(a, _)
+            | Operation::R(a)
+            | Operation::RX(a)
+            | Operation::RY(a)
+            | Operation::MRX(a, _)
+            | Operation::MRY(a, _)
+            | Operation::MToZero(a)
+            | Operation::Feedback(_, _, a)
+            | Operation::Depolarizing(a, _)
+            | Operation::PauliChannel1(a, _, _, _) => vec![*a],
+            Operation::CX(a, b)
+            | Operation::CZ(a, b)
+            | Operation::CY(a, b)
+            | Operation::Swap(a, b)
+            | Operation::ISwap(a, b)
+            | Operation::ISwapDag(a, b)
+            | Operation::XCX(a, b)
+            | Operation::XCY(a, b)
+            | Operation::XCZ(a, b)
+            | Operation::YCX(a, b)
+            | Operation::YCY(a, b)
+            | Operation::YCZ(a, b)
+            | Operation::Depolarizing2(a, b, _)
+            | Operation::PauliChannel2(a, b, _) => vec![*a, *b],
+            Operation::MPP(product, _, _) => product.iter().map(|(a, _)| *a).collect(),
+            Operation::Detector(_, _) | Operation::ObservableInclude(_, _) | Operation::Tick => {
+                vec![]
+            }
+            Operation::Repeat(_, body) => {
+                let qubits: BTreeSet<usize> = body.iter().flat_map(|op| op.qubits()).collect();
+                qubits.into_iter().collect()
+            }
+        }
+    }
+
+    /// オペレーションの名前 (Stimの表記に合わせる)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::CX(_, _) => "CX",
+            Operation::H(_) => "H",
+            Operation::S(_) => "S",
+            Operation::X(_) => "X",
+            Operation::Z(_) => "Z",
+            Operation::Y(_) => "Y",
+            Operation::SDag(_) => "S_DAG",
+            Operation::SqrtX(_) => "SQRT_X",
+            Operation::SqrtXDag(_) => "SQRT_X_DAG",
+            Operation::SqrtY(_) => "SQRT_Y",
+            Operation::SqrtYDag(_) => "SQRT_Y_DAG",
+            Operation::T(_) => "T",
+            Operation::TDag(_) => "T_DAG",
+            Operation::CZ(_, _) => "CZ",
+            Operation::CY(_, _) => "CY",
+            Operation::Swap(_, _) => "SWAP",
+            Operation::ISwap(_, _) => "ISWAP",
+            Operation::ISwapDag(_, _) => "ISWAP_DAG",
+            Operation::XCX(_, _) => "XCX",
+            Operation::XCY(_, _) => "XCY",
+            Operation::XCZ(_, _) => "XCZ",
+            Operation::YCX(_, _) => "YCX",
+            Operation::YCY(_, _) => "YCY",
+            Operation::YCZ(_, _) => "YCZ",
+            Operation::M(_, _) => "M",
+            Operation::MR(_, _) => "MR",
+            Operation::MX(_, _) => "MX",
+            Operation::MY(_, _) => "MY",
+            Operation::R(_) => "R",
+            Operation::RX(_) => "RX",
+            Operation::RY(_) => "RY",
+            Operation::MRX(_, _) => "MRX",
+            Operation::MRY(_, _) => "MRY",
+            Operation::MPP(_, _, _) => "MPP",
+            Operation::MToZero(_) => "M_TO_ZERO",
+            // Stimでは"CX rec[-k] q"と書く
+            Operation::Feedback(pauli, _, _) => match pauli {
+                Pauli::I => "I",
+                Pauli::X => "CX",
+                Pauli::Y => "CY",
+                Pauli::Z => "CZ",
+            },
+            Operation::Depolarizing(_, _) => "DEPOLARIZE1",
+            Operation::PauliChannel1(_, _, _, _) => "PAULI_CHANNEL_1",
+            Operation::Depolarizing2(_, _, _) => "DEPOLARIZE2",
+            Operation::PauliChannel2(_, _, _) => "PAULI_CHANNEL_2",
+            Operation::Detector(_, _) => "DETECTOR",
+            Operation::ObservableInclude(_, _) => "OBSERVABLE_INCLUDE",
+            Operation::Tick => "TICK",
+            Operation::Repeat(_, _) => "REPEAT",
+        }
+    }
+
+    /// measurement recordに結果を追加するオペレーションか
+    pub fn is_measurement(&self) -> bool {
+        matches!(
+            self,
+            Operation::M(_, _)
+                | Operation::MR(_, _)
+                | Operation::MX(_, _)
+                | Operation::MY(_, _)
+                | Operation::MRX(_, _)
+                | Operation::MRY(_, _)
+                | Operation::MPP(_, _, _)
+        )
+    }
+
+    /// ノイズチャネルか
+    pub fn is_noise(&self) -> bool {
+        matches!(
+            self,
+            Operation::Depolarizing(_, _)
+                | Operation::PauliChannel1(_, _, _, _)
+                | Operation::Depolarizing2(_, _, _)
+                | Operation::PauliChannel2(_, _, _)
+        )
+    }
+
+    /// ノイズを取り除いたオペレーション (ノイズチャネルはNone, 測定のerror rateは0)
+    pub fn noiseless(&self) -> Option<Operation> {
+        match self {
+            op if op.is_noise() => None,
+            Operation::M(a, _) => Some(Operation::M(*a, 0.0)),
+            Operation::MR(a, _) => Some(Operation::MR(*a, 0.0)),
+            Operation::MX(a, _) => Some(Operation::MX(*a, 0.0)),
+            Operation::MY(a, _) => Some(Operation::MY(*a, 0.0)),
+            Operation::MRX(a, _) => Some(Operation::MRX(*a, 0.0)),
+            Operation::MRY(a, _) => Some(Operation::MRY(*a, 0.0)),
+            Operation::MPP(product, sign, _) => Some(Operation::MPP(product.clone(), *sign, 0.0)),
+            Operation::Repeat(n, body) => Some(Operation::Repeat(
+                *n,
+                body.iter().filter_map(Operation::noiseless).collect(),
+            )),
+            other => Some(other.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Type {
     CHPSimulator,
+    FrameSimulator,
+    StateVectorSimulator,
 }
@@ -1,312 +1,550 @@
 use super::{
     core::{Dispatcher, SimulatorCore},
+    measurement_record::MeasurementRecord,
+    pauli::{combine_pauli_product, Pauli},
+    stabilizer_tableau::StabilizerTableau,
     Operation, SimulatorInterface,
 };
-use crate::noise::noise_model::NoiseType;
-use ndarray::*;
-use rand::{rngs::SmallRng, Rng};
-use std::cell::Cell;
-use std::rc::Rc;
+use crate::circuit::Circuit;
+use crate::noise::{
+    circuit_noise,
+    noise_model::{NoiseModel, NoiseType},
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 
 pub struct CHPSimulatorCore {
-    qubit_num: usize,
-    stabilizer_tableau: Array2<u8>,
+    stabilizer_tableau: StabilizerTableau,
     rng: SmallRng,
-    classical_register: Vec<u8>,
+    measurement_record: MeasurementRecord,
+    detection_events: Vec<u8>,
+    observable_flips: Vec<u8>,
 }
 
 pub struct CHPSimulator {
     core: CHPSimulatorCore,
     dispatcher: Dispatcher,
+    measurement_count: usize,
+    detector_count: usize,
+    observable_count: usize,
+    /// ノイズのない回路でのdetectorとobservableの値 (回路が変わると計算し直す)
+    reference: Option<(Vec<u8>, Vec<u8>)>,
 }
 
 impl CHPSimulator {
     pub fn new(qubit_num: usize, rng: SmallRng) -> Self {
-        let size = qubit_num * 2;
-        let stabilizer_tableau: Array2<u8> =
-            concatenate![Axis(1), Array::eye(size), Array::zeros((size, 1))];
+        let stabilizer_tableau = StabilizerTableau::new(qubit_num);
 
         let operations = Vec::new();
-        let classical_register = vec![0; qubit_num];
-        let round = 1; // デフォルト値
-
         CHPSimulator {
-            core: CHPSimulatorCore {
-                qubit_num,
-                stabilizer_tableau,
-                rng,
-                classical_register,
-            },
-            dispatcher: Dispatcher::new(operations, round),
+            core: CHPSimulatorCore::new(stabilizer_tableau, rng),
+            dispatcher: Dispatcher::new(operations),
+            measurement_count: 0,
+            detector_count: 0,
+            observable_count: 0,
+            reference: None,
         }
     }
 
-    pub fn result(&self) -> &Vec<u8> {
-        &self.core.classical_register
+    /// オペレーションを追加する
+    fn push(&mut self, operation: Operation) {
+        self.reference = None;
+        self.dispatcher.push(operation);
+    }
+
+    /// 測定オペレーションを追加し, measurement recordでのindexを返す
+    fn push_measurement(&mut self, operation: Operation) -> usize {
+        self.push(operation);
+        self.measurement_count += 1;
+        self.measurement_count - 1
+    }
+
+    /// ノイズを除いた回路を|0...0>から実行し, detectorとobservableの参照値を求める
+    fn reference_sample(&self) -> (Vec<u8>, Vec<u8>) {
+        let reference = reference_sample(
+            self.core.stabilizer_tableau.qubit_num(),
+            self.dispatcher.operations(),
+            self.observable_count,
+        );
+        (reference.detectors, reference.observables)
+    }
+
+    /// 最後に実行した回路の後の状態
+    pub fn stabilizer_tableau(&self) -> &StabilizerTableau {
+        &self.core.stabilizer_tableau
+    }
+
+    /// Z_aの期待値 (+1, -1, 結果がランダムなら0) を状態を変えずに求める
+    pub fn peek_z(&mut self, a: usize) -> i8 {
+        self.core.peek_z(a)
+    }
+
+    /// X_aの期待値 (+1, -1, 結果がランダムなら0) を状態を変えずに求める
+    pub fn peek_x(&mut self, a: usize) -> i8 {
+        self.peek_observable(&[(a, Pauli::X)])
+    }
+
+    /// Y_aの期待値 (+1, -1, 結果がランダムなら0) を状態を変えずに求める
+    pub fn peek_y(&mut self, a: usize) -> i8 {
+        self.peek_observable(&[(a, Pauli::Y)])
+    }
+
+    /// X0*Z3*Y5のようなPauli積の期待値 (+1, -1, 結果がランダムなら0) を状態を変えずに求める
+    pub fn peek_observable(&mut self, product: &[(usize, Pauli)]) -> i8 {
+        let (sign, product) = combine_pauli_product(product).unwrap_or_else(|e| panic!("{}", e));
+        self.core.peek_pauli_product(&product, sign)
     }
 }
 
-impl CHPSimulatorCore {
-    fn g(&self, x1: u8, z1: u8, x2: u8, z2: u8) -> i8 {
-        match (x1, z1, x2, z2) {
-            (0, 0, _, _) => 0,
-            (1, 1, _, _) => (z2 as i8 - x2 as i8),
-            (1, 0, _, _) => z2 as i8 * (2 * x2 as i8 - 1),
-            (0, 1, _, _) => x2 as i8 * (1 - 2 * z2 as i8),
-            _ => panic!("Tableau parameters must be 0 or 1"),
-        }
+/// ノイズを除いた回路を|0...0>から実行した結果
+pub struct ReferenceSample {
+    pub measurements: Vec<u8>,
+    pub detectors: Vec<u8>,
+    pub observables: Vec<u8>,
+    /// 結果がランダムだったMToZeroで射影する前の, Z_aと反可換なstabilizer (MToZeroの順, 決定的ならNone)
+    pub gauges: Vec<Option<Vec<(usize, Pauli)>>>,
+    /// 実行後の状態
+    pub tableau: StabilizerTableau,
+}
+
+/// ノイズを除いた回路を|0...0>から実行する (ランダムな測定結果はseed 0で決める)
+pub fn reference_sample(
+    qubit_num: usize,
+    operations: &[Operation],
+    observable_count: usize,
+) -> ReferenceSample {
+    let mut core = CHPSimulatorCore::new(
+        StabilizerTableau::new(qubit_num),
+        SmallRng::seed_from_u64(0),
+    );
+    core.observable_flips = vec![0; observable_count];
+    let mut gauges = Vec::new();
+    execute_reference(&mut core, operations, &mut gauges);
+
+    ReferenceSample {
+        measurements: core.measurement_record.bits().to_vec(),
+        detectors: core.detection_events,
+        observables: core.observable_flips,
+        gauges,
+        tableau: core.stabilizer_tableau,
     }
+}
 
-    fn row_sum(&mut self, h: usize, i: usize) {
-        let mut g_sum = 0;
+/// ノイズを除いてoperationsを実行し, MToZeroごとのgaugeを記録する (REPEATブロックは繰り返し実行する)
+fn execute_reference(
+    core: &mut CHPSimulatorCore,
+    operations: &[Operation],
+    gauges: &mut Vec<Option<Vec<(usize, Pauli)>>>,
+) {
+    for op in operations.iter().filter_map(Operation::noiseless) {
+        match op {
+            Operation::MToZero(a) => {
+                let tableau = &core.stabilizer_tableau;
+                gauges.push(tableau.random_measurement_pivot(a).map(|p| {
+                    (0..tableau.qubit_num())
+                        .map(|q| {
+                            (
+                                q,
+                                Pauli::from_bits(tableau.x_bit(p, q), tableau.z_bit(p, q)),
+                            )
+                        })
+                        .filter(|&(_, pauli)| pauli != Pauli::I)
+                        .collect()
+                }));
+            }
+            Operation::Repeat(n, ref body) => {
+                for _ in 0..n {
+                    execute_reference(core, body.operations(), gauges);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        core.execute(&op);
+    }
+}
 
-        for j in 0..self.qubit_num {
-            g_sum += self.g(
-                self.stabilizer_tableau[[i, j]],
-                self.stabilizer_tableau[[i, self.qubit_num + j]],
-                self.stabilizer_tableau[[h, j]],
-                self.stabilizer_tableau[[h, self.qubit_num + j]],
-            );
+impl CHPSimulatorCore {
+    fn new(stabilizer_tableau: StabilizerTableau, rng: SmallRng) -> Self {
+        CHPSimulatorCore {
+            stabilizer_tableau,
+            rng,
+            measurement_record: MeasurementRecord::new(),
+            detection_events: Vec::new(),
+            observable_flips: Vec::new(),
         }
+    }
 
-        let checker = 2 * self.stabilizer_tableau[[h, self.qubit_num * 2]] as i8
-            + 2 * self.stabilizer_tableau[[i, self.qubit_num * 2]] as i8
-            + g_sum;
+    /// rec[-k]のparity
+    fn parity(&self, lookback: &[usize]) -> u8 {
+        lookback
+            .iter()
+            .fold(0, |parity, &k| parity ^ self.measurement_record.lookback(k))
+    }
 
-        match checker % 4 {
-            2 | -2 => self.stabilizer_tableau[[h, self.qubit_num * 2]] = 1,
-            0 => self.stabilizer_tableau[[h, self.qubit_num * 2]] = 0,
-            _ => panic!("Error at row_sum value: {}", checker % 4),
+    /// オペレーションを1つ実行する
+    fn execute(&mut self, op: &Operation) {
+        match op {
+            Operation::CX(a, b) => self.cx(*a, *b),
+            Operation::H(a) => self.h(*a),
+            Operation::Depolarizing(a, p) => self.depolarizing(*a, *p),
+            Operation::PauliChannel1(a, px, py, pz) => self.pauli_channel_1(*a, *px, *py, *pz),
+            Operation::Depolarizing2(a, b, p) => self.depolarizing_2(*a, *b, *p),
+            Operation::PauliChannel2(a, b, p) => self.pauli_channel_2(*a, *b, p),
+            Operation::MR(a, error_rate) => {
+                self.measurement_and_reset(*a, *error_rate);
+            }
+            Operation::M(a, error_rate) => {
+                self.measurement(*a, *error_rate);
+            }
+            Operation::MToZero(a) => self.measurement_to_zero(*a),
+            Operation::Feedback(pauli, lookback, a) => self.feedback(*pauli, *lookback, *a),
+            Operation::MX(a, error_rate) => {
+                self.measurement_x(*a, *error_rate);
+            }
+            Operation::MY(a, error_rate) => {
+                self.measurement_y(*a, *error_rate);
+            }
+            Operation::MPP(product, sign, error_rate) => {
+                self.measurement_pauli_product(product, *sign, *error_rate);
+            }
+            Operation::R(a) => self.reset_z(*a),
+            Operation::RX(a) => self.reset_x(*a),
+            Operation::RY(a) => self.reset_y(*a),
+            Operation::MRX(a, error_rate) => {
+                self.measurement_and_reset_x(*a, *error_rate);
+            }
+            Operation::MRY(a, error_rate) => {
+                self.measurement_and_reset_y(*a, *error_rate);
+            }
+            Operation::S(a) => self.s(*a),
+            Operation::X(a) => self.x(*a),
+            Operation::Z(a) => self.z(*a),
+            Operation::Y(a) => self.y(*a),
+            Operation::SDag(a) => self.s_dag(*a),
+            Operation::SqrtX(a) => self.sqrt_x(*a),
+            Operation::SqrtXDag(a) => self.sqrt_x_dag(*a),
+            Operation::SqrtY(a) => self.sqrt_y(*a),
+            Operation::SqrtYDag(a) => self.sqrt_y_dag(*a),
+            Operation::T(_) | Operation::TDag(_) => {
+                panic!("{:?} is not a Clifford gate, use StateVectorSimulator", op)
+            }
+            Operation::CZ(a, b) => self.cz(*a, *b),
+            Operation::CY(a, b) => self.cy(*a, *b),
+            Operation::Swap(a, b) => self.swap(*a, *b),
+            Operation::ISwap(a, b) => self.iswap(*a, *b),
+            Operation::ISwapDag(a, b) => self.iswap_dag(*a, *b),
+            Operation::XCX(a, b) => self.xcx(*a, *b),
+            Operation::XCY(a, b) => self.xcy(*a, *b),
+            Operation::XCZ(a, b) => self.xcz(*a, *b),
+            Operation::YCX(a, b) => self.ycx(*a, *b),
+            Operation::YCY(a, b) => self.ycy(*a, *b),
+            Operation::YCZ(a, b) => self.ycz(*a, *b),
+            Operation::Detector(_, lookback) => self.detector(lookback),
+            Operation::ObservableInclude(observable, lookback) => {
+                self.observable_include(*observable, lookback)
+            }
+            Operation::Tick => (),
+            // bodyを展開せずにn回実行する
+            Operation::Repeat(n, body) => {
+                for _ in 0..*n {
+                    for op in body.iter() {
+                        self.execute(op);
+                    }
+                }
+            }
         }
+    }
 
-        let (mut row_h, row_i) = self
-            .stabilizer_tableau
-            .multi_slice_mut((s![h, ..self.qubit_num * 2], s![i, ..self.qubit_num * 2]));
+    /// noiseless measurement in Z basis
+    fn measure_z(&mut self, a: usize) -> u8 {
+        match self.stabilizer_tableau.random_measurement_pivot(a) {
+            // 一つでもXpa = 1のとき、結果はランダム
+            Some(p) => {
+                // rpを1/2でセットし、これが観測結果となる
+                let outcome = if self.rng.gen::<f32>() < 0.5 { 1 } else { 0 };
+                self.stabilizer_tableau.collapse(p, a, outcome);
+                outcome
+            }
+            // 測定結果が決定的のとき
+            None => self.stabilizer_tableau.deterministic_measurement(a),
+        }
+    }
 
-        row_h ^= &row_i;
+    /// 測定エラーを加えた結果をmeasurement recordに追加する
+    fn record_measurement(&mut self, outcome: u8, error_rate: f32) -> u8 {
+        let outcome = if self.rng.gen::<f32>() < error_rate {
+            if cfg!(debug_assertions) {
+                println!("measurement error: rec[{}]", self.measurement_record.len());
+            }
+            outcome ^ 1
+        } else {
+            outcome
+        };
+        self.measurement_record.push(outcome);
+        outcome
     }
 
-    fn row_sum_temp(&self, i: usize, temp: &mut Array1<u8>) {
-        let mut g_sum = 0;
+    /// 測定結果が決定的なら期待値±1, ランダムなら0 (tableauのscratch行以外は変えない)
+    fn peek_z(&mut self, a: usize) -> i8 {
+        match self.stabilizer_tableau.random_measurement_pivot(a) {
+            Some(_) => 0,
+            None => expectation(self.stabilizer_tableau.deterministic_measurement(a)),
+        }
+    }
 
-        for j in 0..self.qubit_num {
-            g_sum += self.g(
-                self.stabilizer_tableau[[i, j]],
-                self.stabilizer_tableau[[i, self.qubit_num + j]],
-                temp[j],
-                temp[self.qubit_num + j],
-            );
+    /// (-1)^sign * Pauli積についてのpeek_z
+    fn peek_pauli_product(&mut self, product: &[(usize, Pauli)], sign: u8) -> i8 {
+        let (x, z) = self.stabilizer_tableau.pack_pauli_product(product);
+        match self
+            .stabilizer_tableau
+            .random_pauli_measurement_pivot(&x, &z)
+        {
+            Some(_) => 0,
+            None => expectation(
+                self.stabilizer_tableau
+                    .deterministic_pauli_measurement(&x, &z)
+                    ^ sign,
+            ),
         }
+    }
 
-        let checker = 2 * temp[self.qubit_num * 2] as i8
-            + 2 * self.stabilizer_tableau[[i, self.qubit_num * 2]] as i8
-            + g_sum;
+    /// noiseless reset to |0>
+    fn reset_z(&mut self, a: usize) {
+        if self.measure_z(a) == 1 {
+            self.x(a);
+        }
+    }
 
-        match checker % 4 {
-            2 | -2 => temp[self.qubit_num * 2] = 1,
-            0 => temp[self.qubit_num * 2] = 0,
-            _ => panic!("Error at row_sum"),
+    /// apply single qubit Pauli operator
+    fn pauli(&mut self, a: usize, pauli: Pauli) {
+        match pauli {
+            Pauli::I => (),
+            Pauli::X => self.x(a),
+            Pauli::Y => self.y(a),
+            Pauli::Z => self.z(a),
         }
     }
 }
 
+/// 測定結果0, 1を期待値+1, -1に変換する
+fn expectation(outcome: u8) -> i8 {
+    1 - 2 * outcome as i8
+}
+
 impl SimulatorCore for CHPSimulatorCore {
     /// CNOT gate
     fn cx(&mut self, a: usize, b: usize) {
-        let (mut r, x_a, mut x_b, mut z_a, z_b) = self.stabilizer_tableau.multi_slice_mut((
-            s![.., -1],
-            s![.., a],
-            s![.., b],
-            s![.., self.qubit_num + a],
-            s![.., self.qubit_num + b],
-        ));
-
-        // rを計算
-        let buf = &x_a * &z_b * (&x_b ^ &z_a ^ 1);
-        r ^= &buf;
-
-        // xを計算
-        x_b ^= &x_a;
-
-        // zを計算
-        z_a ^= &z_b;
+        self.stabilizer_tableau.cx(a, b);
     }
 
     /// Hadamard gate
     fn h(&mut self, a: usize) {
-        let (mut r, x_a, z_a) = self.stabilizer_tableau.multi_slice_mut((
-            s![.., -1],
-            s![.., a],
-            s![.., self.qubit_num + a],
-        ));
-
-        let buf = &x_a & &z_a;
-        r ^= &buf;
-
-        // XとZを入れ替える
-        Zip::from(x_a).and(z_a).for_each(::std::mem::swap);
+        self.stabilizer_tableau.h(a);
     }
 
     /// S gate (Phase gate)
     fn s(&mut self, a: usize) {
-        let (mut r, x_a, mut z_a) = self.stabilizer_tableau.multi_slice_mut((
-            s![.., -1],
-            s![.., a],
-            s![.., self.qubit_num + a],
-        ));
-
-        let buf = &x_a & &z_a;
-        r ^= &buf;
-
-        z_a ^= &x_a;
+        self.stabilizer_tableau.s(a);
     }
 
     ///X gate
     fn x(&mut self, a: usize) {
-        let (mut r, z_a) = self
-            .stabilizer_tableau
-            .multi_slice_mut((s![.., -1], s![.., self.qubit_num + a]));
-
-        r ^= &z_a;
+        self.stabilizer_tableau.x(a);
     }
 
     /// Z gate
     fn z(&mut self, a: usize) {
-        let (mut r, x_a) = self
-            .stabilizer_tableau
-            .multi_slice_mut((s![.., -1], s![.., a]));
-        r ^= &x_a;
+        self.stabilizer_tableau.z(a);
     }
 
-    /// measurement
-    fn measurement(&mut self, a: usize, register: &Rc<Cell<u8>>, error_rate: f32) -> bool {
-        let p = self
-            .stabilizer_tableau
-            .slice(s![self.qubit_num.., a])
-            .iter()
-            .enumerate()
-            .filter(|(_, &x)| x == 1)
-            .map(|(i, _)| i + self.qubit_num)
-            .collect::<Vec<usize>>();
-
-        // 一つでもXpa = 1のとき、結果はランダム
-        if p.len() != 0 {
-            let p_destabilizer = self
-                .stabilizer_tableau
-                .slice(s![..self.qubit_num, a])
-                .iter()
-                .enumerate()
-                .filter(|(_, &x)| x == 1)
-                .map(|(i, _)| i)
-                .collect::<Vec<usize>>();
+    /// Y gate
+    fn y(&mut self, a: usize) {
+        self.stabilizer_tableau.y(a);
+    }
 
-            p_destabilizer.iter().for_each(|&i| self.row_sum(i, p[0]));
-            p.iter().skip(1).for_each(|&i| self.row_sum(i, p[0]));
+    /// S dagger gate
+    fn s_dag(&mut self, a: usize) {
+        self.stabilizer_tableau.s_dag(a);
+    }
 
-            // (p[0] - qubit_num) 行目をp[0]行目に置換
-            let (mut q_n, mut q) = self
-                .stabilizer_tableau
-                .multi_slice_mut((s![p[0] - self.qubit_num, ..], s![p[0], ..]));
+    /// sqrt(X) gate
+    fn sqrt_x(&mut self, a: usize) {
+        self.stabilizer_tableau.sqrt_x(a);
+    }
 
-            q_n.assign(&q);
-            for i in q.iter_mut() {
-                *i = 0;
-            }
-            q[self.qubit_num + a] = 1;
+    /// sqrt(X) dagger gate
+    fn sqrt_x_dag(&mut self, a: usize) {
+        self.stabilizer_tableau.sqrt_x_dag(a);
+    }
 
-            // rpを1/2でセットし、これが観測結果となる
-            if self.rng.gen::<f32>() < 0.5 {
-                self.stabilizer_tableau[[p[0], self.qubit_num * 2]] = 1;
-            } else {
-                self.stabilizer_tableau[[p[0], self.qubit_num * 2]] = 0;
-            }
+    /// sqrt(Y) gate
+    fn sqrt_y(&mut self, a: usize) {
+        self.stabilizer_tableau.sqrt_y(a);
+    }
 
-            // 値を格納
-            register.set(self.stabilizer_tableau[[p[0], self.qubit_num * 2]]);
-        } else {
-            // 測定結果が決定的のとき
-            let mut temp: Array1<u8> = Array::zeros(self.qubit_num * 2 + 1);
+    /// sqrt(Y) dagger gate
+    fn sqrt_y_dag(&mut self, a: usize) {
+        self.stabilizer_tableau.sqrt_y_dag(a);
+    }
 
-            self.stabilizer_tableau
-                .slice(s![..self.qubit_num, a])
-                .iter()
-                .enumerate()
-                .filter(|(_, &i)| i == 1)
-                .for_each(|(i, _)| self.row_sum_temp(i + self.qubit_num, &mut temp));
+    /// controlled Z gate
+    fn cz(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.cz(a, b);
+    }
 
-            // 値を格納
-            register.set(temp[self.qubit_num * 2]);
-        }
+    /// controlled Y gate
+    fn cy(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.cy(a, b);
+    }
 
-        if self.rng.gen::<f32>() < error_rate {
-            register.set(register.get() ^ 1);
-            if cfg!(debug_assertions) {
-                println!("measurement error: {}", a);
-            }
-            true
-        } else {
-            false
-        }
+    /// SWAP gate
+    fn swap(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.swap(a, b);
+    }
+
+    /// iSWAP gate
+    fn iswap(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.iswap(a, b);
+    }
+
+    /// iSWAP dagger gate
+    fn iswap_dag(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.iswap_dag(a, b);
+    }
+
+    /// X-controlled X gate
+    fn xcx(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.xcx(a, b);
+    }
+
+    /// X-controlled Y gate
+    fn xcy(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.xcy(a, b);
+    }
+
+    /// X-controlled Z gate
+    fn xcz(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.xcz(a, b);
+    }
+
+    /// Y-controlled X gate
+    fn ycx(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.ycx(a, b);
+    }
+
+    /// Y-controlled Y gate
+    fn ycy(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.ycy(a, b);
+    }
+
+    /// Y-controlled Z gate
+    fn ycz(&mut self, a: usize, b: usize) {
+        self.stabilizer_tableau.ycz(a, b);
+    }
+
+    /// measurement
+    fn measurement(&mut self, a: usize, error_rate: f32) -> u8 {
+        let outcome = self.measure_z(a);
+        self.record_measurement(outcome, error_rate)
     }
 
     /// measurement
     fn measurement_to_zero(&mut self, a: usize) {
-        let p = self
+        // 結果がランダムのときは必ず0にセット(固有値1)
+        if let Some(p) = self.stabilizer_tableau.random_measurement_pivot(a) {
+            self.stabilizer_tableau.collapse(p, a, 0);
+        }
+    }
+
+    /// measurement and reset
+    fn measurement_and_reset(&mut self, a: usize, error_rate: f32) -> u8 {
+        let outcome = self.measure_z(a);
+        let recorded = self.record_measurement(outcome, error_rate);
+        // 測定エラーに関係なく, 実際の状態に応じて|0>に戻す
+        if outcome == 1 {
+            self.x(a);
+        }
+        recorded
+    }
+
+    /// measurement of (-1)^sign * Pauli product
+    fn measurement_pauli_product(
+        &mut self,
+        product: &[(usize, Pauli)],
+        sign: u8,
+        error_rate: f32,
+    ) -> u8 {
+        let (x, z) = self.stabilizer_tableau.pack_pauli_product(product);
+
+        // (x, z)の測定結果に積の符号を反映する
+        let outcome = match self
             .stabilizer_tableau
-            .slice(s![self.qubit_num.., a])
-            .iter()
-            .enumerate()
-            .filter(|(_, &x)| x == 1)
-            .map(|(i, _)| i + self.qubit_num)
-            .collect::<Vec<usize>>();
-
-        // 一つでもXpa = 1のとき、結果はランダム
-        if p.len() != 0 {
-            let p_destabilizer = self
+            .random_pauli_measurement_pivot(&x, &z)
+        {
+            // 反可換なstabilizerが存在するとき、結果はランダム
+            Some(p) => {
+                let outcome = if self.rng.gen::<f32>() < 0.5 { 1 } else { 0 };
+                self.stabilizer_tableau.collapse_pauli(p, &x, &z, outcome);
+                outcome
+            }
+            // 測定結果が決定的のとき
+            None => self
                 .stabilizer_tableau
-                .slice(s![..self.qubit_num, a])
-                .iter()
-                .enumerate()
-                .filter(|(_, &x)| x == 1)
-                .map(|(i, _)| i)
-                .collect::<Vec<usize>>();
+                .deterministic_pauli_measurement(&x, &z),
+        } ^ sign;
 
-            p_destabilizer.iter().for_each(|&i| self.row_sum(i, p[0]));
-            p.iter().skip(1).for_each(|&i| self.row_sum(i, p[0]));
+        self.record_measurement(outcome, error_rate)
+    }
 
-            // (p[0] - qubit_num) 行目をp[0]行目に置換
-            let (mut q_n, mut q) = self
-                .stabilizer_tableau
-                .multi_slice_mut((s![p[0] - self.qubit_num, ..], s![p[0], ..]));
+    /// measurement in X basis
+    fn measurement_x(&mut self, a: usize, error_rate: f32) -> u8 {
+        self.h(a);
+        let outcome = self.measurement(a, error_rate);
+        self.h(a);
+        outcome
+    }
 
-            q_n.assign(&q);
-            for i in q.iter_mut() {
-                *i = 0;
-            }
-            q[self.qubit_num + a] = 1;
+    /// measurement in Y basis
+    fn measurement_y(&mut self, a: usize, error_rate: f32) -> u8 {
+        // Y固有状態をZ固有状態に移してから測定する
+        self.sqrt_x(a);
+        let outcome = self.measurement(a, error_rate);
+        self.sqrt_x_dag(a);
+        outcome
+    }
 
-            // 必ず0にセット(固有値1)
-            self.stabilizer_tableau[[p[0], self.qubit_num * 2]] = 0;
-        } else {
-            // 測定結果が決定的のとき
-            let mut temp: Array1<u8> = Array::zeros(self.qubit_num * 2 + 1);
+    /// reset to |+>
+    fn reset_x(&mut self, a: usize) {
+        self.reset_z(a);
+        self.h(a);
+    }
 
-            self.stabilizer_tableau
-                .slice(s![..self.qubit_num, a])
-                .iter()
-                .enumerate()
-                .filter(|(_, &i)| i == 1)
-                .for_each(|(i, _)| self.row_sum_temp(i + self.qubit_num, &mut temp));
-        }
+    /// reset to |+i>
+    fn reset_y(&mut self, a: usize) {
+        self.reset_z(a);
+        self.sqrt_x_dag(a);
     }
 
-    /// measurement and reset
-    fn measurement_and_reset(&mut self, a: usize, register: &Rc<Cell<u8>>, error_rate: f32) {
-        let measurement_error = self.measurement(a, register, error_rate);
-        if (register.get() == 1) && !measurement_error {
-            self.x(a);
-        } else if (register.get() == 0) && measurement_error {
-            self.x(a);
+    /// measurement and reset in X basis
+    fn measurement_and_reset_x(&mut self, a: usize, error_rate: f32) -> u8 {
+        self.h(a);
+        let outcome = self.measurement_and_reset(a, error_rate);
+        self.h(a);
+        outcome
+    }
+
+    /// measurement and reset in Y basis
+    fn measurement_and_reset_y(&mut self, a: usize, error_rate: f32) -> u8 {
+        self.sqrt_x(a);
+        let outcome = self.measurement_and_reset(a, error_rate);
+        self.sqrt_x_dag(a);
+        outcome
+    }
+
+    /// classically controlled Pauli (測定エラーを含む記録された結果で決まる)
+    fn feedback(&mut self, pauli: Pauli, lookback: usize, a: usize) {
+        if self.measurement_record.lookback(lookback) == 1 {
+            self.pauli(a, pauli);
         }
     }
 
@@ -316,7 +554,7 @@ impl SimulatorCore for CHPSimulatorCore {
             // insert noise
             match self.rng.gen::<f32>() {
                 x if (0.0..1.0 / 3.0).contains(&x) => {
-                    // self.z(a);
+                    self.z(a);
                     if cfg!(debug_assertions) {
                         println!("z error: {}", a)
                     }
@@ -328,101 +566,392 @@ impl SimulatorCore for CHPSimulatorCore {
                     }
                 } // X error
                 x if (2.0 / 3.0..1.0).contains(&x) => {
-                    // Y error
-                    // self.x(a);
-                    // self.z(a);
+                    self.y(a);
                     if cfg!(debug_assertions) {
                         println!("y error: {}", a)
                     }
-                }
+                } // Y error
                 _ => panic!("rng must be 0.0..1.0"),
             }
         }
     }
 
+    /// insert Pauli channel noise
+    fn pauli_channel_1(&mut self, a: usize, px: f32, py: f32, pz: f32) {
+        match self.rng.gen::<f32>() {
+            x if x < px => {
+                self.x(a);
+                if cfg!(debug_assertions) {
+                    println!("x error: {}", a)
+                }
+            } // X error
+            x if x < px + py => {
+                self.y(a);
+                if cfg!(debug_assertions) {
+                    println!("y error: {}", a)
+                }
+            } // Y error
+            x if x < px + py + pz => {
+                self.z(a);
+                if cfg!(debug_assertions) {
+                    println!("z error: {}", a)
+                }
+            } // Z error
+            _ => (),
+        }
+    }
+
+    /// insert two qubit depolarizing noise
+    fn depolarizing_2(&mut self, a: usize, b: usize, p: f32) {
+        self.pauli_channel_2(a, b, &[p / 15.0; 15]);
+    }
+
+    /// insert two qubit Pauli channel noise
+    fn pauli_channel_2(&mut self, a: usize, b: usize, p: &[f32; 15]) {
+        const PAULI: [Pauli; 4] = [Pauli::I, Pauli::X, Pauli::Y, Pauli::Z];
+
+        let r = self.rng.gen::<f32>();
+        let mut cumulative = 0.0;
+        // IX, IY, IZ, XI, ..., ZZの順
+        for (i, &p_i) in p.iter().enumerate() {
+            cumulative += p_i;
+            if r < cumulative {
+                let (pauli_a, pauli_b) = (PAULI[(i + 1) >> 2], PAULI[(i + 1) & 3]);
+                self.pauli(a, pauli_a);
+                self.pauli(b, pauli_b);
+                if cfg!(debug_assertions) {
+                    println!("{:?}{:?} error: {}, {}", pauli_a, pauli_b, a, b)
+                }
+                break;
+            }
+        }
+    }
+
     fn reset(&mut self) {
-        let size = self.qubit_num * 2;
-        self.stabilizer_tableau = concatenate![Axis(1), Array::eye(size), Array::zeros((size, 1))];
+        self.stabilizer_tableau.reset();
+        self.measurement_record.clear();
+        self.detection_events.clear();
+        self.observable_flips.iter_mut().for_each(|flip| *flip = 0);
+    }
+
+    fn detector(&mut self, lookback: &[usize]) {
+        let parity = self.parity(lookback);
+        self.detection_events.push(parity);
+    }
+
+    fn observable_include(&mut self, observable: usize, lookback: &[usize]) {
+        if self.observable_flips.len() <= observable {
+            self.observable_flips.resize(observable + 1, 0);
+        }
+        self.observable_flips[observable] ^= self.parity(lookback);
     }
 }
 
 impl SimulatorInterface for CHPSimulator {
     /// add CNOT gate
     fn add_cx(&mut self, a: usize, b: usize) {
-        self.dispatcher.push(Operation::CX(a, b));
+        self.push(Operation::CX(a, b));
     }
 
     /// add Hadamard gate
     fn add_h(&mut self, a: usize) {
-        self.dispatcher.push(Operation::H(a));
+        self.push(Operation::H(a));
     }
 
     /// add S gate (Phase gate)
     fn add_s(&mut self, a: usize) {
-        self.dispatcher.push(Operation::S(a));
+        self.push(Operation::S(a));
     }
 
     /// add X gate
     fn add_x(&mut self, a: usize) {
-        self.dispatcher.push(Operation::X(a))
+        self.push(Operation::X(a))
     }
 
     /// add Z gate
     fn add_z(&mut self, a: usize) {
-        self.dispatcher.push(Operation::Z(a))
+        self.push(Operation::Z(a))
+    }
+
+    /// add Y gate
+    fn add_y(&mut self, a: usize) {
+        self.push(Operation::Y(a));
+    }
+
+    /// add S dagger gate
+    fn add_s_dag(&mut self, a: usize) {
+        self.push(Operation::SDag(a));
+    }
+
+    /// add sqrt(X) gate
+    fn add_sqrt_x(&mut self, a: usize) {
+        self.push(Operation::SqrtX(a));
+    }
+
+    /// add sqrt(X) dagger gate
+    fn add_sqrt_x_dag(&mut self, a: usize) {
+        self.push(Operation::SqrtXDag(a));
+    }
+
+    /// add sqrt(Y) gate
+    fn add_sqrt_y(&mut self, a: usize) {
+        self.push(Operation::SqrtY(a));
+    }
+
+    /// add sqrt(Y) dagger gate
+    fn add_sqrt_y_dag(&mut self, a: usize) {
+        self.push(Operation::SqrtYDag(a));
+    }
+
+    /// add controlled Z gate
+    fn add_cz(&mut self, a: usize, b: usize) {
+        self.push(Operation::CZ(a, b));
+    }
+
+    /// add controlled Y gate
+    fn add_cy(&mut self, a: usize, b: usize) {
+        self.push(Operation::CY(a, b));
+    }
+
+    /// add SWAP gate
+    fn add_swap(&mut self, a: usize, b: usize) {
+        self.push(Operation::Swap(a, b));
+    }
+
+    /// add iSWAP gate
+    fn add_iswap(&mut self, a: usize, b: usize) {
+        self.push(Operation::ISwap(a, b));
+    }
+
+    /// add iSWAP dagger gate
+    fn add_iswap_dag(&mut self, a: usize, b: usize) {
+        self.push(Operation::ISwapDag(a, b));
+    }
+
+    /// add X-controlled X gate
+    fn add_xcx(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCX(a, b));
+    }
+
+    /// add X-controlled Y gate
+    fn add_xcy(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCY(a, b));
+    }
+
+    /// add X-controlled Z gate
+    fn add_xcz(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCZ(a, b));
+    }
+
+    /// add Y-controlled X gate
+    fn add_ycx(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCX(a, b));
+    }
+
+    /// add Y-controlled Y gate
+    fn add_ycy(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCY(a, b));
+    }
+
+    /// add Y-controlled Z gate
+    fn add_ycz(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCZ(a, b));
     }
 
     /// add measurement
-    fn add_measurement(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32) {
-        self.dispatcher.push(Operation::M(a, register, error_rate));
+    fn add_measurement(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::M(a, error_rate))
     }
 
     fn add_measurement_to_zero(&mut self, a: usize) {
-        self.dispatcher.push(Operation::MToZero(a));
+        self.push(Operation::MToZero(a));
     }
 
     /// add measurement_and_reset
-    fn add_measurement_and_reset(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32) {
-        self.dispatcher.push(Operation::MR(a, register, error_rate));
+    fn add_measurement_and_reset(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MR(a, error_rate))
+    }
+
+    /// add measurement of Pauli product
+    fn add_measurement_signed_pauli_product(
+        &mut self,
+        product: Vec<(usize, Pauli)>,
+        sign: u8,
+        error_rate: f32,
+    ) -> usize {
+        let operation = Operation::measurement_pauli_product(&product, sign, error_rate)
+            .unwrap_or_else(|e| panic!("{}", e));
+        self.push_measurement(operation)
+    }
+
+    /// add measurement in X basis
+    fn add_measurement_x(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MX(a, error_rate))
+    }
+
+    /// add measurement in Y basis
+    fn add_measurement_y(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MY(a, error_rate))
+    }
+
+    /// add reset to |0>
+    fn add_reset_z(&mut self, a: usize) {
+        self.push(Operation::R(a));
+    }
+
+    /// add reset to |+>
+    fn add_reset_x(&mut self, a: usize) {
+        self.push(Operation::RX(a));
+    }
+
+    /// add reset to |+i>
+    fn add_reset_y(&mut self, a: usize) {
+        self.push(Operation::RY(a));
+    }
+
+    /// add measurement_and_reset in X basis
+    fn add_measurement_and_reset_x(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MRX(a, error_rate))
+    }
+
+    /// add measurement_and_reset in Y basis
+    fn add_measurement_and_reset_y(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MRY(a, error_rate))
+    }
+
+    /// add Pauli controlled by rec[-k]
+    fn add_feedback(&mut self, pauli: Pauli, lookback: usize, a: usize) {
+        assert!(
+            (1..=self.measurement_count).contains(&lookback),
+            "feedback refers to a measurement that does not exist"
+        );
+        self.push(Operation::Feedback(pauli, lookback, a));
     }
 
     fn add_noise(&mut self, a: usize, noise_type: NoiseType) {
         match noise_type {
-            NoiseType::Depolarizing(p) => self.dispatcher.push(Operation::Depolarizing(a, p)),
+            NoiseType::Depolarizing(p) => self.push(Operation::Depolarizing(a, p)),
+            NoiseType::PauliChannel1(px, py, pz) => {
+                self.push(Operation::PauliChannel1(a, px, py, pz))
+            }
+            _ => panic!("{:?} is two qubit noise, use add_noise2", noise_type),
+        }
+    }
+
+    fn add_noise2(&mut self, a: usize, b: usize, noise_type: NoiseType) {
+        match noise_type {
+            NoiseType::Depolarizing2(p) => self.push(Operation::Depolarizing2(a, b, p)),
+            NoiseType::PauliChannel2(p) => self.push(Operation::PauliChannel2(a, b, p)),
+            _ => panic!("{:?} is single qubit noise, use add_noise", noise_type),
         }
     }
 
+    fn add_tick(&mut self) {
+        self.push(Operation::Tick);
+    }
+
+    fn repeat_from(&mut self, start: usize, repetitions: usize) {
+        self.reference = None;
+        let body = self.dispatcher.repeat_from(start, repetitions);
+        let (measurements, detectors) = (body.measurement_count(), body.detector_count());
+        self.measurement_count = self.measurement_count - measurements + repetitions * measurements;
+        self.detector_count = self.detector_count - detectors + repetitions * detectors;
+    }
+
+    fn operation_count(&self) -> usize {
+        self.dispatcher.operations().len()
+    }
+
+    fn insert_noise_model(&mut self, model: &NoiseModel, data_qubit: &[usize], start: usize) {
+        let operations = circuit_noise::apply_noise_model(
+            &self.dispatcher.operations()[start..],
+            model,
+            data_qubit,
+        );
+        self.reference = None;
+        self.dispatcher.replace_from(start, operations);
+    }
+
     /// Reset stabilizer tableau
     fn reset(&mut self) {
         self.core.reset();
     }
 
     /// measurement direct
-    fn measurement(&mut self, a: usize, register: Rc<Cell<u8>>, error_rate: f32) {
-        self.core.measurement(a, &register, error_rate);
+    fn measurement(&mut self, a: usize, error_rate: f32) -> u8 {
+        self.core.measurement(a, error_rate)
+    }
+
+    fn measurement_count(&self) -> usize {
+        self.measurement_count
+    }
+
+    fn measurement_record(&self) -> &MeasurementRecord {
+        &self.core.measurement_record
+    }
+
+    fn add_detector(&mut self, coords: Vec<f32>, lookback: Vec<usize>) -> usize {
+        debug_assert!(
+            lookback
+                .iter()
+                .all(|k| (1..=self.measurement_count).contains(k)),
+            "detector refers to a measurement that does not exist"
+        );
+        self.push(Operation::Detector(coords, lookback));
+        self.detector_count += 1;
+        self.detector_count - 1
+    }
+
+    fn add_observable_include(&mut self, observable: usize, lookback: Vec<usize>) {
+        self.observable_count = self.observable_count.max(observable + 1);
+        self.push(Operation::ObservableInclude(observable, lookback));
+    }
+
+    fn detector_count(&self) -> usize {
+        self.detector_count
+    }
+
+    fn detection_events(&self) -> &[u8] {
+        &self.core.detection_events
+    }
+
+    fn circuit(&self) -> &Circuit {
+        self.dispatcher.circuit()
+    }
+
+    fn observable_flips(&self) -> &[u8] {
+        &self.core.observable_flips
     }
 
     /// run circuit
     fn run(&mut self) {
-        let Self { core, dispatcher } = self;
+        if self.reference.is_none() && self.detector_count + self.observable_count > 0 {
+            self.reference = Some(self.reference_sample());
+        }
+
+        let Self {
+            core,
+            dispatcher,
+            observable_count,
+            reference,
+            ..
+        } = self;
+        core.measurement_record.clear();
+        core.detection_events.clear();
+        core.observable_flips = vec![0; *observable_count];
 
         for op in dispatcher.operations().iter() {
-            match op {
-                Operation::CX(a, b) => core.cx(*a, *b),
-                Operation::H(a) => core.h(*a),
-                Operation::Depolarizing(a, p) => core.depolarizing(*a, *p),
-                Operation::MR(a, register, error_rate) => {
-                    core.measurement_and_reset(*a, register, *error_rate)
-                }
-                Operation::M(a, register, error_rate) => {
-                    core.measurement(*a, register, *error_rate);
-                }
-                Operation::MToZero(a) => core.measurement_to_zero(*a),
-                Operation::S(a) => core.s(*a),
-                Operation::X(a) => core.x(*a),
-                Operation::Z(a) => core.z(*a),
+            core.execute(op);
+        }
+
+        // ノイズのない回路との差がdetection event, observable flipになる
+        if let Some((detectors, observables)) = reference {
+            for (event, reference) in core.detection_events.iter_mut().zip(detectors.iter()) {
+                *event ^= reference;
+            }
+            for (flip, reference) in core.observable_flips.iter_mut().zip(observables.iter()) {
+                *flip ^= reference;
             }
-            //println!("{:?}", op);
         }
     }
 }
@@ -1,11 +1,8 @@
-use std::cell::Cell;
-use std::rc::Rc;
-
-use super::Operation;
+use super::{pauli::Pauli, Operation};
+use crate::circuit::Circuit;
 
 pub struct Dispatcher {
-    operations: Vec<Operation>,
-    round: usize,
+    circuit: Circuit,
 }
 
 /// シミュレータの内部からのみアクセスできるオペレーション
@@ -25,36 +22,146 @@ pub trait SimulatorCore {
     /// Z gate
     fn z(&mut self, a: usize);
 
+    /// Y gate
+    fn y(&mut self, a: usize);
+
+    /// S dagger gate
+    fn s_dag(&mut self, a: usize);
+
+    /// sqrt(X) gate
+    fn sqrt_x(&mut self, a: usize);
+
+    /// sqrt(X) dagger gate
+    fn sqrt_x_dag(&mut self, a: usize);
+
+    /// sqrt(Y) gate
+    fn sqrt_y(&mut self, a: usize);
+
+    /// sqrt(Y) dagger gate
+    fn sqrt_y_dag(&mut self, a: usize);
+
+    /// controlled Z gate
+    fn cz(&mut self, a: usize, b: usize);
+
+    /// controlled Y gate
+    fn cy(&mut self, a: usize, b: usize);
+
+    /// SWAP gate
+    fn swap(&mut self, a: usize, b: usize);
+
+    /// iSWAP gate
+    fn iswap(&mut self, a: usize, b: usize);
+
+    /// iSWAP dagger gate
+    fn iswap_dag(&mut self, a: usize, b: usize);
+
+    /// X-controlled X gate
+    fn xcx(&mut self, a: usize, b: usize);
+
+    /// X-controlled Y gate
+    fn xcy(&mut self, a: usize, b: usize);
+
+    /// X-controlled Z gate
+    fn xcz(&mut self, a: usize, b: usize);
+
+    /// Y-controlled X gate
+    fn ycx(&mut self, a: usize, b: usize);
+
+    /// Y-controlled Y gate
+    fn ycy(&mut self, a: usize, b: usize);
+
+    /// Y-controlled Z gate
+    fn ycz(&mut self, a: usize, b: usize);
+
     /// measurement
-    /// if measurement error occurs return true, otherwise return false 
-    fn measurement(&mut self, a: usize, register: &Rc<Cell<u8>>, error_rate: f32) -> bool;
+    /// return the outcome recorded in the measurement record (including measurement error)
+    fn measurement(&mut self, a: usize, error_rate: f32) -> u8;
 
     fn measurement_to_zero(&mut self, a: usize);
 
     /// measurement and reset
-    fn measurement_and_reset(&mut self, a: usize, register: &Rc<Cell<u8>>, error_rate: f32);
+    fn measurement_and_reset(&mut self, a: usize, error_rate: f32) -> u8;
+
+    /// measurement of (-1)^sign * Pauli product (同じqubitは1回だけ現れる)
+    fn measurement_pauli_product(
+        &mut self,
+        product: &[(usize, Pauli)],
+        sign: u8,
+        error_rate: f32,
+    ) -> u8;
+
+    /// measurement in X basis
+    fn measurement_x(&mut self, a: usize, error_rate: f32) -> u8;
+
+    /// measurement in Y basis
+    fn measurement_y(&mut self, a: usize, error_rate: f32) -> u8;
+
+    /// reset to |+>
+    fn reset_x(&mut self, a: usize);
+
+    /// reset to |+i>
+    fn reset_y(&mut self, a: usize);
+
+    /// measurement and reset in X basis
+    fn measurement_and_reset_x(&mut self, a: usize, error_rate: f32) -> u8;
+
+    /// measurement and reset in Y basis
+    fn measurement_and_reset_y(&mut self, a: usize, error_rate: f32) -> u8;
+
+    /// apply Pauli on qubit a if the measurement result rec[-k] is 1 (k = lookback)
+    fn feedback(&mut self, pauli: Pauli, lookback: usize, a: usize);
 
     ///reset
     fn reset(&mut self);
 
     /// depolarizing noise channel
     fn depolarizing(&mut self, a: usize, p: f32);
+
+    /// Pauli channel applying X, Y, Z with probability px, py, pz
+    fn pauli_channel_1(&mut self, a: usize, px: f32, py: f32, pz: f32);
+
+    /// two qubit depolarizing noise channel
+    fn depolarizing_2(&mut self, a: usize, b: usize, p: f32);
+
+    /// two qubit Pauli channel in the order IX, IY, IZ, XI, ..., ZZ
+    fn pauli_channel_2(&mut self, a: usize, b: usize, p: &[f32; 15]);
+
+    /// record the parity of rec[-k] for k in lookback as a detector
+    fn detector(&mut self, lookback: &[usize]);
+
+    /// xor the parity of rec[-k] for k in lookback into the logical observable
+    fn observable_include(&mut self, observable: usize, lookback: &[usize]);
 }
 
 impl Dispatcher {
-    pub fn new(operations: Vec<Operation>, round: usize) -> Self {
-        Dispatcher { operations, round }
+    pub fn new(operations: Vec<Operation>) -> Self {
+        Dispatcher {
+            circuit: Circuit::from(operations),
+        }
     }
 
     pub fn push(&mut self, operation: Operation) {
-        self.operations.push(operation);
+        self.circuit.push(operation);
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        self.circuit.operations()
+    }
+
+    pub fn circuit(&self) -> &Circuit {
+        &self.circuit
     }
 
-    pub fn operations(&self) -> &Vec<Operation> {
-        &self.operations
+    /// start以降のオペレーションを置き換える
+    pub fn replace_from(&mut self, start: usize, operations: Vec<Operation>) {
+        self.circuit.replace_from(start, operations);
     }
 
-    pub fn round(&self) -> usize {
-        self.round
+    /// start以降のオペレーションをrepetitions回繰り返すREPEATブロックに置き換え, そのbodyを返す
+    pub fn repeat_from(&mut self, start: usize, repetitions: usize) -> Circuit {
+        let body = self.circuit.slice(start..self.circuit.len());
+        self.circuit
+            .replace_from(start, vec![Operation::Repeat(repetitions, body.clone())]);
+        body
     }
 }
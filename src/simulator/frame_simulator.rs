@@ -0,0 +1,952 @@
+use super::{
+    chp_simulator::{self, ReferenceSample},
+    core::Dispatcher,
+    measurement_record::MeasurementRecord,
+    pauli::Pauli,
+    stabilizer_tableau::StabilizerTableau,
+    tableau::apply_gate,
+    Operation, SimulatorInterface,
+};
+use crate::circuit::Circuit;
+use crate::qubit_graph::ungraph::UnGraph;
+use crate::noise::{
+    circuit_noise,
+    noise_model::{NoiseModel, NoiseType},
+};
+use rand::{rngs::SmallRng, Rng};
+use std::collections::BTreeMap;
+
+/// 一度にサンプルするshot数のデフォルト値
+const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// Pauli frameに対するオペレーション
+enum FrameOp {
+    /// 1 qubit Clifford gate (X, Zの像)
+    Gate1(usize, [[u8; 2]; 2]),
+    /// 2 qubit Clifford gate (X_a, Z_a, X_b, Z_bの像)
+    Gate2(usize, usize, [[u8; 4]; 4]),
+    /// Pauli productの測定 (測定後にリセットする場合は1 qubitのみ)
+    Measure(Vec<(usize, Pauli)>, f32, bool),
+    /// 指定したPauliの+1固有状態へのリセット
+    Reset(usize, Pauli),
+    /// 結果を0に射影する測定 (参照の実行でのstabilizerは実行順に対応する)
+    MeasureToZero(usize),
+    /// rec[-k]で制御されたPauli (Pauli, k, qubit)
+    Feedback(Pauli, usize, usize),
+    /// X, Y, Zの確率
+    Noise1(usize, [f32; 3]),
+    /// IX, IY, ..., ZZの確率
+    Noise2(usize, usize, [f32; 15]),
+    Detector(Vec<usize>),
+    ObservableInclude(usize, Vec<usize>),
+    /// n回繰り返すブロック
+    Repeat(usize, Vec<FrameOp>),
+}
+
+/// 参照の実行結果とそれを元にコンパイルしたオペレーション
+struct Program {
+    reference: ReferenceSample,
+    operations: Vec<FrameOp>,
+}
+
+/// まとめてサンプルしたshotの結果 (各行はshotを64個ずつu64に詰めたもの)
+struct Batch {
+    shots: usize,
+    words: usize,
+    /// 参照からの測定結果の反転 (測定ごと)
+    measurements: Vec<u64>,
+    detectors: Vec<u64>,
+    observables: Vec<u64>,
+    /// 実行後のPauli frame (qubitごと)
+    x: Vec<u64>,
+    z: Vec<u64>,
+}
+
+impl Batch {
+    fn bit(rows: &[u64], words: usize, row: usize, shot: usize) -> u8 {
+        ((rows[row * words + shot / 64] >> (shot % 64)) & 1) as u8
+    }
+
+    fn bits(&self, rows: &[u64], shot: usize) -> Vec<u8> {
+        (0..rows.len() / self.words)
+            .map(|row| Self::bit(rows, self.words, row, shot))
+            .collect()
+    }
+}
+
+/// Pauli frame simulator
+///
+/// ノイズのない回路を一度CHPで実行した結果を参照とし, ノイズによるPauli frameを
+/// 64 shotずつbit演算でまとめて伝搬させる.
+/// 測定結果がランダムになるように, 測定・リセットの後にはその基底のstabilizerをランダムにframeへ掛ける.
+pub struct FrameSimulator {
+    qubit_num: usize,
+    batch_size: usize,
+    rng: SmallRng,
+    dispatcher: Dispatcher,
+    measurement_count: usize,
+    detector_count: usize,
+    observable_count: usize,
+    /// 回路が変わると作り直す
+    program: Option<Box<Program>>,
+    batch: Option<Box<Batch>>,
+    next_shot: usize,
+    /// runで取り出したshotの結果
+    measurement_record: MeasurementRecord,
+    detection_events: Vec<u8>,
+    observable_flips: Vec<u8>,
+    /// runで取り出したshotの実行後の状態 (直接測定に用いる)
+    tableau: StabilizerTableau,
+    frame: Vec<(u8, u8)>,
+}
+
+impl FrameSimulator {
+    pub fn new(qubit_num: usize, rng: SmallRng) -> Self {
+        Self::with_batch_size(qubit_num, DEFAULT_BATCH_SIZE, rng)
+    }
+
+    /// 一度にbatch_size shotをサンプルするsimulatorを作る
+    pub fn with_batch_size(qubit_num: usize, batch_size: usize, rng: SmallRng) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        FrameSimulator {
+            qubit_num,
+            batch_size,
+            rng,
+            dispatcher: Dispatcher::new(Vec::new()),
+            measurement_count: 0,
+            detector_count: 0,
+            observable_count: 0,
+            program: None,
+            batch: None,
+            next_shot: 0,
+            measurement_record: MeasurementRecord::new(),
+            detection_events: Vec::new(),
+            observable_flips: Vec::new(),
+            tableau: StabilizerTableau::new(qubit_num),
+            frame: vec![(0, 0); qubit_num],
+        }
+    }
+
+    /// オペレーションを追加する
+    fn push(&mut self, operation: Operation) {
+        self.program = None;
+        self.dispatcher.push(operation);
+    }
+
+    /// 測定オペレーションを追加し, measurement recordでのindexを返す
+    fn push_measurement(&mut self, operation: Operation) -> usize {
+        self.push(operation);
+        self.measurement_count += 1;
+        self.measurement_count - 1
+    }
+
+    /// shots回分の測定結果をまとめてサンプルする
+    pub fn sample_measurements(&mut self, shots: usize) -> Vec<Vec<u8>> {
+        self.sample(shots, |batch, reference, shot| {
+            batch
+                .bits(&batch.measurements, shot)
+                .into_iter()
+                .zip(reference.measurements.iter())
+                .map(|(flip, bit)| flip ^ bit)
+                .collect()
+        })
+    }
+
+    /// shots回分のdetection eventとlogical observableの反転をまとめてサンプルする
+    pub fn sample_detectors(&mut self, shots: usize) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        self.sample(shots, |batch, _, shot| {
+            (
+                batch.bits(&batch.detectors, shot),
+                batch.bits(&batch.observables, shot),
+            )
+        })
+        .into_iter()
+        .unzip()
+    }
+
+    fn sample<T>(
+        &mut self,
+        shots: usize,
+        f: impl Fn(&Batch, &ReferenceSample, usize) -> T,
+    ) -> Vec<T> {
+        self.compile();
+        let mut samples = Vec::with_capacity(shots);
+        while samples.len() < shots {
+            let batch = self.simulate((shots - samples.len()).min(self.batch_size));
+            let reference = &self.program.as_ref().unwrap().reference;
+            samples.extend((0..batch.shots).map(|shot| f(&batch, reference, shot)));
+        }
+        samples
+    }
+
+    /// 参照の実行とオペレーションのコンパイル
+    fn compile(&mut self) {
+        if self.program.is_some() {
+            return;
+        }
+        let operations = self.dispatcher.operations();
+        let reference =
+            chp_simulator::reference_sample(self.qubit_num, operations, self.observable_count);
+
+        let operations = compile_operations(operations);
+
+        self.program = Some(Box::new(Program {
+            reference,
+            operations,
+        }));
+        self.batch = None;
+    }
+
+    /// shots回分のPauli frameを伝搬させる
+    fn simulate(&mut self, shots: usize) -> Batch {
+        let words = shots.div_ceil(64);
+        let Program {
+            reference,
+            operations,
+        } = self.program.as_deref().expect("program is not compiled");
+        let mut source = Source::Sample(&mut self.rng);
+
+        let mut batch = Batch {
+            shots,
+            words,
+            measurements: Vec::with_capacity(self.measurement_count * words),
+            detectors: Vec::with_capacity(self.detector_count * words),
+            observables: vec![0; self.observable_count * words],
+            x: vec![0; self.qubit_num * words],
+            // |0>はZの固有状態なので, Zをランダムに掛けてよい
+            z: source.random_words(self.qubit_num * words),
+        };
+        let mut gauge = 0;
+        for op in operations.iter() {
+            execute(op, &mut batch, &mut source, reference, &mut gauge);
+        }
+
+        batch
+    }
+}
+
+/// detector error modelの1つのエラー
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorMechanism {
+    /// 同じdetectorとobservableを反転させる独立なエラーのどれかが奇数回起こる確率
+    pub probability: f32,
+    pub detectors: Vec<usize>,
+    pub observables: Vec<usize>,
+}
+
+/// 回路のdetector error model
+///
+/// ノイズチャネルの各Pauliと測定エラーを独立なエラーとみなし, 1つずつ別のshotに入れてPauli frameを伝搬させる.
+/// detectorもobservableも反転させないエラーは含めない
+pub fn detector_error_model(qubit_num: usize, circuit: &Circuit) -> Vec<ErrorMechanism> {
+    let operations = circuit.operations();
+    let observable_count = circuit.observable_count();
+    let reference = chp_simulator::reference_sample(qubit_num, operations, observable_count);
+    let operations = compile_operations(operations);
+
+    let mut probabilities = Vec::new();
+    for op in operations.iter() {
+        error_probabilities(op, &mut probabilities);
+    }
+    let shots = probabilities.len();
+    if shots == 0 {
+        return Vec::new();
+    }
+    let words = shots.div_ceil(64);
+    let mut batch = Batch {
+        shots,
+        words,
+        measurements: Vec::with_capacity(circuit.measurement_count() * words),
+        detectors: Vec::with_capacity(circuit.detector_count() * words),
+        observables: vec![0; observable_count * words],
+        x: vec![0; qubit_num * words],
+        z: vec![0; qubit_num * words],
+    };
+    let mut source = Source::Enumerate(0);
+    let mut gauge = 0;
+    for op in operations.iter() {
+        execute(op, &mut batch, &mut source, &reference, &mut gauge);
+    }
+
+    // エラーごとに反転させるdetectorとobservable
+    let mut flips = vec![(Vec::new(), Vec::new()); shots];
+    for (row, detector) in batch.detectors.chunks(words).enumerate() {
+        iter_bits(detector).for_each(|shot| flips[shot].0.push(row));
+    }
+    for (row, observable) in batch.observables.chunks(words).enumerate() {
+        iter_bits(observable).for_each(|shot| flips[shot].1.push(row));
+    }
+
+    let mut mechanisms: BTreeMap<(Vec<usize>, Vec<usize>), f32> = BTreeMap::new();
+    for (flip, p) in flips.into_iter().zip(probabilities) {
+        if flip.0.is_empty() && flip.1.is_empty() {
+            continue;
+        }
+        let q = mechanisms.entry(flip).or_insert(0.0);
+        *q = UnGraph::merge_error_rate(*q, p);
+    }
+    mechanisms
+        .into_iter()
+        .map(|((detectors, observables), probability)| ErrorMechanism {
+            probability,
+            detectors,
+            observables,
+        })
+        .collect()
+}
+
+/// Source::Enumerateで1つずつshotに入れるエラーの確率 (実行順)
+fn error_probabilities(op: &FrameOp, probabilities: &mut Vec<f32>) {
+    match op {
+        FrameOp::Measure(_, p, _) => probabilities.extend((*p > 0.0).then_some(*p)),
+        FrameOp::Noise1(_, p) => probabilities.extend(p.iter().filter(|&&p| p > 0.0)),
+        FrameOp::Noise2(_, _, p) => probabilities.extend(p.iter().filter(|&&p| p > 0.0)),
+        FrameOp::Repeat(n, body) => {
+            for _ in 0..*n {
+                body.iter().for_each(|op| error_probabilities(op, probabilities));
+            }
+        }
+        _ => (),
+    }
+}
+
+/// ノイズと, 測定・リセットの後にframeへ掛けるstabilizerの決め方
+enum Source<'a> {
+    /// 確率に従ってサンプルし, stabilizerはランダムに掛ける
+    Sample(&'a mut SmallRng),
+    /// 確率が0でないエラーを1つずつ別のshotに入れ, stabilizerは掛けない (次にエラーを入れるshot)
+    Enumerate(usize),
+}
+
+impl Source<'_> {
+    /// stabilizerを掛けるshotのmask
+    fn random_words(&mut self, words: usize) -> Vec<u64> {
+        match self {
+            Source::Sample(rng) => random_words(rng, words),
+            Source::Enumerate(_) => vec![0; words],
+        }
+    }
+
+    /// 確率pのエラーが起こるshotのmask
+    fn sample_mask(&mut self, p: f32, shots: usize) -> Vec<u64> {
+        match self {
+            Source::Sample(rng) => sample_mask(rng, p, shots),
+            Source::Enumerate(next) => {
+                let mut mask = vec![0; shots.div_ceil(64)];
+                if p > 0.0 {
+                    mask[*next / 64] |= 1 << (*next % 64);
+                    *next += 1;
+                }
+                mask
+            }
+        }
+    }
+
+    /// 確率pのPauliのどれかが起こるshotと, 起こったPauliのindex
+    fn choose(&mut self, p: &[f32], shots: usize) -> Vec<(usize, usize)> {
+        match self {
+            Source::Sample(rng) => {
+                let hits = sample_mask(rng, p.iter().sum(), shots);
+                iter_bits(&hits).map(|shot| (shot, choose(rng, p))).collect()
+            }
+            Source::Enumerate(next) => p
+                .iter()
+                .enumerate()
+                .filter(|&(_, &p)| p > 0.0)
+                .map(|(i, _)| {
+                    *next += 1;
+                    (*next - 1, i)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// オペレーションをPauli frameに対するオペレーションにコンパイルする
+fn compile_operations(operations: &[Operation]) -> Vec<FrameOp> {
+    operations
+        .iter()
+        .filter_map(|op| {
+            Some(match op {
+                Operation::M(a, p) => FrameOp::Measure(vec![(*a, Pauli::Z)], *p, false),
+                Operation::MX(a, p) => FrameOp::Measure(vec![(*a, Pauli::X)], *p, false),
+                Operation::MY(a, p) => FrameOp::Measure(vec![(*a, Pauli::Y)], *p, false),
+                Operation::MR(a, p) => FrameOp::Measure(vec![(*a, Pauli::Z)], *p, true),
+                Operation::MRX(a, p) => FrameOp::Measure(vec![(*a, Pauli::X)], *p, true),
+                Operation::MRY(a, p) => FrameOp::Measure(vec![(*a, Pauli::Y)], *p, true),
+                // 符号は参照の測定結果にだけ影響する
+                Operation::MPP(product, _, p) => FrameOp::Measure(product.clone(), *p, false),
+                Operation::R(a) => FrameOp::Reset(*a, Pauli::Z),
+                Operation::RX(a) => FrameOp::Reset(*a, Pauli::X),
+                Operation::RY(a) => FrameOp::Reset(*a, Pauli::Y),
+                Operation::MToZero(a) => FrameOp::MeasureToZero(*a),
+                Operation::Feedback(pauli, lookback, a) => FrameOp::Feedback(*pauli, *lookback, *a),
+                Operation::Depolarizing(a, p) => FrameOp::Noise1(*a, [p / 3.0; 3]),
+                Operation::PauliChannel1(a, px, py, pz) => FrameOp::Noise1(*a, [*px, *py, *pz]),
+                Operation::Depolarizing2(a, b, p) => FrameOp::Noise2(*a, *b, [p / 15.0; 15]),
+                Operation::PauliChannel2(a, b, p) => FrameOp::Noise2(*a, *b, *p),
+                Operation::Detector(_, lookback) => FrameOp::Detector(lookback.clone()),
+                Operation::ObservableInclude(observable, lookback) => {
+                    FrameOp::ObservableInclude(*observable, lookback.clone())
+                }
+                Operation::Tick => return None,
+                Operation::Repeat(n, body) => {
+                    FrameOp::Repeat(*n, compile_operations(body.operations()))
+                }
+                gate => match gate.qubits()[..] {
+                    [a] => FrameOp::Gate1(a, conjugation_1(gate)),
+                    [a, b] => FrameOp::Gate2(a, b, conjugation_2(gate)),
+                    _ => unreachable!("{:?} is not a gate", gate),
+                },
+            })
+        })
+        .collect()
+}
+
+/// オペレーションを1つ実行し, shotごとのPauli frameを伝搬させる
+///
+/// gaugeは次のMeasureToZeroに対応する参照の実行でのstabilizerのindex
+fn execute(
+    op: &FrameOp,
+    batch: &mut Batch,
+    source: &mut Source,
+    reference: &ReferenceSample,
+    gauge: &mut usize,
+) {
+    let (shots, words) = (batch.shots, batch.words);
+    let (x, z) = (&mut batch.x, &mut batch.z);
+    match op {
+        FrameOp::Gate1(a, image) => {
+            for w in 0..words {
+                let (i, [new_x, new_z]) = (
+                    a * words + w,
+                    [0, 1].map(|k| {
+                        mask(image[0][k]) & x[a * words + w] ^ mask(image[1][k]) & z[a * words + w]
+                    }),
+                );
+                x[i] = new_x;
+                z[i] = new_z;
+            }
+        }
+        FrameOp::Gate2(a, b, image) => {
+            for w in 0..words {
+                let (i, j) = (a * words + w, b * words + w);
+                let bits = [x[i], z[i], x[j], z[j]];
+                let new = [0, 1, 2, 3]
+                    .map(|k| (0..4).fold(0, |acc, g| acc ^ (mask(image[g][k]) & bits[g])));
+                [x[i], z[i], x[j], z[j]] = new;
+            }
+        }
+        FrameOp::Measure(product, p, reset) => {
+            let mut flip = source.sample_mask(*p, shots);
+            for &(q, pauli) in product.iter() {
+                let (px, pz) = pauli.to_bits();
+                for (w, flip) in flip.iter_mut().enumerate() {
+                    // frameが測定するPauliと反可換なら結果が反転する
+                    *flip ^= mask(pz) & x[q * words + w] ^ mask(px) & z[q * words + w];
+                }
+            }
+            batch.measurements.extend(flip);
+
+            let r = source.random_words(words);
+            for &(q, pauli) in product.iter() {
+                let (px, pz) = pauli.to_bits();
+                for (w, &r) in r.iter().enumerate() {
+                    let i = q * words + w;
+                    if *reset {
+                        x[i] = 0;
+                        z[i] = 0;
+                    }
+                    x[i] ^= mask(px) & r;
+                    z[i] ^= mask(pz) & r;
+                }
+            }
+        }
+        FrameOp::Reset(a, pauli) => {
+            let (px, pz) = pauli.to_bits();
+            for (w, r) in source.random_words(words).into_iter().enumerate() {
+                x[a * words + w] = mask(px) & r;
+                z[a * words + w] = mask(pz) & r;
+            }
+        }
+        FrameOp::MeasureToZero(a) => {
+            // frameがZ_aと反可換なshotには, 参照の状態のstabilizerを掛けて可換にする
+            *gauge += 1;
+            if let Some(stabilizer) = &reference.gauges[*gauge - 1] {
+                let anticommute: Vec<u64> = x[a * words..(a + 1) * words].to_vec();
+                for &(q, pauli) in stabilizer.iter() {
+                    let (px, pz) = pauli.to_bits();
+                    for (w, &m) in anticommute.iter().enumerate() {
+                        x[q * words + w] ^= mask(px) & m;
+                        z[q * words + w] ^= mask(pz) & m;
+                    }
+                }
+            }
+            for (w, r) in source.random_words(words).into_iter().enumerate() {
+                z[a * words + w] ^= r;
+            }
+        }
+        FrameOp::Feedback(pauli, lookback, a) => {
+            // 参照の実行ではその測定結果でPauliを作用させているので, 結果が反転したshotだけ差が出る
+            let flip = lookback_parity(&batch.measurements, words, &[*lookback]);
+            let (px, pz) = pauli.to_bits();
+            for (w, flip) in flip.into_iter().enumerate() {
+                x[a * words + w] ^= mask(px) & flip;
+                z[a * words + w] ^= mask(pz) & flip;
+            }
+        }
+        FrameOp::Noise1(a, p) => {
+            for (shot, pauli) in source.choose(p, shots) {
+                let (px, pz) = [Pauli::X, Pauli::Y, Pauli::Z][pauli].to_bits();
+                let (w, bit) = (shot / 64, 1 << (shot % 64));
+                x[a * words + w] ^= mask(px) & bit;
+                z[a * words + w] ^= mask(pz) & bit;
+            }
+        }
+        FrameOp::Noise2(a, b, p) => {
+            const PAULI: [Pauli; 4] = [Pauli::I, Pauli::X, Pauli::Y, Pauli::Z];
+            for (shot, i) in source.choose(p, shots) {
+                let i = i + 1;
+                let (w, bit) = (shot / 64, 1 << (shot % 64));
+                for (q, pauli) in [(a, PAULI[i >> 2]), (b, PAULI[i & 3])] {
+                    let (px, pz) = pauli.to_bits();
+                    x[q * words + w] ^= mask(px) & bit;
+                    z[q * words + w] ^= mask(pz) & bit;
+                }
+            }
+        }
+        FrameOp::Detector(lookback) => {
+            let parity = lookback_parity(&batch.measurements, words, lookback);
+            batch.detectors.extend(parity);
+        }
+        FrameOp::ObservableInclude(observable, lookback) => {
+            let parity = lookback_parity(&batch.measurements, words, lookback);
+            for (w, parity) in parity.into_iter().enumerate() {
+                batch.observables[observable * words + w] ^= parity;
+            }
+        }
+        FrameOp::Repeat(n, body) => {
+            for _ in 0..*n {
+                for op in body.iter() {
+                    execute(op, batch, source, reference, gauge);
+                }
+            }
+        }
+    }
+}
+
+/// bitが1なら全bitが1のmask
+#[inline]
+fn mask(bit: u8) -> u64 {
+    0u64.wrapping_sub(bit as u64)
+}
+
+fn random_words(rng: &mut SmallRng, words: usize) -> Vec<u64> {
+    (0..words).map(|_| rng.gen()).collect()
+}
+
+/// 各shotが確率pで1になるmask (幾何分布で次の1までを飛ばす)
+fn sample_mask(rng: &mut SmallRng, p: f32, shots: usize) -> Vec<u64> {
+    let mut mask = vec![0; shots.div_ceil(64)];
+    if p <= 0.0 {
+        return mask;
+    }
+    let log_q = (1.0 - p.min(1.0) as f64).ln();
+    let mut shot = 0;
+    loop {
+        if log_q != f64::NEG_INFINITY {
+            let u: f64 = rng.gen();
+            shot += ((1.0 - u).ln() / log_q).floor() as usize;
+        }
+        if shot >= shots {
+            return mask;
+        }
+        mask[shot / 64] |= 1 << (shot % 64);
+        shot += 1;
+    }
+}
+
+/// maskで1になっているshotのindex
+fn iter_bits(mask: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    mask.iter().enumerate().flat_map(|(w, &word)| {
+        (0..64)
+            .filter(move |i| (word >> i) & 1 == 1)
+            .map(move |i| w * 64 + i)
+    })
+}
+
+/// 確率pに比例してindexを選ぶ
+fn choose(rng: &mut SmallRng, p: &[f32]) -> usize {
+    let r = rng.gen::<f32>() * p.iter().sum::<f32>();
+    let mut cumulative = 0.0;
+    for (i, &p_i) in p.iter().enumerate() {
+        cumulative += p_i;
+        if r < cumulative {
+            return i;
+        }
+    }
+    p.len() - 1
+}
+
+/// rec[-k]の反転のparity
+fn lookback_parity(measurements: &[u64], words: usize, lookback: &[usize]) -> Vec<u64> {
+    let count = measurements.len() / words;
+    let mut parity = vec![0; words];
+    for &k in lookback.iter() {
+        let row = &measurements[(count - k) * words..(count - k + 1) * words];
+        parity.iter_mut().zip(row).for_each(|(p, r)| *p ^= r);
+    }
+    parity
+}
+
+/// 1 qubit gateによるX, Zの像 ((x, z) bit)
+///
+/// |0>のtableauのdestabilizerはX, stabilizerはZなので, gateを作用させた後の各行が像になる
+fn conjugation_1(gate: &Operation) -> [[u8; 2]; 2] {
+    let mut tableau = StabilizerTableau::new(1);
+    apply_gate(&mut tableau, gate, 0, 0);
+    [0, 1].map(|row| [tableau.x_bit(row, 0), tableau.z_bit(row, 0)])
+}
+
+/// 2 qubit gateによるX_a, Z_a, X_b, Z_bの像 ((x_a, z_a, x_b, z_b) bit)
+fn conjugation_2(gate: &Operation) -> [[u8; 4]; 4] {
+    let mut tableau = StabilizerTableau::new(2);
+    apply_gate(&mut tableau, gate, 0, 1);
+    // X_a, Z_a, X_b, Z_bに対応する行
+    [0, 2, 1, 3].map(|row| {
+        [
+            tableau.x_bit(row, 0),
+            tableau.z_bit(row, 0),
+            tableau.x_bit(row, 1),
+            tableau.z_bit(row, 1),
+        ]
+    })
+}
+
+impl SimulatorInterface for FrameSimulator {
+    /// add CNOT gate
+    fn add_cx(&mut self, a: usize, b: usize) {
+        self.push(Operation::CX(a, b));
+    }
+
+    /// add Hadamard gate
+    fn add_h(&mut self, a: usize) {
+        self.push(Operation::H(a));
+    }
+
+    /// add S gate (Phase gate)
+    fn add_s(&mut self, a: usize) {
+        self.push(Operation::S(a));
+    }
+
+    /// add X gate
+    fn add_x(&mut self, a: usize) {
+        self.push(Operation::X(a))
+    }
+
+    /// add Z gate
+    fn add_z(&mut self, a: usize) {
+        self.push(Operation::Z(a))
+    }
+
+    /// add Y gate
+    fn add_y(&mut self, a: usize) {
+        self.push(Operation::Y(a));
+    }
+
+    /// add S dagger gate
+    fn add_s_dag(&mut self, a: usize) {
+        self.push(Operation::SDag(a));
+    }
+
+    /// add sqrt(X) gate
+    fn add_sqrt_x(&mut self, a: usize) {
+        self.push(Operation::SqrtX(a));
+    }
+
+    /// add sqrt(X) dagger gate
+    fn add_sqrt_x_dag(&mut self, a: usize) {
+        self.push(Operation::SqrtXDag(a));
+    }
+
+    /// add sqrt(Y) gate
+    fn add_sqrt_y(&mut self, a: usize) {
+        self.push(Operation::SqrtY(a));
+    }
+
+    /// add sqrt(Y) dagger gate
+    fn add_sqrt_y_dag(&mut self, a: usize) {
+        self.push(Operation::SqrtYDag(a));
+    }
+
+    /// add controlled Z gate
+    fn add_cz(&mut self, a: usize, b: usize) {
+        self.push(Operation::CZ(a, b));
+    }
+
+    /// add controlled Y gate
+    fn add_cy(&mut self, a: usize, b: usize) {
+        self.push(Operation::CY(a, b));
+    }
+
+    /// add SWAP gate
+    fn add_swap(&mut self, a: usize, b: usize) {
+        self.push(Operation::Swap(a, b));
+    }
+
+    /// add iSWAP gate
+    fn add_iswap(&mut self, a: usize, b: usize) {
+        self.push(Operation::ISwap(a, b));
+    }
+
+    /// add iSWAP dagger gate
+    fn add_iswap_dag(&mut self, a: usize, b: usize) {
+        self.push(Operation::ISwapDag(a, b));
+    }
+
+    /// add X-controlled X gate
+    fn add_xcx(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCX(a, b));
+    }
+
+    /// add X-controlled Y gate
+    fn add_xcy(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCY(a, b));
+    }
+
+    /// add X-controlled Z gate
+    fn add_xcz(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCZ(a, b));
+    }
+
+    /// add Y-controlled X gate
+    fn add_ycx(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCX(a, b));
+    }
+
+    /// add Y-controlled Y gate
+    fn add_ycy(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCY(a, b));
+    }
+
+    /// add Y-controlled Z gate
+    fn add_ycz(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCZ(a, b));
+    }
+
+    /// add measurement
+    fn add_measurement(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::M(a, error_rate))
+    }
+
+    fn add_measurement_to_zero(&mut self, a: usize) {
+        self.push(Operation::MToZero(a));
+    }
+
+    /// add measurement_and_reset
+    fn add_measurement_and_reset(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MR(a, error_rate))
+    }
+
+    /// add measurement of Pauli product
+    fn add_measurement_signed_pauli_product(
+        &mut self,
+        product: Vec<(usize, Pauli)>,
+        sign: u8,
+        error_rate: f32,
+    ) -> usize {
+        let operation = Operation::measurement_pauli_product(&product, sign, error_rate)
+            .unwrap_or_else(|e| panic!("{}", e));
+        self.push_measurement(operation)
+    }
+
+    /// add measurement in X basis
+    fn add_measurement_x(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MX(a, error_rate))
+    }
+
+    /// add measurement in Y basis
+    fn add_measurement_y(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MY(a, error_rate))
+    }
+
+    /// add reset to |0>
+    fn add_reset_z(&mut self, a: usize) {
+        self.push(Operation::R(a));
+    }
+
+    /// add reset to |+>
+    fn add_reset_x(&mut self, a: usize) {
+        self.push(Operation::RX(a));
+    }
+
+    /// add reset to |+i>
+    fn add_reset_y(&mut self, a: usize) {
+        self.push(Operation::RY(a));
+    }
+
+    /// add measurement_and_reset in X basis
+    fn add_measurement_and_reset_x(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MRX(a, error_rate))
+    }
+
+    /// add measurement_and_reset in Y basis
+    fn add_measurement_and_reset_y(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MRY(a, error_rate))
+    }
+
+    /// add Pauli controlled by rec[-k]
+    fn add_feedback(&mut self, pauli: Pauli, lookback: usize, a: usize) {
+        assert!(
+            (1..=self.measurement_count).contains(&lookback),
+            "feedback refers to a measurement that does not exist"
+        );
+        self.push(Operation::Feedback(pauli, lookback, a));
+    }
+
+    fn add_noise(&mut self, a: usize, noise_type: NoiseType) {
+        match noise_type {
+            NoiseType::Depolarizing(p) => self.push(Operation::Depolarizing(a, p)),
+            NoiseType::PauliChannel1(px, py, pz) => {
+                self.push(Operation::PauliChannel1(a, px, py, pz))
+            }
+            _ => panic!("{:?} is two qubit noise, use add_noise2", noise_type),
+        }
+    }
+
+    fn add_noise2(&mut self, a: usize, b: usize, noise_type: NoiseType) {
+        match noise_type {
+            NoiseType::Depolarizing2(p) => self.push(Operation::Depolarizing2(a, b, p)),
+            NoiseType::PauliChannel2(p) => self.push(Operation::PauliChannel2(a, b, p)),
+            _ => panic!("{:?} is single qubit noise, use add_noise", noise_type),
+        }
+    }
+
+    fn add_tick(&mut self) {
+        self.push(Operation::Tick);
+    }
+
+    fn repeat_from(&mut self, start: usize, repetitions: usize) {
+        self.program = None;
+        let body = self.dispatcher.repeat_from(start, repetitions);
+        let (measurements, detectors) = (body.measurement_count(), body.detector_count());
+        self.measurement_count = self.measurement_count - measurements + repetitions * measurements;
+        self.detector_count = self.detector_count - detectors + repetitions * detectors;
+    }
+
+    fn operation_count(&self) -> usize {
+        self.dispatcher.operations().len()
+    }
+
+    fn insert_noise_model(&mut self, model: &NoiseModel, data_qubit: &[usize], start: usize) {
+        let operations = circuit_noise::apply_noise_model(
+            &self.dispatcher.operations()[start..],
+            model,
+            data_qubit,
+        );
+        self.program = None;
+        self.dispatcher.replace_from(start, operations);
+    }
+
+    /// reset (runは常に|0...0>から実行される)
+    fn reset(&mut self) {
+        self.measurement_record.clear();
+        self.detection_events.clear();
+        self.observable_flips.clear();
+    }
+
+    /// measurement direct
+    fn measurement(&mut self, a: usize, error_rate: f32) -> u8 {
+        let outcome = match self.tableau.random_measurement_pivot(a) {
+            Some(p) => {
+                let outcome = self.rng.gen_range(0..2);
+                self.tableau.collapse(p, a, outcome);
+                outcome
+            }
+            None => self.tableau.deterministic_measurement(a),
+        };
+        // frameのXで結果が反転し, 測定後はZをランダムに掛けてよい
+        let outcome = outcome ^ self.frame[a].0;
+        self.frame[a].1 ^= self.rng.gen_range(0..2);
+
+        let outcome = if self.rng.gen::<f32>() < error_rate {
+            outcome ^ 1
+        } else {
+            outcome
+        };
+        self.measurement_record.push(outcome);
+        outcome
+    }
+
+    fn measurement_count(&self) -> usize {
+        self.measurement_count
+    }
+
+    fn measurement_record(&self) -> &MeasurementRecord {
+        &self.measurement_record
+    }
+    fn add_detector(&mut self, coords: Vec<f32>, lookback: Vec<usize>) -> usize {
+        debug_assert!(
+            lookback
+                .iter()
+                .all(|k| (1..=self.measurement_count).contains(k)),
+            "detector refers to a measurement that does not exist"
+        );
+        self.push(Operation::Detector(coords, lookback));
+        self.detector_count += 1;
+        self.detector_count - 1
+    }
+
+    fn add_observable_include(&mut self, observable: usize, lookback: Vec<usize>) {
+        self.observable_count = self.observable_count.max(observable + 1);
+        self.push(Operation::ObservableInclude(observable, lookback));
+    }
+
+    fn detector_count(&self) -> usize {
+        self.detector_count
+    }
+
+    fn detection_events(&self) -> &[u8] {
+        &self.detection_events
+    }
+
+    fn circuit(&self) -> &Circuit {
+        self.dispatcher.circuit()
+    }
+
+    fn observable_flips(&self) -> &[u8] {
+        &self.observable_flips
+    }
+
+    /// run circuit (まとめてサンプルしたshotを1つずつ取り出す)
+    fn run(&mut self) {
+        self.compile();
+        if self.batch.is_none() || self.next_shot == self.batch_size {
+            self.batch = Some(Box::new(self.simulate(self.batch_size)));
+            self.next_shot = 0;
+        }
+        let shot = self.next_shot;
+        self.next_shot += 1;
+
+        let batch = self.batch.as_ref().unwrap();
+        let reference = &self.program.as_ref().unwrap().reference;
+
+        self.measurement_record.clear();
+        for (flip, bit) in batch
+            .bits(&batch.measurements, shot)
+            .into_iter()
+            .zip(reference.measurements.iter())
+        {
+            self.measurement_record.push(flip ^ bit);
+        }
+        self.detection_events = batch.bits(&batch.detectors, shot);
+        self.observable_flips = batch.bits(&batch.observables, shot);
+
+        self.tableau = reference.tableau.clone();
+        self.frame = batch
+            .bits(&batch.x, shot)
+            .into_iter()
+            .zip(batch.bits(&batch.z, shot))
+            .collect();
+    }
+}
@@ -0,0 +1,59 @@
+/// 測定結果を測定した順に記録する (追記のみ)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeasurementRecord {
+    record: Vec<u8>,
+}
+
+impl MeasurementRecord {
+    pub fn new() -> Self {
+        Self { record: Vec::new() }
+    }
+
+    /// 測定結果を追加し, そのindexを返す
+    pub fn push(&mut self, outcome: u8) -> usize {
+        debug_assert!(outcome <= 1, "outcome must be 0 or 1");
+        self.record.push(outcome);
+        self.record.len() - 1
+    }
+
+    /// 記録を消去する
+    pub fn clear(&mut self) {
+        self.record.clear();
+    }
+
+    /// 記録された測定の数
+    pub fn len(&self) -> usize {
+        self.record.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.record.is_empty()
+    }
+
+    /// index番目の測定結果
+    pub fn get(&self, index: usize) -> u8 {
+        *self
+            .record
+            .get(index)
+            .unwrap_or_else(|| panic!("measurement {} is not recorded", index))
+    }
+
+    /// k個前の測定結果 (rec[-k], kは1以上)
+    pub fn lookback(&self, k: usize) -> u8 {
+        assert!(
+            (1..=self.record.len()).contains(&k),
+            "rec[-{}] is out of range",
+            k
+        );
+        self.record[self.record.len() - k]
+    }
+
+    /// 測定結果をbit vectorとして返す
+    pub fn bits(&self) -> &[u8] {
+        &self.record
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, u8> {
+        self.record.iter()
+    }
+}
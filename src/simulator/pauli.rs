@@ -0,0 +1,141 @@
+/// single qubit Pauli operator
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
+}
+
+impl Pauli {
+    /// return (x bit, z bit) of the symplectic representation
+    pub fn to_bits(self) -> (u8, u8) {
+        match self {
+            Pauli::I => (0, 0),
+            Pauli::X => (1, 0),
+            Pauli::Y => (1, 1),
+            Pauli::Z => (0, 1),
+        }
+    }
+
+    /// make Pauli from (x bit, z bit)
+    pub fn from_bits(x: u8, z: u8) -> Self {
+        match (x, z) {
+            (0, 0) => Pauli::I,
+            (1, 0) => Pauli::X,
+            (1, 1) => Pauli::Y,
+            (0, 1) => Pauli::Z,
+            _ => panic!("Pauli bits must be 0 or 1"),
+        }
+    }
+
+    /// self * other = i^k * P となる (k, P)
+    pub fn multiply(self, other: Pauli) -> (u8, Pauli) {
+        let (x1, z1) = self.to_bits();
+        let (x2, z2) = other.to_bits();
+        let (x, z) = (x1 ^ x2, z1 ^ z2);
+        // Y = iXZとすると X^x1 Z^z1 X^x2 Z^z2 = (-1)^(z1 x2) X^x Z^z
+        let k = (x1 * z1 + x2 * z2 + 2 * z1 * x2 + 4 - x * z) % 4;
+        (k, Pauli::from_bits(x, z))
+    }
+}
+
+/// 同じqubitに作用するPauliをまとめ, 積が(-1)^sign * (まとめたPauli積) となる (sign, Pauli積) を返す
+/// (X0*Z0 = -iY0 のような反エルミートな積は測定できないのでErr)
+pub fn combine_pauli_product(
+    product: &[(usize, Pauli)],
+) -> Result<(u8, Vec<(usize, Pauli)>), String> {
+    let mut combined: Vec<(usize, Pauli)> = Vec::new();
+    let mut log_i = 0;
+    for &(a, pauli) in product.iter() {
+        match combined.iter_mut().find(|(b, _)| *b == a) {
+            Some((_, current)) => {
+                let (k, result) = current.multiply(pauli);
+                log_i = (log_i + k) % 4;
+                *current = result;
+            }
+            None => combined.push((a, pauli)),
+        }
+    }
+    if log_i % 2 == 1 {
+        let text = product
+            .iter()
+            .map(|(a, pauli)| format!("{:?}{}", pauli, a))
+            .collect::<Vec<_>>()
+            .join("*");
+        return Err(format!("Pauli product {} is anti-Hermitian", text));
+    }
+    combined.retain(|&(_, pauli)| pauli != Pauli::I);
+    Ok((log_i / 2, combined))
+}
+
+/// 符号付きのPauli積 (e.g. +XXZI), i番目の文字がqubit iに作用する
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PauliString {
+    /// 0なら+, 1なら-
+    pub sign: u8,
+    pub paulis: Vec<Pauli>,
+}
+
+impl PauliString {
+    /// identity on qubit_num qubits
+    pub fn identity(qubit_num: usize) -> Self {
+        PauliString {
+            sign: 0,
+            paulis: vec![Pauli::I; qubit_num],
+        }
+    }
+
+    /// number of qubits
+    pub fn len(&self) -> usize {
+        self.paulis.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paulis.is_empty()
+    }
+
+    /// Iを除いた(qubit, Pauli)の列 (add_measurement_pauli_productなどに渡せる形)
+    pub fn to_product(&self) -> Vec<(usize, Pauli)> {
+        self.paulis
+            .iter()
+            .enumerate()
+            .filter(|(_, &pauli)| pauli != Pauli::I)
+            .map(|(a, &pauli)| (a, pauli))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for PauliString {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", if self.sign == 0 { '+' } else { '-' })?;
+        for pauli in self.paulis.iter() {
+            write!(f, "{:?}", pauli)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for PauliString {
+    type Err = String;
+
+    /// parse such as "+XXZI", "-YZ" or "XZ" (符号がなければ+)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sign, body) = match s.chars().next() {
+            Some('+') => (0, &s[1..]),
+            Some('-') => (1, &s[1..]),
+            _ => (0, s),
+        };
+        let paulis = body
+            .chars()
+            .map(|c| match c {
+                'I' | '_' => Ok(Pauli::I),
+                'X' => Ok(Pauli::X),
+                'Y' => Ok(Pauli::Y),
+                'Z' => Ok(Pauli::Z),
+                _ => Err(format!("invalid Pauli '{}' in \"{}\"", c, s)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(PauliString { sign, paulis })
+    }
+}
@@ -0,0 +1,626 @@
+use super::pauli::{Pauli, PauliString};
+use std::ops::Range;
+
+/// 64bitのwordに詰めたstabilizer tableau
+///
+/// 行0..nがdestabilizer, 行n..2nがstabilizer.
+/// qubitごとに全ての行のx bitとz bitを列として持つので, gateは1回のword演算で64行に作用する
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StabilizerTableau {
+    qubit_num: usize,
+    /// 1列あたりのword数
+    words: usize,
+    /// qubit aの列は x[a * words..(a + 1) * words]
+    x: Vec<u64>,
+    z: Vec<u64>,
+    /// 各行の符号
+    r: Vec<u64>,
+}
+
+impl StabilizerTableau {
+    /// make tableau of |0...0>
+    pub fn new(qubit_num: usize) -> Self {
+        let words = (qubit_num * 2).div_ceil(64);
+
+        let mut tableau = Self {
+            qubit_num,
+            words,
+            x: vec![0; qubit_num * words],
+            z: vec![0; qubit_num * words],
+            r: vec![0; words],
+        };
+        tableau.reset();
+
+        tableau
+    }
+
+    /// reset to |0...0>
+    pub fn reset(&mut self) {
+        self.x.iter_mut().for_each(|w| *w = 0);
+        self.z.iter_mut().for_each(|w| *w = 0);
+        self.r.iter_mut().for_each(|r| *r = 0);
+
+        for a in 0..self.qubit_num {
+            self.set_bits(a, a, 1, 0);
+            self.set_bits(a + self.qubit_num, a, 0, 1);
+        }
+    }
+
+    /// return the number of qubits
+    pub fn qubit_num(&self) -> usize {
+        self.qubit_num
+    }
+
+    /// index (行やqubit) をwordの位置とmaskにする
+    #[inline]
+    fn bit(a: usize) -> (usize, u64) {
+        (a / 64, 1 << (a % 64))
+    }
+
+    /// qubit aの列
+    #[inline]
+    fn column(&self, a: usize) -> Range<usize> {
+        a * self.words..(a + 1) * self.words
+    }
+
+    #[inline]
+    fn index(&self, row: usize, a: usize) -> (usize, u64) {
+        let (word, mask) = Self::bit(row);
+        (a * self.words + word, mask)
+    }
+
+    /// x bit of qubit `a` in `row`
+    #[inline]
+    pub fn x_bit(&self, row: usize, a: usize) -> u8 {
+        let (i, mask) = self.index(row, a);
+        (self.x[i] & mask != 0) as u8
+    }
+
+    /// z bit of qubit `a` in `row`
+    #[inline]
+    pub fn z_bit(&self, row: usize, a: usize) -> u8 {
+        let (i, mask) = self.index(row, a);
+        (self.z[i] & mask != 0) as u8
+    }
+
+    /// sign bit of `row`
+    #[inline]
+    pub fn sign(&self, row: usize) -> u8 {
+        let (word, mask) = Self::bit(row);
+        (self.r[word] & mask != 0) as u8
+    }
+
+    fn set_bits(&mut self, row: usize, a: usize, x: u8, z: u8) {
+        let (i, mask) = self.index(row, a);
+        self.x[i] = (self.x[i] & !mask) | (mask * x as u64);
+        self.z[i] = (self.z[i] & !mask) | (mask * z as u64);
+    }
+
+    fn set_sign(&mut self, row: usize, sign: u8) {
+        let (word, mask) = Self::bit(row);
+        self.r[word] = (self.r[word] & !mask) | (mask * sign as u64);
+    }
+
+    /// qubit aの列の各word (64行分の(x, z, r)) にfを作用させる
+    #[inline]
+    fn for_each_word_1(&mut self, a: usize, f: impl Fn(&mut u64, &mut u64, &mut u64)) {
+        for (w, i) in self.column(a).enumerate() {
+            f(&mut self.x[i], &mut self.z[i], &mut self.r[w]);
+        }
+    }
+
+    /// qubit a, bの列の各word (64行分の(x_a, z_a, x_b, z_b), r) にfを作用させる
+    #[inline]
+    fn for_each_word_2(&mut self, a: usize, b: usize, f: impl Fn(&mut [u64; 4], &mut u64)) {
+        assert_ne!(a, b, "two qubit gate needs two different qubits");
+
+        for (w, (i, j)) in self.column(a).zip(self.column(b)).enumerate() {
+            let mut bits = [self.x[i], self.z[i], self.x[j], self.z[j]];
+            f(&mut bits, &mut self.r[w]);
+            [self.x[i], self.z[i], self.x[j], self.z[j]] = bits;
+        }
+    }
+
+    /// CNOT gate
+    pub fn cx(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, row_cx);
+    }
+
+    /// Hadamard gate
+    pub fn h(&mut self, a: usize) {
+        self.for_each_word_1(a, row_h);
+    }
+
+    /// S gate (Phase gate)
+    pub fn s(&mut self, a: usize) {
+        self.for_each_word_1(a, row_s);
+    }
+
+    /// S dagger gate
+    pub fn s_dag(&mut self, a: usize) {
+        self.for_each_word_1(a, row_s_dag);
+    }
+
+    /// X gate
+    pub fn x(&mut self, a: usize) {
+        self.for_each_word_1(a, |_, z, r| *r ^= *z);
+    }
+
+    /// Y gate
+    pub fn y(&mut self, a: usize) {
+        self.for_each_word_1(a, |x, z, r| *r ^= *x ^ *z);
+    }
+
+    /// Z gate
+    pub fn z(&mut self, a: usize) {
+        self.for_each_word_1(a, |x, _, r| *r ^= *x);
+    }
+
+    /// sqrt(X) gate
+    pub fn sqrt_x(&mut self, a: usize) {
+        self.for_each_word_1(a, row_sqrt_x);
+    }
+
+    /// sqrt(X) dagger gate
+    pub fn sqrt_x_dag(&mut self, a: usize) {
+        self.for_each_word_1(a, row_sqrt_x_dag);
+    }
+
+    /// sqrt(Y) gate
+    pub fn sqrt_y(&mut self, a: usize) {
+        self.for_each_word_1(a, |x, z, r| {
+            *r ^= *x & !*z;
+            std::mem::swap(x, z);
+        });
+    }
+
+    /// sqrt(Y) dagger gate
+    pub fn sqrt_y_dag(&mut self, a: usize) {
+        self.for_each_word_1(a, |x, z, r| {
+            *r ^= *z & !*x;
+            std::mem::swap(x, z);
+        });
+    }
+
+    /// controlled Z gate
+    pub fn cz(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, row_cz);
+    }
+
+    /// controlled Y gate
+    pub fn cy(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, row_cy);
+    }
+
+    /// SWAP gate
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, |bits, _| row_swap(bits));
+    }
+
+    /// iSWAP gate
+    pub fn iswap(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, |bits, r| {
+            let [x_a, z_a, x_b, z_b] = bits;
+            row_s(x_a, z_a, r);
+            row_s(x_b, z_b, r);
+            row_cz(bits, r);
+            row_swap(bits);
+        });
+    }
+
+    /// iSWAP dagger gate
+    pub fn iswap_dag(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, |bits, r| {
+            let [x_a, z_a, x_b, z_b] = bits;
+            row_s_dag(x_a, z_a, r);
+            row_s_dag(x_b, z_b, r);
+            row_cz(bits, r);
+            row_swap(bits);
+        });
+    }
+
+    /// X-controlled X gate
+    pub fn xcx(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, |bits, r| {
+            let [x_a, z_a, ..] = bits;
+            row_h(x_a, z_a, r);
+            row_cx(bits, r);
+            let [x_a, z_a, ..] = bits;
+            row_h(x_a, z_a, r);
+        });
+    }
+
+    /// X-controlled Y gate
+    pub fn xcy(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, |bits, r| {
+            let [x_a, z_a, ..] = bits;
+            row_h(x_a, z_a, r);
+            row_cy(bits, r);
+            let [x_a, z_a, ..] = bits;
+            row_h(x_a, z_a, r);
+        });
+    }
+
+    /// X-controlled Z gate
+    pub fn xcz(&mut self, a: usize, b: usize) {
+        self.cx(b, a);
+    }
+
+    /// Y-controlled X gate
+    pub fn ycx(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, |bits, r| {
+            let [x_a, z_a, ..] = bits;
+            row_sqrt_x(x_a, z_a, r);
+            row_cx(bits, r);
+            let [x_a, z_a, ..] = bits;
+            row_sqrt_x_dag(x_a, z_a, r);
+        });
+    }
+
+    /// Y-controlled Y gate
+    pub fn ycy(&mut self, a: usize, b: usize) {
+        self.for_each_word_2(a, b, |bits, r| {
+            let [x_a, z_a, ..] = bits;
+            row_sqrt_x(x_a, z_a, r);
+            row_cy(bits, r);
+            let [x_a, z_a, ..] = bits;
+            row_sqrt_x_dag(x_a, z_a, r);
+        });
+    }
+
+    /// Y-controlled Z gate
+    pub fn ycz(&mut self, a: usize, b: usize) {
+        self.cy(b, a);
+    }
+
+    /// 行iをtargetsの全ての行に掛ける (Aaronson-Gottesmanのrowsum)
+    ///
+    /// 積で生じるiの冪は行ごとの2bitのカウンタとして64行ずつまとめて数える
+    fn multiply_rows(&mut self, targets: &[u64], i: usize) {
+        debug_assert_eq!(targets[i / 64] >> (i % 64) & 1, 0);
+        let words = nonzero_words(targets);
+        let mut cnt1 = vec![0u64; self.words];
+        let mut cnt2 = vec![0u64; self.words];
+
+        for a in 0..self.qubit_num {
+            let (x2, z2) = (broadcast(self.x_bit(i, a)), broadcast(self.z_bit(i, a)));
+            if x2 == 0 && z2 == 0 {
+                continue;
+            }
+            for &w in words.iter() {
+                let k = a * self.words + w;
+                let (x1, z1) = (self.x[k], self.z[k]);
+                let (x, z) = (x1 ^ x2, z1 ^ z2);
+
+                let x1z2 = x1 & z2;
+                let anti_commutes = ((x2 & z1) ^ x1z2) & targets[w];
+                cnt2[w] ^= (cnt1[w] ^ x ^ z ^ x1z2) & anti_commutes;
+                cnt1[w] ^= anti_commutes;
+
+                self.x[k] ^= x2 & targets[w];
+                self.z[k] ^= z2 & targets[w];
+            }
+        }
+
+        // 可換な行の積なのでiの冪は偶数で, cnt2が符号の反転になる
+        let r_i = broadcast(self.sign(i));
+        for &w in words.iter() {
+            self.r[w] ^= (r_i ^ cnt2[w]) & targets[w];
+        }
+    }
+
+    /// rowsの行 (互いに可換) の積の符号
+    fn product_sign(&self, rows: &[u64]) -> u8 {
+        let mut log_i: u32 = rows
+            .iter()
+            .zip(self.r.iter())
+            .map(|(&m, &r)| 2 * (m & r).count_ones())
+            .sum();
+
+        // qubitごとに i^(xz) X^x Z^z の積をX, Zの順に並べ替えたときのiの冪を数える
+        let words = nonzero_words(rows);
+        for a in 0..self.qubit_num {
+            let (mut y_count, mut swaps, mut x_parity, mut z_parity) = (0, 0, 0, 0);
+            for &w in words.iter() {
+                let k = a * self.words + w;
+                let (x, z) = (self.x[k] & rows[w], self.z[k] & rows[w]);
+                y_count += (x & z).count_ones();
+                // 後ろの行のX^xを前の行のZ^zより左に移すと, その数だけ符号が反転する
+                let z_before = prefix_parity(z) ^ z ^ broadcast(z_parity);
+                swaps += (x & z_before).count_ones();
+                x_parity ^= (x.count_ones() & 1) as u8;
+                z_parity ^= (z.count_ones() & 1) as u8;
+            }
+            // X^x Z^z = i^(-xz) (xzが1ならY)
+            log_i += y_count + 2 * swaps + 4 - (x_parity & z_parity) as u32;
+        }
+        debug_assert_eq!(log_i % 2, 0, "product of stabilizers must be Hermitian");
+
+        ((log_i >> 1) & 1) as u8
+    }
+
+    /// X0*Z3*Y5のようなPauli積を(x words, z words)に詰める
+    /// (同じqubitのPauliはbitのxorになり, 位相は無視するのでcombine_pauli_productでまとめたものを渡す)
+    pub fn pack_pauli_product(&self, product: &[(usize, Pauli)]) -> (Vec<u64>, Vec<u64>) {
+        let mut x = vec![0; self.qubit_num.div_ceil(64)];
+        let mut z = vec![0; self.qubit_num.div_ceil(64)];
+
+        for &(a, pauli) in product.iter() {
+            let (word, mask) = Self::bit(a);
+            let (x_bit, z_bit) = pauli.to_bits();
+            x[word] ^= mask * x_bit as u64;
+            z[word] ^= mask * z_bit as u64;
+        }
+
+        (x, z)
+    }
+
+    /// Z_aを詰めたPauli
+    fn pack_z(&self, a: usize) -> (Vec<u64>, Vec<u64>) {
+        let x = vec![0; self.qubit_num.div_ceil(64)];
+        let mut z = x.clone();
+        let (word, mask) = Self::bit(a);
+        z[word] = mask;
+        (x, z)
+    }
+
+    /// 詰めたPauli (x, z) と反可換な行
+    fn anticommuting_rows(&self, x: &[u64], z: &[u64]) -> Vec<u64> {
+        let mut rows = vec![0; self.words];
+        for a in 0..self.qubit_num {
+            let (word, mask) = Self::bit(a);
+            let (x_a, z_a) = (x[word] & mask != 0, z[word] & mask != 0);
+            for (w, k) in self.column(a).enumerate() {
+                if z_a {
+                    rows[w] ^= self.x[k];
+                }
+                if x_a {
+                    rows[w] ^= self.z[k];
+                }
+            }
+        }
+        rows
+    }
+
+    fn stabilizer_rows(&self) -> Range<usize> {
+        self.qubit_num..self.qubit_num * 2
+    }
+
+    /// 詰めたPauli (x, z) と反可換なstabilizerの最初の行 (あれば測定結果はランダム)
+    pub fn random_pauli_measurement_pivot(&self, x: &[u64], z: &[u64]) -> Option<usize> {
+        first_row(&self.anticommuting_rows(x, z), self.stabilizer_rows())
+    }
+
+    /// pivotの行pを使って, 詰めたPauli (x, z) の固有値(-1)^outcomeの固有空間に射影する
+    pub fn collapse_pauli(&mut self, p: usize, x: &[u64], z: &[u64], outcome: u8) {
+        let mut rows = self.anticommuting_rows(x, z);
+        let (word, mask) = Self::bit(p);
+        rows[word] &= !mask;
+        self.multiply_rows(&rows, p);
+
+        // (p - qubit_num) 行目をp行目に置換し、p行目を測定したPauliにする
+        let destabilizer = p - self.qubit_num;
+        for a in 0..self.qubit_num {
+            self.set_bits(destabilizer, a, self.x_bit(p, a), self.z_bit(p, a));
+            let (word, mask) = Self::bit(a);
+            let (x_a, z_a) = (x[word] & mask != 0, z[word] & mask != 0);
+            self.set_bits(p, a, x_a as u8, z_a as u8);
+        }
+        self.set_sign(destabilizer, self.sign(p));
+        self.set_sign(p, outcome);
+    }
+
+    /// 決定的なときの詰めたPauli (x, z) の測定結果
+    pub fn deterministic_pauli_measurement(&self, x: &[u64], z: &[u64]) -> u8 {
+        self.deterministic_outcome(&self.anticommuting_rows(x, z))
+    }
+
+    /// 測定するPauliと反可換なdestabilizer iに対応するstabilizer n + iの積が, 測定するPauliの±1倍になる
+    fn deterministic_outcome(&self, anticommuting: &[u64]) -> u8 {
+        let mut stabilizers = vec![0; self.words];
+        for (w, &word) in anticommuting.iter().enumerate() {
+            let mut word = word & range_mask(w, &(0..self.qubit_num));
+            while word != 0 {
+                let i = w * 64 + word.trailing_zeros() as usize;
+                let (word_s, mask) = Self::bit(i + self.qubit_num);
+                stabilizers[word_s] |= mask;
+                word &= word - 1;
+            }
+        }
+
+        self.product_sign(&stabilizers)
+    }
+
+    /// Z_aと反可換なstabilizerの最初の行 (あればqubit aの測定結果はランダム)
+    pub fn random_measurement_pivot(&self, a: usize) -> Option<usize> {
+        first_row(&self.x[self.column(a)], self.stabilizer_rows())
+    }
+
+    /// [`random_measurement_pivot`](Self::random_measurement_pivot)の行pを使って,
+    /// qubit aをoutcomeに射影する
+    pub fn collapse(&mut self, p: usize, a: usize, outcome: u8) {
+        let (x, z) = self.pack_z(a);
+        self.collapse_pauli(p, &x, &z, outcome);
+    }
+
+    /// 決定的なときのqubit aの測定結果
+    pub fn deterministic_measurement(&self, a: usize) -> u8 {
+        self.deterministic_outcome(&self.x[self.column(a)])
+    }
+
+    /// `row` as a signed Pauli string
+    pub fn row(&self, row: usize) -> PauliString {
+        PauliString {
+            sign: self.sign(row),
+            paulis: (0..self.qubit_num)
+                .map(|a| Pauli::from_bits(self.x_bit(row, a), self.z_bit(row, a)))
+                .collect(),
+        }
+    }
+
+    /// stabilizer generators (rows `n..2n`)
+    pub fn stabilizers(&self) -> Vec<PauliString> {
+        self.stabilizer_rows().map(|row| self.row(row)).collect()
+    }
+
+    /// destabilizers (rows `0..n`)
+    pub fn destabilizers(&self) -> Vec<PauliString> {
+        (0..self.qubit_num).map(|row| self.row(row)).collect()
+    }
+
+    /// 既約行階段形にしたstabilizer generator
+    ///
+    /// qubitごとにXのpivot, Zのpivotの順に他の行から消去するので,
+    /// 同じ状態の2つのtableauは同じgeneratorになる
+    pub fn canonical_stabilizers(&self) -> Vec<PauliString> {
+        let mut tableau = self.clone();
+        let rows = self.stabilizer_rows();
+        let mut pivot = self.qubit_num;
+
+        for a in 0..self.qubit_num {
+            for z_pivot in [false, true] {
+                let column = tableau.column(a);
+                let bits = match z_pivot {
+                    false => tableau.x[column].to_vec(),
+                    true => tableau.z[column].to_vec(),
+                };
+                let Some(p) = first_row(&bits, pivot..rows.end) else {
+                    continue;
+                };
+                let mut targets: Vec<u64> = bits
+                    .iter()
+                    .enumerate()
+                    .map(|(w, &word)| word & range_mask(w, &rows))
+                    .collect();
+                let (word, mask) = Self::bit(p);
+                targets[word] &= !mask;
+                tableau.multiply_rows(&targets, p);
+                tableau.swap_rows(p, pivot);
+                pivot += 1;
+            }
+        }
+
+        tableau.stabilizers()
+    }
+
+    /// whether the two tableaus represent the same state (destabilizers are ignored)
+    pub fn same_state(&self, other: &Self) -> bool {
+        self.qubit_num == other.qubit_num
+            && self.canonical_stabilizers() == other.canonical_stabilizers()
+    }
+
+    /// swap rows `i` and `j`
+    fn swap_rows(&mut self, i: usize, j: usize) {
+        if i == j {
+            return;
+        }
+        for a in 0..self.qubit_num {
+            let (x_i, z_i) = (self.x_bit(i, a), self.z_bit(i, a));
+            self.set_bits(i, a, self.x_bit(j, a), self.z_bit(j, a));
+            self.set_bits(j, a, x_i, z_i);
+        }
+        let r_i = self.sign(i);
+        self.set_sign(i, self.sign(j));
+        self.set_sign(j, r_i);
+    }
+}
+
+/// bitを全bitに広げる
+#[inline]
+fn broadcast(bit: u8) -> u64 {
+    0u64.wrapping_sub(bit as u64)
+}
+
+/// 各bitにそれ以下のbitのxorを入れる
+#[inline]
+fn prefix_parity(mut bits: u64) -> u64 {
+    for shift in [1, 2, 4, 8, 16, 32] {
+        bits ^= bits << shift;
+    }
+    bits
+}
+
+/// 0でないwordの位置
+fn nonzero_words(bits: &[u64]) -> Vec<usize> {
+    (0..bits.len()).filter(|&w| bits[w] != 0).collect()
+}
+
+/// w番目のwordのうちrowsに含まれる行のmask
+fn range_mask(w: usize, rows: &Range<usize>) -> u64 {
+    let (lo, hi) = (w * 64, w * 64 + 64);
+    let (start, end) = (rows.start.clamp(lo, hi) - lo, rows.end.clamp(lo, hi) - lo);
+    if start >= end {
+        0
+    } else {
+        (!0 >> (64 - (end - start))) << start
+    }
+}
+
+/// bitsの立っている行のうち, rowsに含まれる最初の行
+fn first_row(bits: &[u64], rows: Range<usize>) -> Option<usize> {
+    bits.iter().enumerate().find_map(|(w, &word)| {
+        let word = word & range_mask(w, &rows);
+        (word != 0).then(|| w * 64 + word.trailing_zeros() as usize)
+    })
+}
+
+// 64行分のビット(x, z, r)に対するゲートの作用
+
+#[inline]
+fn row_h(x: &mut u64, z: &mut u64, r: &mut u64) {
+    *r ^= *x & *z;
+    std::mem::swap(x, z);
+}
+
+#[inline]
+fn row_s(x: &mut u64, z: &mut u64, r: &mut u64) {
+    *r ^= *x & *z;
+    *z ^= *x;
+}
+
+#[inline]
+fn row_s_dag(x: &mut u64, z: &mut u64, r: &mut u64) {
+    *r ^= *x & !*z;
+    *z ^= *x;
+}
+
+#[inline]
+fn row_sqrt_x(x: &mut u64, z: &mut u64, r: &mut u64) {
+    *r ^= *z & !*x;
+    *x ^= *z;
+}
+
+#[inline]
+fn row_sqrt_x_dag(x: &mut u64, z: &mut u64, r: &mut u64) {
+    *r ^= *x & *z;
+    *x ^= *z;
+}
+
+#[inline]
+fn row_cx([x_a, z_a, x_b, z_b]: &mut [u64; 4], r: &mut u64) {
+    *r ^= *x_a & *z_b & !(*x_b ^ *z_a);
+    *x_b ^= *x_a;
+    *z_a ^= *z_b;
+}
+
+#[inline]
+fn row_cz([x_a, z_a, x_b, z_b]: &mut [u64; 4], r: &mut u64) {
+    *r ^= *x_a & *x_b & (*z_a ^ *z_b);
+    *z_a ^= *x_b;
+    *z_b ^= *x_a;
+}
+
+#[inline]
+fn row_cy(bits: &mut [u64; 4], r: &mut u64) {
+    let [.., x_b, z_b] = bits;
+    row_s_dag(x_b, z_b, r);
+    row_cx(bits, r);
+    let [.., x_b, z_b] = bits;
+    row_s(x_b, z_b, r);
+}
+
+#[inline]
+fn row_swap(bits: &mut [u64; 4]) {
+    bits.swap(0, 2);
+    bits.swap(1, 3);
+}
@@ -0,0 +1,982 @@
+use super::{
+    core::{Dispatcher, SimulatorCore},
+    measurement_record::MeasurementRecord,
+    pauli::{combine_pauli_product, Pauli},
+    Operation, SimulatorInterface,
+};
+use crate::circuit::Circuit;
+use crate::noise::{
+    circuit_noise,
+    noise_model::{NoiseModel, NoiseType},
+};
+use num_complex::Complex64;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_4};
+
+/// 扱えるqubit数の上限 (状態ベクトルは2^n個の振幅を持つ)
+pub const MAX_QUBIT_NUM: usize = 24;
+
+/// 確率がこれ以下なら測定結果は決定的とみなす
+const EPSILON: f64 = 1e-9;
+
+type Matrix = [[Complex64; 2]; 2];
+
+const fn c(re: f64, im: f64) -> Complex64 {
+    Complex64::new(re, im)
+}
+
+const ZERO: Complex64 = c(0.0, 0.0);
+const ONE: Complex64 = c(1.0, 0.0);
+const I: Complex64 = c(0.0, 1.0);
+
+const MATRIX_H: Matrix = [
+    [c(FRAC_1_SQRT_2, 0.0), c(FRAC_1_SQRT_2, 0.0)],
+    [c(FRAC_1_SQRT_2, 0.0), c(-FRAC_1_SQRT_2, 0.0)],
+];
+const MATRIX_S: Matrix = [[ONE, ZERO], [ZERO, I]];
+const MATRIX_S_DAG: Matrix = [[ONE, ZERO], [ZERO, c(0.0, -1.0)]];
+const MATRIX_X: Matrix = [[ZERO, ONE], [ONE, ZERO]];
+const MATRIX_Y: Matrix = [[ZERO, c(0.0, -1.0)], [I, ZERO]];
+const MATRIX_Z: Matrix = [[ONE, ZERO], [ZERO, c(-1.0, 0.0)]];
+const MATRIX_SQRT_X: Matrix = [[c(0.5, 0.5), c(0.5, -0.5)], [c(0.5, -0.5), c(0.5, 0.5)]];
+const MATRIX_SQRT_X_DAG: Matrix = [[c(0.5, -0.5), c(0.5, 0.5)], [c(0.5, 0.5), c(0.5, -0.5)]];
+const MATRIX_SQRT_Y: Matrix = [[c(0.5, 0.5), c(-0.5, -0.5)], [c(0.5, 0.5), c(0.5, 0.5)]];
+const MATRIX_SQRT_Y_DAG: Matrix = [[c(0.5, -0.5), c(0.5, -0.5)], [c(-0.5, 0.5), c(0.5, -0.5)]];
+
+pub struct StateVectorSimulatorCore {
+    qubit_num: usize,
+    /// qubit aはindexのa bit目に対応する
+    state: Vec<Complex64>,
+    rng: SmallRng,
+    measurement_record: MeasurementRecord,
+    detection_events: Vec<u8>,
+    observable_flips: Vec<u8>,
+}
+
+/// 状態ベクトルによるシミュレータ (非Clifford gateも扱えるが, qubit数はMAX_QUBIT_NUMまで)
+pub struct StateVectorSimulator {
+    core: StateVectorSimulatorCore,
+    dispatcher: Dispatcher,
+    measurement_count: usize,
+    detector_count: usize,
+    observable_count: usize,
+    /// ノイズのない回路でのdetectorとobservableの値 (回路が変わると計算し直す)
+    reference: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl StateVectorSimulator {
+    pub fn new(qubit_num: usize, rng: SmallRng) -> Self {
+        let operations = Vec::new();
+        StateVectorSimulator {
+            core: StateVectorSimulatorCore::new(qubit_num, rng),
+            dispatcher: Dispatcher::new(operations),
+            measurement_count: 0,
+            detector_count: 0,
+            observable_count: 0,
+            reference: None,
+        }
+    }
+
+    /// 現在の状態ベクトル (qubit aはindexのa bit目)
+    pub fn state(&self) -> &[Complex64] {
+        &self.core.state
+    }
+
+    /// X0*Z3*Y5のようなPauli積の期待値 (+1, -1, 固有状態でなければ0) を状態を変えずに求める
+    pub fn peek_observable(&self, product: &[(usize, Pauli)]) -> i8 {
+        let (sign, product) = combine_pauli_product(product).unwrap_or_else(|e| panic!("{}", e));
+        let applied = self.core.apply_pauli_product(&product, sign);
+        let expectation = self.core.expectation(&applied);
+        if expectation > 1.0 - EPSILON {
+            1
+        } else if expectation < -1.0 + EPSILON {
+            -1
+        } else {
+            0
+        }
+    }
+
+    /// オペレーションを追加する
+    fn push(&mut self, operation: Operation) {
+        self.reference = None;
+        self.dispatcher.push(operation);
+    }
+
+    /// 測定オペレーションを追加し, measurement recordでのindexを返す
+    fn push_measurement(&mut self, operation: Operation) -> usize {
+        self.push(operation);
+        self.measurement_count += 1;
+        self.measurement_count - 1
+    }
+
+    /// ノイズを除いた回路を|0...0>から実行し, detectorとobservableの参照値を求める
+    ///
+    /// 回路がT gateを含むことがあるので, CHPではなくこのシミュレータ自身で実行する
+    fn reference_sample(&self) -> (Vec<u8>, Vec<u8>) {
+        let mut core =
+            StateVectorSimulatorCore::new(self.core.qubit_num, SmallRng::seed_from_u64(0));
+        core.observable_flips = vec![0; self.observable_count];
+        for op in self
+            .dispatcher
+            .operations()
+            .iter()
+            .filter_map(Operation::noiseless)
+        {
+            core.execute(&op);
+        }
+        (core.detection_events, core.observable_flips)
+    }
+}
+
+impl StateVectorSimulatorCore {
+    fn new(qubit_num: usize, rng: SmallRng) -> Self {
+        assert!(
+            qubit_num <= MAX_QUBIT_NUM,
+            "StateVectorSimulator supports at most {} qubits",
+            MAX_QUBIT_NUM
+        );
+        let mut state = vec![ZERO; 1 << qubit_num];
+        state[0] = ONE;
+        StateVectorSimulatorCore {
+            qubit_num,
+            state,
+            rng,
+            measurement_record: MeasurementRecord::new(),
+            detection_events: Vec::new(),
+            observable_flips: Vec::new(),
+        }
+    }
+
+    /// rec[-k]のparity
+    fn parity(&self, lookback: &[usize]) -> u8 {
+        lookback
+            .iter()
+            .fold(0, |parity, &k| parity ^ self.measurement_record.lookback(k))
+    }
+
+    /// オペレーションを1つ実行する
+    fn execute(&mut self, op: &Operation) {
+        match op {
+            Operation::CX(a, b) => self.cx(*a, *b),
+            Operation::H(a) => self.h(*a),
+            Operation::Depolarizing(a, p) => self.depolarizing(*a, *p),
+            Operation::PauliChannel1(a, px, py, pz) => self.pauli_channel_1(*a, *px, *py, *pz),
+            Operation::Depolarizing2(a, b, p) => self.depolarizing_2(*a, *b, *p),
+            Operation::PauliChannel2(a, b, p) => self.pauli_channel_2(*a, *b, p),
+            Operation::MR(a, error_rate) => {
+                self.measurement_and_reset(*a, *error_rate);
+            }
+            Operation::M(a, error_rate) => {
+                self.measurement(*a, *error_rate);
+            }
+            Operation::MToZero(a) => self.measurement_to_zero(*a),
+            Operation::Feedback(pauli, lookback, a) => self.feedback(*pauli, *lookback, *a),
+            Operation::MX(a, error_rate) => {
+                self.measurement_x(*a, *error_rate);
+            }
+            Operation::MY(a, error_rate) => {
+                self.measurement_y(*a, *error_rate);
+            }
+            Operation::MPP(product, sign, error_rate) => {
+                self.measurement_pauli_product(product, *sign, *error_rate);
+            }
+            Operation::R(a) => self.reset_z(*a),
+            Operation::RX(a) => self.reset_x(*a),
+            Operation::RY(a) => self.reset_y(*a),
+            Operation::MRX(a, error_rate) => {
+                self.measurement_and_reset_x(*a, *error_rate);
+            }
+            Operation::MRY(a, error_rate) => {
+                self.measurement_and_reset_y(*a, *error_rate);
+            }
+            Operation::S(a) => self.s(*a),
+            Operation::X(a) => self.x(*a),
+            Operation::Z(a) => self.z(*a),
+            Operation::Y(a) => self.y(*a),
+            Operation::SDag(a) => self.s_dag(*a),
+            Operation::SqrtX(a) => self.sqrt_x(*a),
+            Operation::SqrtXDag(a) => self.sqrt_x_dag(*a),
+            Operation::SqrtY(a) => self.sqrt_y(*a),
+            Operation::SqrtYDag(a) => self.sqrt_y_dag(*a),
+            Operation::T(a) => self.t(*a),
+            Operation::TDag(a) => self.t_dag(*a),
+            Operation::CZ(a, b) => self.cz(*a, *b),
+            Operation::CY(a, b) => self.cy(*a, *b),
+            Operation::Swap(a, b) => self.swap(*a, *b),
+            Operation::ISwap(a, b) => self.iswap(*a, *b),
+            Operation::ISwapDag(a, b) => self.iswap_dag(*a, *b),
+            Operation::XCX(a, b) => self.xcx(*a, *b),
+            Operation::XCY(a, b) => self.xcy(*a, *b),
+            Operation::XCZ(a, b) => self.xcz(*a, *b),
+            Operation::YCX(a, b) => self.ycx(*a, *b),
+            Operation::YCY(a, b) => self.ycy(*a, *b),
+            Operation::YCZ(a, b) => self.ycz(*a, *b),
+            Operation::Detector(_, lookback) => self.detector(lookback),
+            Operation::ObservableInclude(observable, lookback) => {
+                self.observable_include(*observable, lookback)
+            }
+            Operation::Tick => (),
+            // bodyを展開せずにn回実行する
+            Operation::Repeat(n, body) => {
+                for _ in 0..*n {
+                    for op in body.iter() {
+                        self.execute(op);
+                    }
+                }
+            }
+        }
+    }
+
+    /// qubit aに2x2のユニタリを作用させる
+    fn apply(&mut self, a: usize, u: &Matrix) {
+        self.apply_controlled(None, a, u);
+    }
+
+    /// qubit controlが1のときだけqubit aに2x2のユニタリを作用させる
+    fn apply_controlled(&mut self, control: Option<usize>, a: usize, u: &Matrix) {
+        let bit = 1 << a;
+        let control = control.map_or(0, |control| 1 << control);
+        for i in 0..self.state.len() {
+            if i & bit != 0 || i & control != control {
+                continue;
+            }
+            let (s0, s1) = (self.state[i], self.state[i | bit]);
+            self.state[i] = u[0][0] * s0 + u[0][1] * s1;
+            self.state[i | bit] = u[1][0] * s0 + u[1][1] * s1;
+        }
+    }
+
+    /// qubit aが1になる確率
+    fn probability_one(&self, a: usize) -> f64 {
+        self.state
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| (i >> a) & 1 == 1)
+            .map(|(_, amplitude)| amplitude.norm_sqr())
+            .sum()
+    }
+
+    /// qubit aの測定結果がoutcomeになるように射影して規格化する
+    fn project(&mut self, a: usize, outcome: u8, probability: f64) {
+        let norm = probability.sqrt();
+        for (i, amplitude) in self.state.iter_mut().enumerate() {
+            if (i >> a) & 1 == outcome as usize {
+                *amplitude /= norm;
+            } else {
+                *amplitude = ZERO;
+            }
+        }
+    }
+
+    /// noiseless measurement in Z basis
+    fn measure_z(&mut self, a: usize) -> u8 {
+        let p1 = self.probability_one(a);
+        let outcome = if self.rng.gen::<f64>() < p1 { 1 } else { 0 };
+        let probability = if outcome == 1 { p1 } else { 1.0 - p1 };
+        self.project(a, outcome, probability);
+        outcome
+    }
+
+    /// (-1)^sign * Pauli積を作用させた状態 (同じqubitは1回だけ現れる)
+    fn apply_pauli_product(&self, product: &[(usize, Pauli)], sign: u8) -> Vec<Complex64> {
+        let (mut x_mask, mut z_mask, mut y_count) = (0, 0, 0);
+        for &(a, pauli) in product.iter() {
+            match pauli {
+                Pauli::I => (),
+                Pauli::X => x_mask ^= 1 << a,
+                Pauli::Z => z_mask ^= 1 << a,
+                Pauli::Y => {
+                    x_mask ^= 1 << a;
+                    z_mask ^= 1 << a;
+                    y_count += 1;
+                }
+            }
+        }
+        // Y = iXZ
+        let phase = I.powi(y_count + 2 * sign as i32);
+        let mut result = vec![ZERO; self.state.len()];
+        for (i, &amplitude) in self.state.iter().enumerate() {
+            let sign = if (i & z_mask).count_ones() % 2 == 1 {
+                -1.0
+            } else {
+                1.0
+            };
+            result[i ^ x_mask] = phase * sign * amplitude;
+        }
+        result
+    }
+
+    /// Pauli積の期待値 <ψ|P|ψ> (appliedはapply_pauli_productで求めたP|ψ>)
+    fn expectation(&self, applied: &[Complex64]) -> f64 {
+        self.state
+            .iter()
+            .zip(applied.iter())
+            .map(|(s, p)| (s.conj() * p).re)
+            .sum()
+    }
+
+    /// 測定エラーを加えた結果をmeasurement recordに追加する
+    fn record_measurement(&mut self, outcome: u8, error_rate: f32) -> u8 {
+        let outcome = if self.rng.gen::<f32>() < error_rate {
+            outcome ^ 1
+        } else {
+            outcome
+        };
+        self.measurement_record.push(outcome);
+        outcome
+    }
+
+    /// noiseless reset to |0>
+    fn reset_z(&mut self, a: usize) {
+        if self.measure_z(a) == 1 {
+            self.x(a);
+        }
+    }
+
+    /// apply single qubit Pauli operator
+    fn pauli(&mut self, a: usize, pauli: Pauli) {
+        match pauli {
+            Pauli::I => (),
+            Pauli::X => self.x(a),
+            Pauli::Y => self.y(a),
+            Pauli::Z => self.z(a),
+        }
+    }
+
+    /// T gate
+    fn t(&mut self, a: usize) {
+        self.apply(
+            a,
+            &[[ONE, ZERO], [ZERO, Complex64::from_polar(1.0, FRAC_PI_4)]],
+        );
+    }
+
+    /// T dagger gate
+    fn t_dag(&mut self, a: usize) {
+        self.apply(
+            a,
+            &[[ONE, ZERO], [ZERO, Complex64::from_polar(1.0, -FRAC_PI_4)]],
+        );
+    }
+}
+
+impl SimulatorCore for StateVectorSimulatorCore {
+    /// CNOT gate
+    fn cx(&mut self, a: usize, b: usize) {
+        self.apply_controlled(Some(a), b, &MATRIX_X);
+    }
+
+    /// Hadamard gate
+    fn h(&mut self, a: usize) {
+        self.apply(a, &MATRIX_H);
+    }
+
+    /// S gate (Phase gate)
+    fn s(&mut self, a: usize) {
+        self.apply(a, &MATRIX_S);
+    }
+
+    ///X gate
+    fn x(&mut self, a: usize) {
+        self.apply(a, &MATRIX_X);
+    }
+
+    /// Z gate
+    fn z(&mut self, a: usize) {
+        self.apply(a, &MATRIX_Z);
+    }
+
+    /// Y gate
+    fn y(&mut self, a: usize) {
+        self.apply(a, &MATRIX_Y);
+    }
+
+    /// S dagger gate
+    fn s_dag(&mut self, a: usize) {
+        self.apply(a, &MATRIX_S_DAG);
+    }
+
+    /// sqrt(X) gate
+    fn sqrt_x(&mut self, a: usize) {
+        self.apply(a, &MATRIX_SQRT_X);
+    }
+
+    /// sqrt(X) dagger gate
+    fn sqrt_x_dag(&mut self, a: usize) {
+        self.apply(a, &MATRIX_SQRT_X_DAG);
+    }
+
+    /// sqrt(Y) gate
+    fn sqrt_y(&mut self, a: usize) {
+        self.apply(a, &MATRIX_SQRT_Y);
+    }
+
+    /// sqrt(Y) dagger gate
+    fn sqrt_y_dag(&mut self, a: usize) {
+        self.apply(a, &MATRIX_SQRT_Y_DAG);
+    }
+
+    /// controlled Z gate
+    fn cz(&mut self, a: usize, b: usize) {
+        self.apply_controlled(Some(a), b, &MATRIX_Z);
+    }
+
+    /// controlled Y gate
+    fn cy(&mut self, a: usize, b: usize) {
+        self.apply_controlled(Some(a), b, &MATRIX_Y);
+    }
+
+    /// SWAP gate
+    fn swap(&mut self, a: usize, b: usize) {
+        let (bit_a, bit_b) = (1 << a, 1 << b);
+        for i in 0..self.state.len() {
+            if i & bit_a != 0 && i & bit_b == 0 {
+                self.state.swap(i, i ^ bit_a ^ bit_b);
+            }
+        }
+    }
+
+    /// iSWAP gate
+    fn iswap(&mut self, a: usize, b: usize) {
+        self.swap(a, b);
+        self.cz(a, b);
+        self.s(a);
+        self.s(b);
+    }
+
+    /// iSWAP dagger gate
+    fn iswap_dag(&mut self, a: usize, b: usize) {
+        self.s_dag(a);
+        self.s_dag(b);
+        self.cz(a, b);
+        self.swap(a, b);
+    }
+
+    /// X-controlled X gate
+    fn xcx(&mut self, a: usize, b: usize) {
+        self.h(a);
+        self.cx(a, b);
+        self.h(a);
+    }
+
+    /// X-controlled Y gate
+    fn xcy(&mut self, a: usize, b: usize) {
+        self.h(a);
+        self.cy(a, b);
+        self.h(a);
+    }
+
+    /// X-controlled Z gate
+    fn xcz(&mut self, a: usize, b: usize) {
+        self.cx(b, a);
+    }
+
+    /// Y-controlled X gate
+    fn ycx(&mut self, a: usize, b: usize) {
+        // sqrt(X)はYをZに移す
+        self.sqrt_x(a);
+        self.cx(a, b);
+        self.sqrt_x_dag(a);
+    }
+
+    /// Y-controlled Y gate
+    fn ycy(&mut self, a: usize, b: usize) {
+        self.sqrt_x(a);
+        self.cy(a, b);
+        self.sqrt_x_dag(a);
+    }
+
+    /// Y-controlled Z gate
+    fn ycz(&mut self, a: usize, b: usize) {
+        self.cy(b, a);
+    }
+
+    /// measurement
+    fn measurement(&mut self, a: usize, error_rate: f32) -> u8 {
+        let outcome = self.measure_z(a);
+        self.record_measurement(outcome, error_rate)
+    }
+
+    /// measurement
+    fn measurement_to_zero(&mut self, a: usize) {
+        // 結果がランダムのときは必ず0にセット
+        let p1 = self.probability_one(a);
+        if EPSILON < p1 && p1 < 1.0 - EPSILON {
+            self.project(a, 0, 1.0 - p1);
+        }
+    }
+
+    /// measurement and reset
+    fn measurement_and_reset(&mut self, a: usize, error_rate: f32) -> u8 {
+        let outcome = self.measure_z(a);
+        let recorded = self.record_measurement(outcome, error_rate);
+        // 測定エラーに関係なく, 実際の状態に応じて|0>に戻す
+        if outcome == 1 {
+            self.x(a);
+        }
+        recorded
+    }
+
+    /// measurement of (-1)^sign * Pauli product
+    fn measurement_pauli_product(
+        &mut self,
+        product: &[(usize, Pauli)],
+        sign: u8,
+        error_rate: f32,
+    ) -> u8 {
+        let applied = self.apply_pauli_product(product, sign);
+        // <P> = 1 - 2 p1
+        let expectation = self.expectation(&applied);
+        let p1 = ((1.0 - expectation) / 2.0).clamp(0.0, 1.0);
+        let outcome = if self.rng.gen::<f64>() < p1 { 1 } else { 0 };
+
+        // (1 ± P)/2で射影する
+        let (sign, probability) = if outcome == 1 {
+            (-1.0, p1)
+        } else {
+            (1.0, 1.0 - p1)
+        };
+        let norm = 2.0 * probability.sqrt();
+        for (s, p) in self.state.iter_mut().zip(applied) {
+            *s = (*s + sign * p) / norm;
+        }
+
+        self.record_measurement(outcome, error_rate)
+    }
+
+    /// measurement in X basis
+    fn measurement_x(&mut self, a: usize, error_rate: f32) -> u8 {
+        self.h(a);
+        let outcome = self.measurement(a, error_rate);
+        self.h(a);
+        outcome
+    }
+
+    /// measurement in Y basis
+    fn measurement_y(&mut self, a: usize, error_rate: f32) -> u8 {
+        // Y固有状態をZ固有状態に移してから測定する
+        self.sqrt_x(a);
+        let outcome = self.measurement(a, error_rate);
+        self.sqrt_x_dag(a);
+        outcome
+    }
+
+    /// reset to |+>
+    fn reset_x(&mut self, a: usize) {
+        self.reset_z(a);
+        self.h(a);
+    }
+
+    /// reset to |+i>
+    fn reset_y(&mut self, a: usize) {
+        self.reset_z(a);
+        self.sqrt_x_dag(a);
+    }
+
+    /// measurement and reset in X basis
+    fn measurement_and_reset_x(&mut self, a: usize, error_rate: f32) -> u8 {
+        self.h(a);
+        let outcome = self.measurement_and_reset(a, error_rate);
+        self.h(a);
+        outcome
+    }
+
+    /// measurement and reset in Y basis
+    fn measurement_and_reset_y(&mut self, a: usize, error_rate: f32) -> u8 {
+        self.sqrt_x(a);
+        let outcome = self.measurement_and_reset(a, error_rate);
+        self.sqrt_x_dag(a);
+        outcome
+    }
+
+    /// classically controlled Pauli (測定エラーを含む記録された結果で決まる)
+    fn feedback(&mut self, pauli: Pauli, lookback: usize, a: usize) {
+        if self.measurement_record.lookback(lookback) == 1 {
+            self.pauli(a, pauli);
+        }
+    }
+
+    /// insert depolarizing noise
+    fn depolarizing(&mut self, a: usize, p: f32) {
+        if self.rng.gen::<f32>() < p {
+            match self.rng.gen_range(0..3) {
+                0 => self.z(a),
+                1 => self.x(a),
+                _ => self.y(a),
+            }
+        }
+    }
+
+    /// insert Pauli channel noise
+    fn pauli_channel_1(&mut self, a: usize, px: f32, py: f32, pz: f32) {
+        match self.rng.gen::<f32>() {
+            x if x < px => self.x(a),
+            x if x < px + py => self.y(a),
+            x if x < px + py + pz => self.z(a),
+            _ => (),
+        }
+    }
+
+    /// insert two qubit depolarizing noise
+    fn depolarizing_2(&mut self, a: usize, b: usize, p: f32) {
+        self.pauli_channel_2(a, b, &[p / 15.0; 15]);
+    }
+
+    /// insert two qubit Pauli channel noise
+    fn pauli_channel_2(&mut self, a: usize, b: usize, p: &[f32; 15]) {
+        const PAULI: [Pauli; 4] = [Pauli::I, Pauli::X, Pauli::Y, Pauli::Z];
+
+        let r = self.rng.gen::<f32>();
+        let mut cumulative = 0.0;
+        // IX, IY, IZ, XI, ..., ZZの順
+        for (i, &p_i) in p.iter().enumerate() {
+            cumulative += p_i;
+            if r < cumulative {
+                self.pauli(a, PAULI[(i + 1) >> 2]);
+                self.pauli(b, PAULI[(i + 1) & 3]);
+                break;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state
+            .iter_mut()
+            .for_each(|amplitude| *amplitude = ZERO);
+        self.state[0] = ONE;
+        self.measurement_record.clear();
+        self.detection_events.clear();
+        self.observable_flips.iter_mut().for_each(|flip| *flip = 0);
+    }
+
+    fn detector(&mut self, lookback: &[usize]) {
+        let parity = self.parity(lookback);
+        self.detection_events.push(parity);
+    }
+
+    fn observable_include(&mut self, observable: usize, lookback: &[usize]) {
+        if self.observable_flips.len() <= observable {
+            self.observable_flips.resize(observable + 1, 0);
+        }
+        self.observable_flips[observable] ^= self.parity(lookback);
+    }
+}
+
+impl SimulatorInterface for StateVectorSimulator {
+    /// add CNOT gate
+    fn add_cx(&mut self, a: usize, b: usize) {
+        self.push(Operation::CX(a, b));
+    }
+
+    /// add Hadamard gate
+    fn add_h(&mut self, a: usize) {
+        self.push(Operation::H(a));
+    }
+
+    /// add S gate (Phase gate)
+    fn add_s(&mut self, a: usize) {
+        self.push(Operation::S(a));
+    }
+
+    /// add X gate
+    fn add_x(&mut self, a: usize) {
+        self.push(Operation::X(a))
+    }
+
+    /// add Z gate
+    fn add_z(&mut self, a: usize) {
+        self.push(Operation::Z(a))
+    }
+
+    /// add Y gate
+    fn add_y(&mut self, a: usize) {
+        self.push(Operation::Y(a));
+    }
+
+    /// add S dagger gate
+    fn add_s_dag(&mut self, a: usize) {
+        self.push(Operation::SDag(a));
+    }
+
+    /// add sqrt(X) gate
+    fn add_sqrt_x(&mut self, a: usize) {
+        self.push(Operation::SqrtX(a));
+    }
+
+    /// add sqrt(X) dagger gate
+    fn add_sqrt_x_dag(&mut self, a: usize) {
+        self.push(Operation::SqrtXDag(a));
+    }
+
+    /// add sqrt(Y) gate
+    fn add_sqrt_y(&mut self, a: usize) {
+        self.push(Operation::SqrtY(a));
+    }
+
+    /// add sqrt(Y) dagger gate
+    fn add_sqrt_y_dag(&mut self, a: usize) {
+        self.push(Operation::SqrtYDag(a));
+    }
+
+    /// add controlled Z gate
+    fn add_cz(&mut self, a: usize, b: usize) {
+        self.push(Operation::CZ(a, b));
+    }
+
+    /// add controlled Y gate
+    fn add_cy(&mut self, a: usize, b: usize) {
+        self.push(Operation::CY(a, b));
+    }
+
+    /// add SWAP gate
+    fn add_swap(&mut self, a: usize, b: usize) {
+        self.push(Operation::Swap(a, b));
+    }
+
+    /// add iSWAP gate
+    fn add_iswap(&mut self, a: usize, b: usize) {
+        self.push(Operation::ISwap(a, b));
+    }
+
+    /// add iSWAP dagger gate
+    fn add_iswap_dag(&mut self, a: usize, b: usize) {
+        self.push(Operation::ISwapDag(a, b));
+    }
+
+    /// add X-controlled X gate
+    fn add_xcx(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCX(a, b));
+    }
+
+    /// add X-controlled Y gate
+    fn add_xcy(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCY(a, b));
+    }
+
+    /// add X-controlled Z gate
+    fn add_xcz(&mut self, a: usize, b: usize) {
+        self.push(Operation::XCZ(a, b));
+    }
+
+    /// add Y-controlled X gate
+    fn add_ycx(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCX(a, b));
+    }
+
+    /// add Y-controlled Y gate
+    fn add_ycy(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCY(a, b));
+    }
+
+    /// add Y-controlled Z gate
+    fn add_ycz(&mut self, a: usize, b: usize) {
+        self.push(Operation::YCZ(a, b));
+    }
+
+    /// add T gate
+    fn add_t(&mut self, a: usize) {
+        self.push(Operation::T(a));
+    }
+
+    /// add T dagger gate
+    fn add_t_dag(&mut self, a: usize) {
+        self.push(Operation::TDag(a));
+    }
+
+    /// add measurement
+    fn add_measurement(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::M(a, error_rate))
+    }
+
+    fn add_measurement_to_zero(&mut self, a: usize) {
+        self.push(Operation::MToZero(a));
+    }
+
+    /// add measurement_and_reset
+    fn add_measurement_and_reset(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MR(a, error_rate))
+    }
+
+    /// add measurement of Pauli product
+    fn add_measurement_signed_pauli_product(
+        &mut self,
+        product: Vec<(usize, Pauli)>,
+        sign: u8,
+        error_rate: f32,
+    ) -> usize {
+        let operation = Operation::measurement_pauli_product(&product, sign, error_rate)
+            .unwrap_or_else(|e| panic!("{}", e));
+        self.push_measurement(operation)
+    }
+
+    /// add measurement in X basis
+    fn add_measurement_x(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MX(a, error_rate))
+    }
+
+    /// add measurement in Y basis
+    fn add_measurement_y(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MY(a, error_rate))
+    }
+
+    /// add reset to |0>
+    fn add_reset_z(&mut self, a: usize) {
+        self.push(Operation::R(a));
+    }
+
+    /// add reset to |+>
+    fn add_reset_x(&mut self, a: usize) {
+        self.push(Operation::RX(a));
+    }
+
+    /// add reset to |+i>
+    fn add_reset_y(&mut self, a: usize) {
+        self.push(Operation::RY(a));
+    }
+
+    /// add measurement_and_reset in X basis
+    fn add_measurement_and_reset_x(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MRX(a, error_rate))
+    }
+
+    /// add measurement_and_reset in Y basis
+    fn add_measurement_and_reset_y(&mut self, a: usize, error_rate: f32) -> usize {
+        self.push_measurement(Operation::MRY(a, error_rate))
+    }
+
+    /// add Pauli controlled by rec[-k]
+    fn add_feedback(&mut self, pauli: Pauli, lookback: usize, a: usize) {
+        assert!(
+            (1..=self.measurement_count).contains(&lookback),
+            "feedback refers to a measurement that does not exist"
+        );
+        self.push(Operation::Feedback(pauli, lookback, a));
+    }
+
+    fn add_noise(&mut self, a: usize, noise_type: NoiseType) {
+        match noise_type {
+            NoiseType::Depolarizing(p) => self.push(Operation::Depolarizing(a, p)),
+            NoiseType::PauliChannel1(px, py, pz) => {
+                self.push(Operation::PauliChannel1(a, px, py, pz))
+            }
+            _ => panic!("{:?} is two qubit noise, use add_noise2", noise_type),
+        }
+    }
+
+    fn add_noise2(&mut self, a: usize, b: usize, noise_type: NoiseType) {
+        match noise_type {
+            NoiseType::Depolarizing2(p) => self.push(Operation::Depolarizing2(a, b, p)),
+            NoiseType::PauliChannel2(p) => self.push(Operation::PauliChannel2(a, b, p)),
+            _ => panic!("{:?} is single qubit noise, use add_noise", noise_type),
+        }
+    }
+
+    fn add_tick(&mut self) {
+        self.push(Operation::Tick);
+    }
+
+    fn repeat_from(&mut self, start: usize, repetitions: usize) {
+        self.reference = None;
+        let body = self.dispatcher.repeat_from(start, repetitions);
+        let (measurements, detectors) = (body.measurement_count(), body.detector_count());
+        self.measurement_count = self.measurement_count - measurements + repetitions * measurements;
+        self.detector_count = self.detector_count - detectors + repetitions * detectors;
+    }
+
+    fn operation_count(&self) -> usize {
+        self.dispatcher.operations().len()
+    }
+
+    fn insert_noise_model(&mut self, model: &NoiseModel, data_qubit: &[usize], start: usize) {
+        let operations = circuit_noise::apply_noise_model(
+            &self.dispatcher.operations()[start..],
+            model,
+            data_qubit,
+        );
+        self.reference = None;
+        self.dispatcher.replace_from(start, operations);
+    }
+
+    /// Reset state vector
+    fn reset(&mut self) {
+        self.core.reset();
+    }
+
+    /// measurement direct
+    fn measurement(&mut self, a: usize, error_rate: f32) -> u8 {
+        self.core.measurement(a, error_rate)
+    }
+
+    fn measurement_count(&self) -> usize {
+        self.measurement_count
+    }
+
+    fn measurement_record(&self) -> &MeasurementRecord {
+        &self.core.measurement_record
+    }
+
+    fn add_detector(&mut self, coords: Vec<f32>, lookback: Vec<usize>) -> usize {
+        debug_assert!(
+            lookback
+                .iter()
+                .all(|k| (1..=self.measurement_count).contains(k)),
+            "detector refers to a measurement that does not exist"
+        );
+        self.push(Operation::Detector(coords, lookback));
+        self.detector_count += 1;
+        self.detector_count - 1
+    }
+
+    fn add_observable_include(&mut self, observable: usize, lookback: Vec<usize>) {
+        self.observable_count = self.observable_count.max(observable + 1);
+        self.push(Operation::ObservableInclude(observable, lookback));
+    }
+
+    fn detector_count(&self) -> usize {
+        self.detector_count
+    }
+
+    fn detection_events(&self) -> &[u8] {
+        &self.core.detection_events
+    }
+
+    fn circuit(&self) -> &Circuit {
+        self.dispatcher.circuit()
+    }
+
+    fn observable_flips(&self) -> &[u8] {
+        &self.core.observable_flips
+    }
+
+    /// run circuit
+    fn run(&mut self) {
+        if self.reference.is_none() && self.detector_count + self.observable_count > 0 {
+            self.reference = Some(self.reference_sample());
+        }
+
+        let Self {
+            core,
+            dispatcher,
+            observable_count,
+            reference,
+            ..
+        } = self;
+        core.measurement_record.clear();
+        core.detection_events.clear();
+        core.observable_flips = vec![0; *observable_count];
+
+        for op in dispatcher.operations().iter() {
+            core.execute(op);
+        }
+
+        // ノイズのない回路との差がdetection event, observable flipになる
+        if let Some((detectors, observables)) = reference {
+            for (event, reference) in core.detection_events.iter_mut().zip(detectors.iter()) {
+                *event ^= reference;
+            }
+            for (flip, reference) in core.observable_flips.iter_mut().zip(observables.iter()) {
+                *flip ^= reference;
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use super::{
+    pauli::{Pauli, PauliString},
+    stabilizer_tableau::StabilizerTableau,
+    Operation,
+};
+
+/// Clifford operation U represented by the images U X_a U^dag and U Z_a U^dag
+///
+/// 状態ではなくユニタリを表し, 大域位相を除いてUを一意に決める
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tableau {
+    qubit_num: usize,
+    x_images: Vec<PauliString>,
+    z_images: Vec<PauliString>,
+}
+
+impl Tableau {
+    /// identity on qubit_num qubits
+    pub fn identity(qubit_num: usize) -> Self {
+        Self::from_stabilizer_tableau(&StabilizerTableau::new(qubit_num))
+    }
+
+    /// Clifford circuit to tableau (ノイズとdetectorなどの注釈は無視する)
+    ///
+    /// return Err if the circuit contains measurement, reset or non-Clifford gate
+    pub fn from_operations(qubit_num: usize, operations: &[Operation]) -> Result<Self, String> {
+        // |0...0>のtableauのdestabilizerはX_a, stabilizerはZ_aなので,
+        // gateを作用させた後の各行がX_a, Z_aの像になる
+        let mut tableau = StabilizerTableau::new(qubit_num);
+        apply_operations(&mut tableau, operations)?;
+        Ok(Self::from_stabilizer_tableau(&tableau))
+    }
+
+    fn from_stabilizer_tableau(tableau: &StabilizerTableau) -> Self {
+        Tableau {
+            qubit_num: tableau.qubit_num(),
+            x_images: tableau.destabilizers(),
+            z_images: tableau.stabilizers(),
+        }
+    }
+
+    /// return the number of qubits
+    pub fn qubit_num(&self) -> usize {
+        self.qubit_num
+    }
+
+    /// U X_a U^dag
+    pub fn x_image(&self, a: usize) -> &PauliString {
+        &self.x_images[a]
+    }
+
+    /// U Z_a U^dag
+    pub fn z_image(&self, a: usize) -> &PauliString {
+        &self.z_images[a]
+    }
+
+    /// U P U^dag
+    pub fn conjugate(&self, pauli: &PauliString) -> PauliString {
+        assert_eq!(pauli.len(), self.qubit_num, "qubit number mismatch");
+        // i^phaseを掛けたPauli積として計算する
+        let mut phase = 2 * pauli.sign;
+        let mut result = PauliString::identity(self.qubit_num);
+        for (a, &p) in pauli.paulis.iter().enumerate() {
+            let images: &[&PauliString] = match p {
+                Pauli::I => &[],
+                Pauli::X => &[&self.x_images[a]],
+                Pauli::Z => &[&self.z_images[a]],
+                // Y = iXZ
+                Pauli::Y => {
+                    phase += 1;
+                    &[&self.x_images[a], &self.z_images[a]]
+                }
+            };
+            for image in images {
+                phase = (phase + multiply(&mut result, image)) % 4;
+            }
+        }
+        debug_assert_eq!(
+            phase % 2,
+            0,
+            "conjugation of a Hermitian Pauli must be Hermitian"
+        );
+        result.sign = (phase / 2) % 2;
+        result
+    }
+
+    /// self, nextの順に作用させるClifford operation
+    pub fn then(&self, next: &Tableau) -> Tableau {
+        assert_eq!(self.qubit_num, next.qubit_num, "qubit number mismatch");
+        Tableau {
+            qubit_num: self.qubit_num,
+            x_images: self.x_images.iter().map(|p| next.conjugate(p)).collect(),
+            z_images: self.z_images.iter().map(|p| next.conjugate(p)).collect(),
+        }
+    }
+
+    /// U^dag
+    pub fn inverse(&self) -> Tableau {
+        // U^dag P Uは, U Z_aと反可換ならX_a, U X_aと反可換ならZ_aを含む
+        let preimage = |pauli: &PauliString| PauliString {
+            sign: 0,
+            paulis: (0..self.qubit_num)
+                .map(|a| {
+                    Pauli::from_bits(
+                        anticommutes(pauli, &self.z_images[a]) as u8,
+                        anticommutes(pauli, &self.x_images[a]) as u8,
+                    )
+                })
+                .collect(),
+        };
+        let mut inverse = Tableau {
+            qubit_num: self.qubit_num,
+            x_images: (0..self.qubit_num)
+                .map(|a| preimage(&single(self.qubit_num, a, Pauli::X)))
+                .collect(),
+            z_images: (0..self.qubit_num)
+                .map(|a| preimage(&single(self.qubit_num, a, Pauli::Z)))
+                .collect(),
+        };
+
+        // U (U^dag P U) U^dag = Pとなるように符号を決める
+        for a in 0..self.qubit_num {
+            inverse.x_images[a].sign = self.conjugate(&inverse.x_images[a]).sign;
+            inverse.z_images[a].sign = self.conjugate(&inverse.z_images[a]).sign;
+        }
+        inverse
+    }
+}
+
+/// 2つのノイズのないClifford回路が大域位相を除いて同じユニタリを実装するか
+///
+/// return Err if either circuit contains measurement, reset or non-Clifford gate
+pub fn circuits_equivalent(a: &[Operation], b: &[Operation]) -> Result<bool, String> {
+    let qubit_num = a
+        .iter()
+        .chain(b.iter())
+        .flat_map(|op| op.qubits())
+        .max()
+        .map_or(0, |a| a + 1);
+    Ok(Tableau::from_operations(qubit_num, a)? == Tableau::from_operations(qubit_num, b)?)
+}
+
+/// ノイズのないClifford回路をtableauに作用させる (REPEATブロックは繰り返し作用させる)
+fn apply_operations(
+    tableau: &mut StabilizerTableau,
+    operations: &[Operation],
+) -> Result<(), String> {
+    let qubit_num = tableau.qubit_num();
+    for op in operations.iter().filter_map(Operation::noiseless) {
+        if let Some(&a) = op.qubits().iter().find(|&&a| a >= qubit_num) {
+            return Err(format!("{:?} acts on qubit {} out of range", op, a));
+        }
+        match op {
+            Operation::Detector(_, _) | Operation::ObservableInclude(_, _) | Operation::Tick => (),
+            Operation::Repeat(n, ref body) => {
+                for _ in 0..n {
+                    apply_operations(tableau, body.operations())?;
+                }
+            }
+            ref gate if is_clifford_gate(gate) => match gate.qubits()[..] {
+                [a] => apply_gate(tableau, gate, a, a),
+                [a, b] => apply_gate(tableau, gate, a, b),
+                _ => unreachable!(),
+            },
+            _ => return Err(format!("{:?} is not a unitary Clifford operation", op)),
+        }
+    }
+    Ok(())
+}
+
+fn is_clifford_gate(op: &Operation) -> bool {
+    !matches!(
+        op,
+        Operation::M(_, _)
+            | Operation::MR(_, _)
+            | Operation::MX(_, _)
+            | Operation::MY(_, _)
+            | Operation::R(_)
+            | Operation::RX(_)
+            | Operation::RY(_)
+            | Operation::MRX(_, _)
+            | Operation::MRY(_, _)
+            | Operation::MPP(_, _, _)
+            | Operation::MToZero(_)
+            | Operation::Feedback(_, _, _)
+            | Operation::T(_)
+            | Operation::TDag(_)
+            | Operation::Depolarizing(_, _)
+            | Operation::PauliChannel1(_, _, _, _)
+            | Operation::Depolarizing2(_, _, _)
+            | Operation::PauliChannel2(_, _, _)
+            | Operation::Detector(_, _)
+            | Operation::ObservableInclude(_, _)
+            | Operation::Tick
+            | Operation::Repeat(_, _)
+    )
+}
+
+/// gateをtableauのqubit a (, b)に作用させる
+pub(crate) fn apply_gate(tableau: &mut StabilizerTableau, gate: &Operation, a: usize, b: usize) {
+    match gate {
+        Operation::H(_) => tableau.h(a),
+        Operation::S(_) => tableau.s(a),
+        Operation::X(_) => tableau.x(a),
+        Operation::Z(_) => tableau.z(a),
+        Operation::Y(_) => tableau.y(a),
+        Operation::SDag(_) => tableau.s_dag(a),
+        Operation::SqrtX(_) => tableau.sqrt_x(a),
+        Operation::SqrtXDag(_) => tableau.sqrt_x_dag(a),
+        Operation::SqrtY(_) => tableau.sqrt_y(a),
+        Operation::SqrtYDag(_) => tableau.sqrt_y_dag(a),
+        Operation::CX(_, _) => tableau.cx(a, b),
+        Operation::CZ(_, _) => tableau.cz(a, b),
+        Operation::CY(_, _) => tableau.cy(a, b),
+        Operation::Swap(_, _) => tableau.swap(a, b),
+        Operation::ISwap(_, _) => tableau.iswap(a, b),
+        Operation::ISwapDag(_, _) => tableau.iswap_dag(a, b),
+        Operation::XCX(_, _) => tableau.xcx(a, b),
+        Operation::XCY(_, _) => tableau.xcy(a, b),
+        Operation::XCZ(_, _) => tableau.xcz(a, b),
+        Operation::YCX(_, _) => tableau.ycx(a, b),
+        Operation::YCY(_, _) => tableau.ycy(a, b),
+        Operation::YCZ(_, _) => tableau.ycz(a, b),
+        _ => unreachable!("{:?} is not a Clifford gate", gate),
+    }
+}
+
+/// P_a on qubit_num qubits
+fn single(qubit_num: usize, a: usize, pauli: Pauli) -> PauliString {
+    let mut result = PauliString::identity(qubit_num);
+    result.paulis[a] = pauli;
+    result
+}
+
+fn anticommutes(p: &PauliString, q: &PauliString) -> bool {
+    p.paulis
+        .iter()
+        .zip(q.paulis.iter())
+        .filter(|&(&p, &q)| p != Pauli::I && q != Pauli::I && p != q)
+        .count()
+        % 2
+        == 1
+}
+
+/// lhsに右からrhsを掛け, 生じた位相 (rhsの符号を含む) をiの冪で返す (lhsの符号は変えない)
+fn multiply(lhs: &mut PauliString, rhs: &PauliString) -> u8 {
+    let mut phase = 2 * rhs.sign;
+    for (p, &q) in lhs.paulis.iter_mut().zip(rhs.paulis.iter()) {
+        let (x1, z1) = p.to_bits();
+        let (x2, z2) = q.to_bits();
+        // XY = iZ, YZ = iX, ZX = iY (逆順は-i)
+        phase += match (*p, q) {
+            (Pauli::X, Pauli::Y) | (Pauli::Y, Pauli::Z) | (Pauli::Z, Pauli::X) => 1,
+            (Pauli::Y, Pauli::X) | (Pauli::Z, Pauli::Y) | (Pauli::X, Pauli::Z) => 3,
+            _ => 0,
+        };
+        phase %= 4;
+        *p = Pauli::from_bits(x1 ^ x2, z1 ^ z2);
+    }
+    phase % 4
+}
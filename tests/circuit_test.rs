@@ -0,0 +1,239 @@
+use rand::{rngs::SmallRng, SeedableRng};
+
+use clifford::circuit::Circuit;
+use clifford::noise::noise_model::NoiseModel;
+use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+use clifford::simulator::{
+    chp_simulator::CHPSimulator, frame_simulator::FrameSimulator, pauli::Pauli,
+    state_vector_simulator::StateVectorSimulator, Operation, SimulatorInterface,
+};
+
+/// GHZ状態を作ってX parityとZを測定する回路
+fn ghz(qubit_num: usize) -> Circuit {
+    let mut circuit = Circuit::new();
+    circuit.push(Operation::H(0));
+    for a in 1..qubit_num {
+        circuit.push(Operation::CX(a - 1, a));
+    }
+    let product = (0..qubit_num).map(|a| (a, Pauli::X)).collect();
+    circuit.push(Operation::MPP(product, 0, 0.0));
+    circuit.push(Operation::Depolarizing(0, 0.0));
+    for a in 0..qubit_num {
+        circuit.push(Operation::M(a, 0.0));
+    }
+    circuit.push(Operation::Detector(vec![], vec![1, 2]));
+    circuit.push(Operation::ObservableInclude(1, vec![1, 4]));
+    circuit
+}
+
+#[test]
+fn circuit_statistics() {
+    let circuit = ghz(4);
+    assert_eq!(circuit.len(), 12);
+    assert_eq!(circuit.qubit_count(), 4);
+    assert_eq!(circuit.measurement_count(), 5);
+    assert_eq!(circuit.detector_count(), 1);
+    assert_eq!(circuit.observable_count(), 2);
+    // H, CX x 3, MPP, M (ノイズと注釈は深さに数えない)
+    assert_eq!(circuit.depth(), 6);
+
+    let counts = circuit.operation_counts();
+    assert_eq!(counts["CX"], 3);
+    assert_eq!(counts["M"], 4);
+    assert_eq!(counts["DEPOLARIZE1"], 1);
+    assert!(!counts.contains_key("CZ"));
+
+    // slice, repeat, append
+    let prefix = circuit.slice(0..4);
+    assert_eq!(prefix.depth(), 4);
+    assert_eq!(prefix.measurement_count(), 0);
+    let repeated = circuit.repeat(3);
+    assert_eq!(repeated.len(), 36);
+    assert_eq!(repeated.measurement_count(), 15);
+    let mut appended = prefix.clone();
+    appended.append(&circuit.slice(4..circuit.len()));
+    assert_eq!(
+        format!("{:?}", appended.operations()),
+        format!("{:?}", circuit.operations())
+    );
+    let collected: Circuit = circuit
+        .iter()
+        .filter(|op| !op.is_noise())
+        .cloned()
+        .collect();
+    assert_eq!(collected.len(), 11);
+    assert!(Circuit::new().is_empty());
+}
+
+#[test]
+fn same_circuit_on_every_backend() {
+    let circuit = ghz(4);
+    let mut sims: Vec<Box<dyn SimulatorInterface>> = vec![
+        Box::new(CHPSimulator::new(4, SmallRng::seed_from_u64(0))),
+        Box::new(FrameSimulator::new(4, SmallRng::seed_from_u64(0))),
+        Box::new(StateVectorSimulator::new(4, SmallRng::seed_from_u64(0))),
+    ];
+
+    for sim in sims.iter_mut() {
+        sim.add_circuit(&circuit);
+        assert_eq!(sim.circuit().len(), circuit.len());
+        assert_eq!(sim.measurement_count(), 5);
+        assert_eq!(sim.detector_count(), 1);
+
+        for _ in 0..20 {
+            sim.reset();
+            sim.run();
+            let bits = sim.measurement_record().bits();
+            // XXXX = +1, Zの結果は全て一致する
+            assert_eq!(bits[0], 0);
+            assert!(bits[1..].iter().all(|&bit| bit == bits[1]));
+            assert_eq!(sim.detection_events(), &[0]);
+            assert_eq!(sim.observable_flips(), &[0, 0]);
+        }
+    }
+}
+
+#[test]
+fn surface_code_circuit_is_reusable_across_threads() {
+    let distance = 3;
+    let mut code = RotatedSurfaceCode::new_with_noise_model(
+        distance,
+        2,
+        NoiseModel::Phenomenological(0.0, 0.0),
+        0,
+    );
+    code.initialize();
+    code.syndrome_measurement();
+    let circuit = code.circuit().clone();
+    assert!(circuit.detector_count() > 0);
+
+    std::thread::scope(|scope| {
+        for seed in 0..4 {
+            let circuit = &circuit;
+            scope.spawn(move || {
+                let mut sim =
+                    CHPSimulator::new(circuit.qubit_count(), SmallRng::seed_from_u64(seed));
+                sim.add_circuit(circuit);
+                assert_eq!(sim.detector_count(), circuit.detector_count());
+                for _ in 0..5 {
+                    sim.reset();
+                    sim.run();
+                    assert!(sim.detection_events().iter().all(|&event| event == 0));
+    assert_eq!(sim.observable_flips(), [0]);
+                }
+            });
+        }
+    });
+}
+
+/// 3 qubitの繰り返し符号 (data: 0, 2, 4, ancilla: 1, 3) の1roundのsyndrome測定
+fn repetition_code_round(p: f32) -> Circuit {
+    let mut round = Circuit::new();
+    for a in [0, 2, 4] {
+        round.push(Operation::Depolarizing(a, p));
+    }
+    round.push(Operation::Tick);
+    for (ancilla, data) in [(1, 0), (1, 2), (3, 2), (3, 4)] {
+        round.push(Operation::CX(data, ancilla));
+    }
+    round.push(Operation::Tick);
+    round.push(Operation::MR(1, p));
+    round.push(Operation::MR(3, p));
+    round.push(Operation::Detector(vec![0.0], vec![2, 4]));
+    round.push(Operation::Detector(vec![1.0], vec![1, 3]));
+    round
+}
+
+#[test]
+fn repeat_block_runs_like_unrolled_circuit() {
+    let round = 50;
+    let mut circuit = Circuit::new();
+    circuit.push(Operation::MR(1, 0.0));
+    circuit.push(Operation::MR(3, 0.0));
+    circuit.push(Operation::Repeat(round, repetition_code_round(0.02)));
+    circuit.push(Operation::M(0, 0.0));
+    circuit.push(Operation::ObservableInclude(0, vec![1]));
+
+    let flat = circuit.flattened();
+    assert_eq!(circuit.len(), 5);
+    assert_eq!(flat.len(), 4 + round * 13);
+    assert_eq!(circuit.measurement_count(), 3 + 2 * round);
+    assert_eq!(circuit.detector_count(), 2 * round);
+    assert_eq!(circuit.observable_count(), 1);
+    assert_eq!(circuit.qubit_count(), 5);
+    assert_eq!(circuit.operation_counts(), flat.operation_counts());
+    assert_eq!(circuit.operation_counts()["TICK"], 2 * round);
+    assert_eq!(circuit.depth(), flat.depth());
+
+    // 同じseedなら展開した回路と全く同じ結果になる
+    let sims = |seed| -> Vec<Box<dyn SimulatorInterface>> {
+        vec![
+            Box::new(CHPSimulator::new(5, SmallRng::seed_from_u64(seed))),
+            Box::new(FrameSimulator::new(5, SmallRng::seed_from_u64(seed))),
+        ]
+    };
+    for (mut repeated, mut unrolled) in sims(0).into_iter().zip(sims(0)) {
+        repeated.add_circuit(&circuit);
+        unrolled.add_circuit(&flat);
+        assert_eq!(repeated.circuit().len(), 5);
+        assert_eq!(repeated.measurement_count(), unrolled.measurement_count());
+        assert_eq!(repeated.detector_count(), unrolled.detector_count());
+
+        let mut events = 0;
+        for _ in 0..20 {
+            for sim in [&mut repeated, &mut unrolled] {
+                sim.reset();
+                sim.run();
+            }
+            assert_eq!(
+                repeated.measurement_record().bits(),
+                unrolled.measurement_record().bits()
+            );
+            assert_eq!(repeated.detection_events(), unrolled.detection_events());
+            assert_eq!(repeated.observable_flips(), unrolled.observable_flips());
+            events += repeated
+                .detection_events()
+                .iter()
+                .filter(|&&e| e == 1)
+                .count();
+        }
+        assert!(events > 0);
+    }
+}
+
+#[test]
+fn surface_code_rounds_are_stored_as_repeat_block() {
+    let distance = 5;
+    let mut codes = [10, 1000].map(|round| {
+        let mut code =
+            RotatedSurfaceCode::new_with_noise_model(distance, round, NoiseModel::SD6(0.001), 0);
+        code.syndrome_measurement();
+        code
+    });
+    // roundを増やしても回路の長さは変わらない
+    assert_eq!(codes[0].circuit().len(), codes[1].circuit().len());
+    let per_round = distance * distance - 1;
+    assert_eq!(codes[1].circuit().detector_count(), 1000 * per_round);
+    // 最後にdata qubitを測定し, logical Zをobservableにする
+    assert_eq!(
+        codes[1].circuit().measurement_count(),
+        1000 * per_round + distance * distance
+    );
+    assert_eq!(codes[1].circuit().observable_count(), 1);
+
+    // ノイズのない回路ではdetection eventは起きない
+    let noiseless: Circuit = codes[0]
+        .circuit()
+        .iter()
+        .filter_map(Operation::noiseless)
+        .collect();
+    let mut sim = CHPSimulator::new(noiseless.qubit_count(), SmallRng::seed_from_u64(0));
+    sim.add_circuit(&noiseless);
+    sim.run();
+    assert_eq!(sim.detection_events().len(), 10 * per_round);
+    assert!(sim.detection_events().iter().all(|&event| event == 0));
+    assert_eq!(sim.observable_flips(), [0]);
+
+    codes[0].run();
+    assert_eq!(codes[0].circuit().detector_count(), 10 * per_round);
+}
@@ -0,0 +1,92 @@
+use rand::{rngs::SmallRng, Rng};
+
+use clifford::decoder::{mwpm::Mwpm, Decoder};
+use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+use clifford::qubit_graph::ungraph::UnGraph;
+
+type Coord = (i32, i32, i32);
+
+/// 左右にboundaryを持つwidth x height x roundの格子 (weightはランダム)
+/// boundaryでないnodeはそれぞれ確率defect_rateでdefectになる
+pub fn random_lattice(
+    width: i32,
+    height: i32,
+    round: i32,
+    defect_rate: f64,
+    rng: &mut SmallRng,
+) -> UnGraph {
+    let mut edges = Vec::new();
+    for t in 0..round {
+        for y in (1..2 * height).step_by(2) {
+            for x in (-1..2 * width).step_by(2) {
+                edges.push(((x, y, t), (x + 2, y, t)));
+            }
+            for x in (1..2 * width).step_by(2) {
+                if y + 2 < 2 * height {
+                    edges.push(((x, y, t), (x, y + 2, t)));
+                }
+                if t + 1 < round {
+                    edges.push(((x, y, t), (x, y, t + 1)));
+                }
+            }
+        }
+    }
+    let is_boundary = |(x, _, _): Coord| x == -1 || x == 2 * width + 1;
+    // 同じ側のboundary nodeはweight 0のedgeで1つのboundaryとみなす
+    let boundary_edges: Vec<_> = (0..round)
+        .flat_map(|t| {
+            (0..height - 1).flat_map(move |y| {
+                [-1, 2 * width + 1].map(|x| ((x, 2 * y + 1, t), (x, 2 * y + 3, t)))
+            })
+        })
+        .collect();
+
+    let mut graph = UnGraph::from_edges(&edges, round as usize - 1, 0);
+    for edge in edges.iter() {
+        graph.set_edge_weight(edge, rng.gen_range(0.5..5.0));
+    }
+    graph.add_edges_from(&boundary_edges);
+    graph.set_edges_weight(&boundary_edges, 0.0);
+    let mut nodes: Vec<_> = graph.nodes().copied().collect();
+    nodes.sort();
+    for node in nodes {
+        graph.set_is_boundary(node, is_boundary(node));
+        let defect = !is_boundary(node) && rng.gen_bool(defect_rate);
+        graph.set_classical_register(node, defect as u8);
+    }
+    graph
+}
+
+/// 同じseedのrotated surface codeをdecoderとMwpm::exactでdecodeし, それぞれのlogical errorの数を返す
+pub fn logical_errors_against_exact_mwpm(
+    decoder: Box<dyn Decoder>,
+    distance: usize,
+    p: f32,
+    shots: usize,
+) -> [u32; 2] {
+    // 同じseedのcodeは同じdetection eventを生成する
+    let mut decoders: Vec<(RotatedSurfaceCode, Box<dyn Decoder>)> = vec![
+        (
+            RotatedSurfaceCode::new(distance, distance, p, p, 0),
+            decoder,
+        ),
+        (
+            RotatedSurfaceCode::new(distance, distance, p, p, 0),
+            Box::new(Mwpm::exact()),
+        ),
+    ];
+    let mut error_num = [0u32; 2];
+    for ((code, decoder), error_num) in decoders.iter_mut().zip(error_num.iter_mut()) {
+        code.initialize();
+        code.syndrome_measurement();
+        for _ in 0..shots {
+            code.reset();
+            code.run();
+            code.decode_with(decoder.as_mut());
+            if code.logical_value_with(decoder.as_mut()) != 0 {
+                *error_num += 1;
+            }
+        }
+    }
+    error_num
+}
@@ -0,0 +1,5 @@
+// テストごとに使う関数が異なるので, 使わない関数の警告を出さない
+#![allow(dead_code)]
+
+pub mod decoding;
+pub mod simulation;
@@ -0,0 +1,147 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use clifford::noise::noise_model::NoiseType;
+use clifford::simulator::{pauli::Pauli, SimulatorInterface};
+
+/// 全てのgateと測定を含むランダムな回路を全てのシミュレータに追加する
+pub fn add_random_circuit(sims: &mut [&mut dyn SimulatorInterface], qubit_num: usize, seed: u64) {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    for _ in 0..40 {
+        let a = rng.gen_range(0..qubit_num);
+        let b = (a + rng.gen_range(1..qubit_num)) % qubit_num;
+        let choice = rng.gen_range(0..36);
+        for sim in sims.iter_mut() {
+            match choice {
+                0 => sim.add_h(a),
+                1 => sim.add_s(a),
+                2 => sim.add_x(a),
+                3 => sim.add_y(a),
+                4 => sim.add_z(a),
+                5 => sim.add_s_dag(a),
+                6 => sim.add_sqrt_x(a),
+                7 => sim.add_sqrt_x_dag(a),
+                8 => sim.add_sqrt_y(a),
+                9 => sim.add_sqrt_y_dag(a),
+                10 => sim.add_cx(a, b),
+                11 => sim.add_cz(a, b),
+                12 => sim.add_cy(a, b),
+                13 => sim.add_swap(a, b),
+                14 => sim.add_iswap(a, b),
+                15 => sim.add_iswap_dag(a, b),
+                16 => sim.add_xcx(a, b),
+                17 => sim.add_xcy(a, b),
+                18 => sim.add_xcz(a, b),
+                19 => sim.add_ycx(a, b),
+                20 => sim.add_ycy(a, b),
+                21 => sim.add_ycz(a, b),
+                22 => {
+                    sim.add_measurement(a, 0.0);
+                }
+                23 => {
+                    sim.add_measurement_x(a, 0.0);
+                }
+                24 => {
+                    sim.add_measurement_y(a, 0.0);
+                }
+                25 => {
+                    sim.add_measurement_and_reset(a, 0.0);
+                }
+                26 => {
+                    sim.add_measurement_and_reset_x(a, 0.0);
+                }
+                27 => {
+                    sim.add_measurement_and_reset_y(a, 0.0);
+                }
+                28 => sim.add_reset_x(a),
+                29 => sim.add_reset_y(a),
+                30 => {
+                    sim.add_measurement_pauli_product(vec![(a, Pauli::X), (b, Pauli::Z)], 0.0);
+                }
+                31 => {
+                    let (b, c) = ((a + 1) % qubit_num, (a + 2) % qubit_num);
+                    let product = vec![(a, Pauli::Y), (b, Pauli::Y), (c, Pauli::X)];
+                    sim.add_measurement_pauli_product(product, 0.0);
+                }
+                32 => sim.add_measurement_to_zero(a),
+                _ => sim.add_h(b),
+            }
+        }
+    }
+    for sim in sims.iter_mut() {
+        for a in 0..qubit_num {
+            sim.add_measurement(a, 0.0);
+        }
+    }
+}
+
+/// 測定結果の各部分集合のparityが決定的ならその値, ランダムならNone
+///
+/// stabilizer回路の測定結果はaffine部分空間上の一様分布なので, これが一致すれば分布も一致する
+pub fn parities(records: &[Vec<u8>]) -> Vec<Option<u8>> {
+    let bits = records[0].len();
+    (1..1usize << bits)
+        .map(|subset| {
+            let parity = |record: &Vec<u8>| {
+                (0..bits)
+                    .filter(|i| (subset >> i) & 1 == 1)
+                    .fold(0, |parity, i| parity ^ record[i])
+            };
+            let first = parity(&records[0]);
+            records
+                .iter()
+                .all(|record| parity(record) == first)
+                .then_some(first)
+        })
+        .collect()
+}
+
+/// 3 qubitの繰り返し符号 (data: 0, 2, 4, ancilla: 1, 3) のsyndrome測定
+pub fn add_repetition_code(sim: &mut dyn SimulatorInterface, round: usize, p: f32) {
+    for t in 0..round {
+        for a in [0, 2, 4] {
+            sim.add_noise(a, NoiseType::Depolarizing(p));
+        }
+        for (ancilla, data) in [(1, 0), (1, 2), (3, 2), (3, 4)] {
+            sim.add_cx(data, ancilla);
+        }
+        sim.add_noise2(0, 2, NoiseType::Depolarizing2(p));
+        sim.add_measurement_and_reset(1, p);
+        sim.add_measurement_and_reset(3, p);
+        for (i, k) in [(0.0, 2), (1.0, 1)] {
+            let lookback = if t == 0 { vec![k] } else { vec![k, k + 2] };
+            sim.add_detector(vec![i, t as f32], lookback);
+        }
+    }
+    for a in [0, 2, 4] {
+        sim.add_measurement(a, 0.0);
+    }
+    sim.add_observable_include(0, vec![3]);
+}
+
+/// 1 shotずつ実行したときの各detector (とobservable) が反転する割合
+pub fn detection_event_rates(sim: &mut dyn SimulatorInterface, shots: usize) -> Vec<f32> {
+    let mut rate = vec![0.0; sim.detector_count() + 1];
+    for _ in 0..shots {
+        sim.reset();
+        sim.run();
+        let events = sim.detection_events().iter().chain(sim.observable_flips());
+        for (rate, &event) in rate.iter_mut().zip(events) {
+            *rate += event as f32 / shots as f32;
+        }
+    }
+    rate
+}
+
+/// CHPで求めた割合と他のシミュレータで求めた割合が近いことを確かめる
+pub fn assert_rates_close(chp_rate: &[f32], other_rate: &[f32], tolerance: f32) {
+    assert_eq!(chp_rate.len(), other_rate.len());
+    for (chp_rate, other_rate) in chp_rate.iter().zip(other_rate.iter()) {
+        assert!(*chp_rate > 0.0);
+        assert!(
+            (chp_rate - other_rate).abs() < tolerance,
+            "chp: {}, other: {}",
+            chp_rate,
+            other_rate
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+use clifford::circuit::Circuit;
+use clifford::noise::noise_model::NoiseModel;
+use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+use clifford::qubit_graph::ungraph::UnGraph;
+use clifford::simulator::{
+    chp_simulator::CHPSimulator,
+    frame_simulator::{self, FrameSimulator},
+    pauli::Pauli,
+    Operation, SimulatorInterface, Type,
+};
+
+mod common;
+use common::simulation::{
+    add_random_circuit, add_repetition_code, assert_rates_close, detection_event_rates, parities,
+};
+
+#[test]
+fn measurement_distribution_matches_chp() {
+    let qubit_num = 3;
+    let shots = 300;
+
+    for seed in 0..10 {
+        let mut chp = CHPSimulator::new(qubit_num, SmallRng::seed_from_u64(seed));
+        let mut frame = FrameSimulator::new(qubit_num, SmallRng::seed_from_u64(seed));
+        add_random_circuit(&mut [&mut chp, &mut frame], qubit_num, seed);
+
+        let chp_records: Vec<_> = (0..shots)
+            .map(|_| {
+                chp.reset();
+                chp.run();
+                chp.measurement_record().bits().to_vec()
+            })
+            .collect();
+        let frame_records = frame.sample_measurements(shots);
+
+        assert_eq!(
+            parities(&chp_records),
+            parities(&frame_records),
+            "seed {}",
+            seed
+        );
+    }
+}
+
+#[test]
+fn feedback_distribution_matches_chp() {
+    let qubit_num = 3;
+    let shots = 300;
+
+    for seed in 0..10 {
+        let mut chp = CHPSimulator::new(qubit_num, SmallRng::seed_from_u64(seed));
+        let mut frame = FrameSimulator::new(qubit_num, SmallRng::seed_from_u64(seed));
+        add_random_circuit(&mut [&mut chp, &mut frame], qubit_num, seed);
+        // 最後の測定結果によるPauliを作用させ, 基底を変えてもう一度測定する
+        let mut rng = SmallRng::seed_from_u64(seed + 100);
+        let feedback: Vec<_> = (0..qubit_num)
+            .map(|a| {
+                let pauli = [Pauli::X, Pauli::Y, Pauli::Z][rng.gen_range(0..3)];
+                (pauli, rng.gen_range(1..=qubit_num), a)
+            })
+            .collect();
+        for sim in [&mut chp as &mut dyn SimulatorInterface, &mut frame] {
+            for &(pauli, k, a) in feedback.iter() {
+                sim.add_feedback(pauli, k, a);
+            }
+            sim.add_h(0);
+            sim.add_cx(0, 1);
+            sim.add_sqrt_y(2);
+            for a in 0..qubit_num {
+                sim.add_measurement(a, 0.0);
+            }
+        }
+
+        // feedbackの前後の測定結果だけを比べる
+        let last = |record: &[u8]| record[record.len() - 2 * qubit_num..].to_vec();
+        let chp_records: Vec<_> = (0..shots)
+            .map(|_| {
+                chp.reset();
+                chp.run();
+                last(chp.measurement_record().bits())
+            })
+            .collect();
+        let frame_records: Vec<_> = frame
+            .sample_measurements(shots)
+            .iter()
+            .map(|record| last(record))
+            .collect();
+
+        assert_eq!(
+            parities(&chp_records),
+            parities(&frame_records),
+            "seed {}",
+            seed
+        );
+    }
+}
+
+#[test]
+fn feedback_uses_recorded_outcome() {
+    // 測定エラーで記録が反転すると, active resetは誤ってXを作用させる
+    let p = 0.2;
+    let shots = 20000;
+    let mut frame = FrameSimulator::new(1, SmallRng::seed_from_u64(0));
+    frame.add_h(0);
+    frame.add_measurement(0, p);
+    frame.add_feedback(Pauli::X, 1, 0);
+    frame.add_measurement(0, 0.0);
+    let records = frame.sample_measurements(shots);
+    let rate = records.iter().filter(|record| record[1] == 1).count() as f32 / shots as f32;
+    assert!((rate - p).abs() < 0.02, "rate: {}", rate);
+}
+
+#[test]
+fn detection_event_rates_match_chp() {
+    let round = 3;
+    let p = 0.05;
+    let shots = 20000;
+
+    let mut chp = CHPSimulator::new(5, SmallRng::seed_from_u64(0));
+    let mut frame = FrameSimulator::new(5, SmallRng::seed_from_u64(0));
+    add_repetition_code(&mut chp, round, p);
+    add_repetition_code(&mut frame, round, p);
+    assert_eq!(frame.detector_count(), 2 * round);
+
+    let chp_rate = detection_event_rates(&mut chp, shots);
+
+    let (detectors, observables) = frame.sample_detectors(shots);
+    let mut frame_rate = vec![0.0; frame.detector_count() + 1];
+    for (events, flips) in detectors.iter().zip(observables.iter()) {
+        for (rate, &event) in frame_rate.iter_mut().zip(events.iter().chain(flips)) {
+            *rate += event as f32 / shots as f32;
+        }
+    }
+
+    assert_rates_close(&chp_rate, &frame_rate, 0.015);
+
+    // 単発のrunでもbatchから1 shotずつ取り出せる
+    frame.run();
+    assert_eq!(frame.detection_events().len(), 2 * round);
+    assert_eq!(frame.measurement_record().len(), 2 * round + 3);
+}
+
+#[test]
+fn surface_code_on_frame_simulator() {
+    let distance = 3;
+    let mut code = RotatedSurfaceCode::new_with_simulator(
+        distance,
+        distance,
+        NoiseModel::Phenomenological(0.0, 0.0),
+        Type::FrameSimulator,
+        0,
+    );
+    code.initialize();
+    code.syndrome_measurement();
+
+    for _ in 0..100 {
+        code.reset();
+        code.run();
+        code.decode_mwpm(distance);
+        assert_eq!(code.logical_value(), 0);
+    }
+}
+
+/// data qubit 0, 1とancilla 2の繰り返し符号を2round測定する回路
+///
+/// outcomesは各ノイズの位置で起こるエラー (2 qubitノイズは0がI, kがPauliChannel2のk-1番目, 測定は1で反転).
+/// Noneならその位置は確率的なノイズのまま
+fn repetition_circuit(p: f32, p_m: f32, outcomes: Option<&[usize]>) -> Circuit {
+    let noise2 = |slot: usize| match outcomes {
+        None => Operation::Depolarizing2(0, 2, p),
+        Some(outcomes) => {
+            let mut q = [0.0; 15];
+            if outcomes[slot] > 0 {
+                q[outcomes[slot] - 1] = 1.0;
+            }
+            Operation::PauliChannel2(0, 2, q)
+        }
+    };
+    let error = |slot: usize| match outcomes {
+        None => p_m,
+        Some(outcomes) => outcomes[slot] as f32,
+    };
+
+    let mut operations = Vec::new();
+    for round in 0..2 {
+        operations.extend([
+            Operation::CX(0, 2),
+            noise2(2 * round),
+            Operation::CX(1, 2),
+            Operation::MR(2, error(2 * round + 1)),
+        ]);
+        let lookback = if round == 0 { vec![1] } else { vec![1, 2] };
+        operations.push(Operation::Detector(vec![], lookback));
+    }
+    operations.extend([
+        Operation::M(0, error(4)),
+        Operation::M(1, error(5)),
+        Operation::Detector(vec![], vec![1, 2, 3]),
+        Operation::ObservableInclude(0, vec![2]),
+    ]);
+    Circuit::from(operations)
+}
+
+/// ノイズの位置ごとのエラーの確率 (2 qubitノイズは16通り, 測定は2通り)
+fn outcome_probabilities(p: f32, p_m: f32) -> Vec<Vec<f32>> {
+    let noise2: Vec<f32> = std::iter::once(1.0 - p).chain([p / 15.0; 15]).collect();
+    let error = vec![1.0 - p_m, p_m];
+    vec![noise2.clone(), error.clone(), noise2, error.clone(), error.clone(), error]
+}
+
+/// エラーを確定させた回路を実行したdetection eventとobservableの反転
+fn run_with_outcomes(outcomes: &[usize]) -> (Vec<u8>, Vec<u8>) {
+    let mut sim = CHPSimulator::new(3, SmallRng::seed_from_u64(0));
+    sim.add_circuit(&repetition_circuit(0.0, 0.0, Some(outcomes)));
+    sim.run();
+    (sim.detection_events().to_vec(), sim.observable_flips().to_vec())
+}
+
+#[test]
+fn detector_error_model_matches_brute_force() {
+    let (p, p_m) = (0.01, 0.02);
+    let model = frame_simulator::detector_error_model(3, &repetition_circuit(p, p_m, None));
+    let probabilities = outcome_probabilities(p, p_m);
+
+    // エラーを1つずつ確定させてCHPで実行し, 反転させるものが同じエラーをまとめる
+    let mut brute_force: BTreeMap<(Vec<usize>, Vec<usize>), f32> = BTreeMap::new();
+    for (slot, outcome_probability) in probabilities.iter().enumerate() {
+        for (k, &q) in outcome_probability.iter().enumerate().skip(1) {
+            let mut outcomes = vec![0; probabilities.len()];
+            outcomes[slot] = k;
+            let (detectors, observables) = run_with_outcomes(&outcomes);
+            let ones = |bits: Vec<u8>| -> Vec<usize> {
+                bits.iter().enumerate().filter(|(_, &b)| b == 1).map(|(i, _)| i).collect()
+            };
+            let key = (ones(detectors), ones(observables));
+            if key.0.is_empty() && key.1.is_empty() {
+                continue;
+            }
+            let merged = brute_force.entry(key).or_insert(0.0);
+            *merged = UnGraph::merge_error_rate(*merged, q);
+        }
+    }
+    assert_eq!(model.len(), brute_force.len());
+    for (mechanism, ((detectors, observables), q)) in model.iter().zip(brute_force.iter()) {
+        assert_eq!(&mechanism.detectors, detectors);
+        assert_eq!(&mechanism.observables, observables);
+        assert!((mechanism.probability - q).abs() < 1e-6, "{:?} {}", mechanism, q);
+    }
+    // 1roundの測定エラーと, ancillaがX, Yでdata qubitがI, Zの4個のPauliは時間方向のedge (detector 0, 1) になる
+    let time_like = model.iter().find(|m| m.detectors == [0, 1] && m.observables.is_empty());
+    let expected = [p_m, p / 15.0, p / 15.0, p / 15.0, p / 15.0]
+        .into_iter()
+        .fold(0.0, UnGraph::merge_error_rate);
+    assert!((time_like.unwrap().probability - expected).abs() < 1e-6);
+
+    // 全てのエラーの組み合わせを数え上げた各detectorの反転の確率と, 独立なエラーとみなしたmodelの予測を比べる
+    let mut exact = [0.0; 3];
+    let mut outcomes = vec![0; probabilities.len()];
+    loop {
+        let q: f32 = outcomes
+            .iter()
+            .zip(probabilities.iter())
+            .map(|(&k, probability)| probability[k])
+            .product();
+        let (detectors, _) = run_with_outcomes(&outcomes);
+        for (rate, &event) in exact.iter_mut().zip(detectors.iter()) {
+            *rate += q * event as f32;
+        }
+        // 次の組み合わせ
+        let Some(slot) = (0..outcomes.len()).find(|&i| outcomes[i] + 1 < probabilities[i].len())
+        else {
+            break;
+        };
+        outcomes[slot] += 1;
+        outcomes[..slot].iter_mut().for_each(|k| *k = 0);
+    }
+    for (detector, &rate) in exact.iter().enumerate() {
+        let predicted = model
+            .iter()
+            .filter(|m| m.detectors.contains(&detector))
+            .fold(0.0, |q, m| UnGraph::merge_error_rate(q, m.probability));
+        assert!((predicted - rate).abs() < 1e-4, "{}: {} {}", detector, predicted, rate);
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+
+use clifford::circuit::Circuit;
+use clifford::noise::{
+    calibration::{self, Calibration},
+    circuit_noise::{self, apply_noise_model},
+    noise_model::{DeviceErrorMap, NoiseModel, NoiseType},
+};
+use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+use clifford::qubit_graph::ungraph::UnGraph;
+use clifford::qubit_network::QubitNetwork;
+use clifford::simulator::{pauli::Pauli, Operation, Type};
+
+/// 2 roundのsyndrome測定を模した回路 (data: 0, 1, ancilla: 2)
+fn two_round_circuit() -> Vec<Operation> {
+    let mut operations = Vec::new();
+    for _ in 0..2 {
+        operations.push(Operation::CX(0, 2));
+        operations.push(Operation::CX(1, 2));
+        operations.push(Operation::MR(2, 0.0));
+    }
+    operations
+}
+
+fn count_depolarizing(operations: &[Operation]) -> (usize, usize) {
+    operations
+        .iter()
+        .fold((0, 0), |(one, two), operation| match operation {
+            Operation::Depolarizing(_, _) => (one + 1, two),
+            Operation::Depolarizing2(_, _, _) => (one, two + 1),
+            _ => (one, two),
+        })
+}
+
+fn measurement_error_rates(operations: &[Operation]) -> Vec<f32> {
+    operations
+        .iter()
+        .filter_map(|operation| match operation {
+            Operation::MR(_, p) => Some(*p),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn code_capacity_and_phenomenological_noise() {
+    let operations = two_round_circuit();
+
+    let noisy = apply_noise_model(&operations, &NoiseModel::CodeCapacity(0.1), &[0, 1]);
+    assert_eq!(count_depolarizing(&noisy), (2, 0));
+    assert!(matches!(noisy[0], Operation::Depolarizing(0, _)));
+    assert_eq!(measurement_error_rates(&noisy), vec![0.0, 0.0]);
+
+    let noisy = apply_noise_model(
+        &operations,
+        &NoiseModel::Phenomenological(0.1, 0.2),
+        &[0, 1],
+    );
+    // 各roundの始めにdata qubitへノイズ
+    assert_eq!(count_depolarizing(&noisy), (4, 0));
+    assert!(matches!(noisy[5], Operation::Depolarizing(0, _)));
+    assert_eq!(measurement_error_rates(&noisy), vec![0.2, 0.2]);
+}
+
+#[test]
+fn circuit_level_noise() {
+    let operations = two_round_circuit();
+
+    let noisy = apply_noise_model(&operations, &NoiseModel::SD6(0.1), &[0, 1]);
+    // 2q gateごとにDEP2, 各CX中に使われないdata qubitと測定中のdata qubitがidle
+    assert_eq!(count_depolarizing(&noisy), (2 * 4, 4));
+    // MR後のリセットエラー
+    let reset_error = noisy
+        .iter()
+        .filter(|operation| matches!(operation, Operation::PauliChannel1(2, _, _, _)))
+        .count();
+    assert_eq!(reset_error, 2);
+    assert_eq!(measurement_error_rates(&noisy), vec![0.1, 0.1]);
+
+    let noisy = apply_noise_model(&operations, &NoiseModel::SI1000(0.1), &[0, 1]);
+    assert!(noisy.iter().any(
+        |operation| matches!(operation, Operation::Depolarizing(0, p) if (p - 0.2).abs() < 1e-6)
+    ));
+    assert!(measurement_error_rates(&noisy)
+        .iter()
+        .all(|p| (p - 0.5).abs() < 1e-6));
+
+    let noisy = apply_noise_model(
+        &operations,
+        &NoiseModel::UniformCircuit {
+            p_1q: 0.0,
+            p_2q: 0.1,
+            p_idle: 0.0,
+            p_reset: 0.0,
+            p_measurement: 0.3,
+        },
+        &[0, 1],
+    );
+    assert_eq!(count_depolarizing(&noisy), (0, 4));
+    assert_eq!(noisy.len(), operations.len() + 4);
+    assert_eq!(measurement_error_rates(&noisy), vec![0.3, 0.3]);
+}
+
+#[test]
+fn surface_code_with_noise_models() {
+    let distance = 3;
+    let models = [
+        NoiseModel::CodeCapacity(0.0),
+        NoiseModel::Phenomenological(0.0, 0.0),
+        NoiseModel::SD6(0.0),
+        NoiseModel::SI1000(0.0),
+        NoiseModel::UniformCircuit {
+            p_1q: 0.0,
+            p_2q: 0.0,
+            p_idle: 0.0,
+            p_reset: 0.0,
+            p_measurement: 0.0,
+        },
+    ];
+
+    for model in models.iter() {
+        let mut code =
+            RotatedSurfaceCode::new_with_noise_model(distance, distance, model.clone(), 0);
+        code.initialize();
+        code.syndrome_measurement();
+
+        for _ in 0..10 {
+            code.reset();
+            code.run();
+            code.decode_mwpm(distance);
+            assert_eq!(code.logical_value(), 0);
+        }
+    }
+
+    // 回路ノイズがあってもシミュレーションできる
+    let mut code =
+        RotatedSurfaceCode::new_with_noise_model(distance, distance, NoiseModel::SD6(0.001), 0);
+    code.initialize();
+    code.syndrome_measurement();
+    code.run();
+}
+
+#[test]
+fn device_noise_from_error_map() {
+    let operations = two_round_circuit();
+
+    let error_map = DeviceErrorMap {
+        gate_1q: HashMap::new(),
+        // couplerごとに異なるerror rate
+        gate_2q: [((0, 2), 0.1), ((1, 2), 0.3)].into_iter().collect(),
+        idle: [(1, NoiseType::PauliChannel1(0.01, 0.01, 0.02))]
+            .into_iter()
+            .collect(),
+        measurement: [(2, 0.05)].into_iter().collect(),
+        reset: [(2, 0.05)].into_iter().collect(),
+    };
+
+    let noisy = apply_noise_model(&operations, &NoiseModel::Device(Box::new(error_map)), &[0, 1]);
+    let gate_2q: Vec<_> = noisy
+        .iter()
+        .filter_map(|operation| match operation {
+            Operation::Depolarizing2(a, b, p) => Some((*a, *b, *p)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        gate_2q,
+        vec![(0, 2, 0.1), (1, 2, 0.3), (0, 2, 0.1), (1, 2, 0.3)]
+    );
+    // qubit 1のidle (CX(0, 2)中と測定中) とqubit 2のリセットエラー
+    let pauli_channel: Vec<_> = noisy
+        .iter()
+        .filter_map(|operation| match operation {
+            Operation::PauliChannel1(a, _, _, _) => Some(*a),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(pauli_channel, vec![1, 2, 1, 1, 2, 1]);
+    assert_eq!(measurement_error_rates(&noisy), vec![0.05, 0.05]);
+}
+
+#[test]
+fn load_calibration_file() {
+    let text = "\
+# qubit x y T1 T2 readout_error [1q gate fidelity]
+qubit 0 0 50.0 40.0 0.01
+qubit 1 1 50.0 40.0 0.02 0.999 # 1q gate fidelityも指定
+coupler 0 0 1 1 0.99
+moment_time 0.1
+";
+    let path = std::env::temp_dir().join("clifford_calibration_test.txt");
+    std::fs::write(&path, text).unwrap();
+    let calibration = Calibration::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(calibration.qubit.len(), 2);
+    assert_eq!(calibration.qubit[&(1, 1)].gate_1q_fidelity, Some(0.999));
+    assert_eq!(calibration.coupler[&((0, 0), (1, 1))], 0.99);
+    assert_eq!(calibration.moment_time, 0.1);
+
+    assert!((calibration::gate_2q_error_rate(0.99) - 0.0125).abs() < 1e-6);
+    assert!((calibration::gate_1q_error_rate(0.999) - 0.0015).abs() < 1e-6);
+    match calibration::idle_noise(0.1, 50.0, 40.0) {
+        NoiseType::PauliChannel1(px, py, pz) => {
+            assert_eq!(px, py);
+            assert!(px > 0.0 && pz > 0.0);
+            assert!(px + py + pz < 0.01);
+        }
+        _ => panic!("idle noise must be Pauli channel"),
+    }
+
+    assert!(Calibration::parse("qubit 0 0 50.0").is_err());
+    assert!(Calibration::parse("fridge 0 0").is_err());
+    // 座標は整数でなければならない
+    for text in ["qubit 0.7 1.9 50.0 40.0 0.01", "coupler 0 0 1 1.5 0.99"] {
+        let error = Calibration::parse(text).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(
+            error.to_string().starts_with("line 1: invalid coordinate"),
+            "{}",
+            error
+        );
+    }
+}
+
+#[test]
+fn round_flip_rate_per_qubit_and_coupler() {
+    let (p, p_m) = (0.03, 0.01);
+    let model = NoiseModel::Phenomenological(p, p_m);
+    let data = circuit_noise::round_flip_rate(&model, 0, &[1, 2, 3, 4], false, Pauli::Z);
+    assert!((data - 2.0 * p / 3.0).abs() < 1e-6);
+    assert_eq!(
+        circuit_noise::round_flip_rate(&model, 1, &[0], true, Pauli::X),
+        p_m
+    );
+
+    // data qubit 0, 2とancilla 1の間のcouplerのうち, (0, 1)だけが悪い
+    let error_map = DeviceErrorMap {
+        gate_2q: HashMap::from([((0, 1), 0.15), ((1, 0), 0.15), ((1, 2), 0.0), ((2, 1), 0.0)]),
+        measurement: HashMap::from([(1, 0.02)]),
+        reset: HashMap::from([(1, 0.02)]),
+        ..Default::default()
+    };
+    let model = NoiseModel::Device(Box::new(error_map));
+    let bad = circuit_noise::round_flip_rate(&model, 0, &[1], false, Pauli::Z);
+    assert!((bad - 0.08).abs() < 1e-6);
+    assert_eq!(
+        circuit_noise::round_flip_rate(&model, 2, &[1], false, Pauli::Z),
+        0.0
+    );
+    // ancillaは測定エラー, リセットエラー, 2q gateのエラーをまとめる
+    let ancilla = circuit_noise::round_flip_rate(&model, 1, &[0, 2], true, Pauli::Z);
+    let expected = [0.02, 0.02, 0.08]
+        .into_iter()
+        .fold(0.0, UnGraph::merge_error_rate);
+    assert!((ancilla - expected).abs() < 1e-6);
+}
+
+#[test]
+fn surface_code_with_calibration() {
+    let distance = 3;
+    // 1つだけ悪いcouplerとqubitを持つデバイス
+    let calibration = Calibration::parse(
+        "\
+qubit 0 0 50.0 40.0 0.2 0.99
+coupler 0 0 1 1 0.9
+moment_time 0.1
+",
+    )
+    .unwrap();
+
+    let mut network = QubitNetwork::new_rotated_planer_lattice_from_rectangle(
+        distance,
+        distance,
+        0.0,
+        Type::CHPSimulator,
+        0,
+    );
+    network.load_calibration(&calibration).unwrap();
+    assert_eq!(network.measurement_error_rate((0, 0)), 0.2);
+    assert_eq!(network.measurement_error_rate((2, 2)), 0.0);
+    // リセットエラーは読み出しエラーと同じ
+    assert_eq!(network.reset_error_rate((0, 0)), 0.2);
+    assert_eq!(
+        network.qubit_error_rate((0, 0)),
+        calibration::gate_1q_error_rate(0.99)
+    );
+    assert_eq!(network.qubit_error_rate((2, 2)), 0.0);
+    // couplerのerror rateは両方の向きに設定される
+    let p_2q = calibration::gate_2q_error_rate(0.9);
+    assert_eq!(network.connection_error_rate(((0, 0), (1, 1))), p_2q);
+    assert_eq!(network.connection_error_rate(((1, 1), (0, 0))), p_2q);
+    assert_eq!(network.connection_error_rate(((2, 2), (1, 1))), 0.0);
+
+    let mut code =
+        RotatedSurfaceCode::new_with_calibration(distance, distance, &calibration, 0).unwrap();
+    let NoiseModel::Device(error_map) = code.noise_model() else {
+        panic!("calibration must give device noise");
+    };
+    let index_to_sim = code.index_to_sim();
+    let (q, c) = (index_to_sim[&(0, 0)], index_to_sim[&(1, 1)]);
+    assert_eq!(error_map.measurement[&q], 0.2);
+    assert_eq!(error_map.measurement[&c], 0.0);
+    assert_eq!(error_map.reset[&q], 0.2);
+    assert_eq!(error_map.gate_1q[&q], calibration::gate_1q_error_rate(0.99));
+    assert_eq!(error_map.gate_2q[&(q, c)], p_2q);
+    assert_eq!(error_map.gate_2q[&(c, q)], p_2q);
+    match (error_map.idle[&q], calibration::idle_noise(0.1, 50.0, 40.0)) {
+        (NoiseType::PauliChannel1(px, py, pz), NoiseType::PauliChannel1(ex, ey, ez)) => {
+            assert_eq!((px, py, pz), (ex, ey, ez));
+            assert!(px > 0.0 && pz > 0.0);
+        }
+        _ => panic!("idle noise must be Pauli channel"),
+    }
+    match error_map.idle[&c] {
+        NoiseType::PauliChannel1(px, py, pz) => assert_eq!(px + py + pz, 0.0),
+        NoiseType::Depolarizing(p) => assert_eq!(p, 0.0),
+        _ => panic!("idle noise must be single qubit noise"),
+    }
+
+    code.initialize();
+    code.syndrome_measurement();
+    for _ in 0..10 {
+        code.reset();
+        code.run();
+        code.decode_mwpm(distance);
+        code.logical_value();
+    }
+}
+
+#[test]
+fn calibration_with_unknown_qubit_or_coupler_is_rejected() {
+    for text in [
+        "coupler 0 0 2 2 0.99\n",
+        "coupler 0 0 100 101 0.99\n",
+        "qubit 100 100 50.0 40.0 0.01\n",
+    ] {
+        let calibration = Calibration::parse(text).unwrap();
+        let error = match RotatedSurfaceCode::new_with_calibration(3, 3, &calibration, 0) {
+            Ok(_) => panic!("{:?} must be rejected", text),
+            Err(error) => error,
+        };
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("does not exist"), "{}", error);
+    }
+
+    // エラーのときはerror rateを変更しない
+    let mut network =
+        QubitNetwork::new_rotated_planer_lattice_from_rectangle(3, 3, 0.0, Type::CHPSimulator, 0);
+    let calibration =
+        Calibration::parse("qubit 0 0 50.0 40.0 0.2\ncoupler 0 0 2 2 0.99\n").unwrap();
+    assert!(network.load_calibration(&calibration).is_err());
+    assert_eq!(network.measurement_error_rate((0, 0)), 0.0);
+}
+
+#[test]
+fn idle_noise_per_tick_layer_and_repeat_block() {
+    let model = NoiseModel::UniformCircuit {
+        p_1q: 0.0,
+        p_2q: 0.0,
+        p_idle: 0.1,
+        p_reset: 0.0,
+        p_measurement: 0.0,
+    };
+    // TICKがなければH(0), H(1)は同じmomentになる
+    let operations = vec![Operation::H(0), Operation::H(1)];
+    assert_eq!(
+        count_depolarizing(&apply_noise_model(&operations, &model, &[])),
+        (0, 0)
+    );
+    // TICKで区切った層ごとに使われないqubitにidleノイズが入る
+    let operations = vec![Operation::H(0), Operation::Tick, Operation::H(1)];
+    let noisy = apply_noise_model(&operations, &model, &[]);
+    assert_eq!(count_depolarizing(&noisy), (2, 0));
+    assert!(matches!(noisy[1], Operation::Depolarizing(1, _)));
+    assert!(matches!(noisy[2], Operation::Tick));
+
+    // REPEATブロックには展開した回路と同じノイズが入る
+    let flat = two_round_circuit();
+    let repeated = vec![Operation::Repeat(2, flat[..3].to_vec().into())];
+    for model in [
+        NoiseModel::CodeCapacity(0.1),
+        NoiseModel::Phenomenological(0.1, 0.2),
+        NoiseModel::SD6(0.1),
+    ] {
+        let noisy: Circuit = apply_noise_model(&repeated, &model, &[0, 1]).into();
+        assert_eq!(
+            format!("{:?}", noisy.flattened().operations()),
+            format!("{:?}", apply_noise_model(&flat, &model, &[0, 1])),
+            "{:?}",
+            model
+        );
+    }
+}
+
+#[test]
+fn network_measurement_uses_qubit_error_rate() {
+    let mut network =
+        QubitNetwork::new_rotated_planer_lattice_from_rectangle(3, 3, 0.0, Type::CHPSimulator, 0);
+    // qubit (0, 0)だけ必ず読み出しを誤る
+    network.set_measurement_error_rate((0, 0), 1.0);
+    network.measurement((0, 0));
+    network.measurement((2, 2));
+    network.measurement_pauli_product(&[((0, 0), Pauli::Z), ((2, 2), Pauli::Z)]);
+    network.run();
+
+    let record = network.measurement_record();
+    assert_eq!(record.get(0), 1);
+    assert_eq!(record.get(1), 0);
+    assert_eq!(record.get(2), 1);
+}
@@ -0,0 +1,289 @@
+use rand::{rngs::SmallRng, SeedableRng};
+
+use clifford::circuit::{qasm::QasmVersion, Circuit};
+use clifford::noise::noise_model::NoiseModel;
+use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+use clifford::simulator::{
+    chp_simulator::CHPSimulator, pauli::Pauli, Operation, SimulatorInterface,
+};
+
+/// Bell状態を2組作って測定する回路
+const BELL_QASM2: &str = "\
+OPENQASM 2.0;
+include \"qelib1.inc\";
+// two Bell pairs
+qreg a[2];
+qreg b[2];
+creg c[2];
+creg d[2];
+reset a;
+h a;
+cx a, b;
+barrier a, b;
+measure a -> c;
+measure b[0] -> d[0];
+measure b[1] -> d[1];
+";
+
+fn measurements(circuit: &Circuit, seed: u64) -> Vec<u8> {
+    let mut sim = CHPSimulator::new(circuit.qubit_count(), SmallRng::seed_from_u64(seed));
+    sim.add_circuit(circuit);
+    sim.run();
+    sim.measurement_record().bits().to_vec()
+}
+
+#[test]
+fn parse_qasm2_with_registers() {
+    let circuit = Circuit::from_qasm(BELL_QASM2).unwrap();
+    assert_eq!(circuit.qubit_count(), 4);
+    assert_eq!(circuit.measurement_count(), 4);
+    assert_eq!(
+        format!("{:?}", &circuit.operations()[..7]),
+        format!(
+            "{:?}",
+            [
+                Operation::R(0),
+                Operation::R(1),
+                Operation::H(0),
+                Operation::H(1),
+                Operation::CX(0, 2),
+                Operation::CX(1, 3),
+                Operation::Tick,
+            ]
+        )
+    );
+
+    for seed in 0..10 {
+        let m = measurements(&circuit, seed);
+        assert_eq!(m[0], m[2]);
+        assert_eq!(m[1], m[3]);
+    }
+}
+
+#[test]
+fn parse_qasm3_syntax() {
+    let circuit = Circuit::from_qasm(
+        "OPENQASM 3;\ninclude \"stdgates.inc\";\nqubit[3] q;\nqubit r;\nbit[2] c;\nbit d;\n\
+         x q[0]; sx q[1]; sxdg q[1];\ncz q[0], r; swap q[1],\n  q[2];\n\
+         c[0] = measure q[0];\nd = measure r;\nmeasure q[1];\n",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", circuit.operations()),
+        format!(
+            "{:?}",
+            [
+                Operation::X(0),
+                Operation::SqrtX(1),
+                Operation::SqrtXDag(1),
+                Operation::CZ(0, 3),
+                Operation::Swap(1, 2),
+                Operation::M(0, 0.0),
+                Operation::M(3, 0.0),
+                Operation::M(1, 0.0),
+            ]
+        )
+    );
+}
+
+#[test]
+fn parse_errors_are_descriptive() {
+    let cases = [
+        (
+            "qreg q[1];\nrz(0.5) q[0];\n",
+            "line 2: non-Clifford gate 'rz' is not supported",
+        ),
+        (
+            "qreg q[3];\n\nccx q[0], q[1], q[2];\n",
+            "line 3: non-Clifford gate 'ccx' is not supported",
+        ),
+        ("qreg q[1];\nfoo q[0];\n", "line 2: unknown gate 'foo'"),
+        (
+            "qreg q[2];\nh q[2];\n",
+            "line 2: index 2 out of range of 'q'",
+        ),
+        ("h q[0];\n", "line 1: undeclared quantum register 'q'"),
+        ("qreg q[2];\ncx q[0];\n", "line 2: cx takes 2 operands"),
+        (
+            "qreg q[2];\ncx q[1], q[1];\n",
+            "line 2: cx acts twice on qubit 1",
+        ),
+        (
+            "qreg q[2];\ncreg c[1];\nmeasure q -> c;\n",
+            "line 3: register sizes do not match",
+        ),
+        (
+            "qreg q[1];\ngate g a { h a; }\n",
+            "line 2: custom gate definitions are not supported",
+        ),
+        (
+            "qreg q[1];\ncreg c[1];\nmeasure q -> c;\nif (c==1) h q[0];\n",
+            "line 4: only x, y and z can be classically controlled, got 'h'",
+        ),
+        (
+            "qreg q[1];\ncreg c[1];\nif (c==1) x q[0];\n",
+            "line 3: c[0] is not measured",
+        ),
+        (
+            "OPENQASM 4.0;\n",
+            "line 1: unsupported OpenQASM version 4.0",
+        ),
+        ("qreg q[1];\nh q[0]\n", "line 2: missing ';'"),
+    ];
+    for (text, expected) in cases {
+        let error = Circuit::from_qasm(text).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(
+            error.to_string().starts_with(expected),
+            "{:?}: {}",
+            text,
+            error
+        );
+    }
+}
+
+#[test]
+fn classically_controlled_pauli() {
+    // 2.0では1 bitのcregについての条件, 3ではbitごとの条件
+    let circuit = Circuit::from_qasm(
+        "OPENQASM 2.0;\nqreg q[2];\ncreg m[1];\ncreg c[2];\nh q[0];\nmeasure q[0] -> m[0];\n\
+         measure q -> c;\nif (m==1) x q[0];\nif (c[0] == 1) z q;\nif (c[1]) y q[1];\n",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", &circuit.operations()[4..]),
+        format!(
+            "{:?}",
+            [
+                Operation::Feedback(Pauli::X, 3, 0),
+                Operation::Feedback(Pauli::Z, 2, 0),
+                Operation::Feedback(Pauli::Z, 2, 1),
+                Operation::Feedback(Pauli::Y, 1, 1),
+            ]
+        )
+    );
+
+    let text = circuit.to_qasm(QasmVersion::V3).unwrap();
+    assert!(
+        text.contains("\nif (c[0]) x q[0];\nif (c[1]) z q[0];\n"),
+        "{}",
+        text
+    );
+    let parsed = Circuit::from_qasm(&text).unwrap();
+    assert_eq!(
+        format!("{:?}", parsed.operations()),
+        format!("{:?}", circuit.operations())
+    );
+    assert!(circuit.to_qasm(QasmVersion::V2).is_err());
+}
+
+#[test]
+fn round_trip_clifford_circuit() {
+    use Operation::*;
+
+    let circuit = Circuit::from(vec![
+        R(0),
+        H(0),
+        S(1),
+        SDag(1),
+        X(2),
+        Y(0),
+        Z(1),
+        SqrtX(2),
+        SqrtXDag(2),
+        T(0),
+        TDag(0),
+        CX(0, 1),
+        CY(1, 2),
+        CZ(2, 0),
+        Swap(0, 2),
+        Tick,
+        M(0, 0.0),
+        M(2, 0.0),
+    ]);
+    for version in [QasmVersion::V2, QasmVersion::V3] {
+        let text = circuit.to_qasm(version).unwrap();
+        let parsed = Circuit::from_qasm(&text).unwrap();
+        assert_eq!(
+            format!("{:?}", parsed.operations()),
+            format!("{:?}", circuit.operations()),
+            "{}",
+            text
+        );
+    }
+
+    let text = circuit.to_qasm(QasmVersion::V2).unwrap();
+    assert!(text.starts_with("OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[3];\ncreg c[2];\n"));
+    assert!(text.contains("\nmeasure q[2] -> c[1];\n"));
+    let text = circuit.to_qasm(QasmVersion::V3).unwrap();
+    assert!(text.contains("\nqubit[3] q;\nbit[2] c;\n"));
+    assert!(text.contains("\nc[1] = measure q[2];\n"));
+
+    let error = Circuit::from(vec![MToZero(0)])
+        .to_qasm(QasmVersion::V2)
+        .unwrap_err();
+    assert!(error.contains("cannot be written in OpenQASM"), "{}", error);
+}
+
+#[test]
+fn export_surface_code_circuit() {
+    let mut code = RotatedSurfaceCode::new_with_noise_model(3, 3, NoiseModel::SD6(0.001), 0);
+    // initializeのMToZeroはOpenQASMで書けないので, syndrome measurementだけを書き出す
+    code.syndrome_measurement();
+    let circuit = code.circuit().clone();
+
+    let path = std::env::temp_dir().join("clifford_qasm_test_surface_code.qasm");
+    circuit.write_qasm_file(&path, QasmVersion::V2).unwrap();
+    let loaded = Circuit::from_qasm_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // REPEATは展開され, X基底の測定などはh, resetに書き換えられる
+    assert_eq!(loaded.measurement_count(), circuit.measurement_count());
+    assert!(loaded
+        .iter()
+        .all(|op| !matches!(op, Operation::Repeat(_, _) | Operation::MR(_, _))));
+
+    // 元の回路で決定的なdetectorは, 書き出した回路でも同じparityになる
+    let mut detectors = Vec::new();
+    let mut count = 0;
+    for op in circuit.flattened().iter() {
+        match op {
+            Operation::Detector(_, lookback) => {
+                detectors.push(lookback.iter().map(|k| count - k).collect::<Vec<_>>())
+            }
+            op if op.is_measurement() => count += 1,
+            _ => (),
+        }
+    }
+    let parities = |circuit: &Circuit| -> Vec<Vec<u8>> {
+        let noiseless: Circuit = circuit
+            .flattened()
+            .iter()
+            .filter_map(Operation::noiseless)
+            .collect();
+        (0..20)
+            .map(|seed| {
+                let m = measurements(&noiseless, seed);
+                detectors
+                    .iter()
+                    .map(|detector| detector.iter().map(|&i| m[i]).sum::<u8>() % 2)
+                    .collect()
+            })
+            .collect()
+    };
+    let original = parities(&circuit);
+    let exported = parities(&loaded);
+    let mut deterministic = 0;
+    for i in 0..detectors.len() {
+        if original.iter().all(|p| p[i] == original[0][i]) {
+            deterministic += 1;
+            assert!(
+                exported.iter().all(|p| p[i] == original[0][i]),
+                "detector {}",
+                i
+            );
+        }
+    }
+    // 1ラウンド目のXスタビライザー以外は決定的
+    assert!(deterministic >= detectors.len() - 4);
+}
@@ -1,32 +1,54 @@
-use rand::{rngs::SmallRng, SeedableRng};
-use std::cell::Cell;
-use std::rc::Rc;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 
-use clifford::simulator::{chp_simulator::CHPSimulator, SimulatorInterface};
+use clifford::noise::noise_model::NoiseType;
+use clifford::simulator::{
+    chp_simulator::CHPSimulator,
+    pauli::{combine_pauli_product, Pauli, PauliString},
+    stabilizer_tableau::StabilizerTableau,
+    state_vector_simulator::StateVectorSimulator,
+    Operation, SimulatorInterface, SimulatorWrapper, Type,
+};
+
+/// 全てのbackend
+const ALL: [Type; 3] = [
+    Type::CHPSimulator,
+    Type::StateVectorSimulator,
+    Type::FrameSimulator,
+];
+
+/// 各backendのsimulatorを同じseedで生成する
+fn simulators(types: &[Type], qubit_num: usize, seed: u64) -> Vec<SimulatorWrapper> {
+    types
+        .iter()
+        .map(|&sim_type| SimulatorWrapper::new(sim_type, qubit_num, SmallRng::seed_from_u64(seed)))
+        .collect()
+}
 
 #[test]
 fn make_bell_state() {
-    let mut count_0 = 0;
-    let loop_num = 10000;
-    for seed in 0..loop_num {
-        let rng = SmallRng::seed_from_u64(seed);
-        let mut sim = CHPSimulator::new(3, rng);
+    for sim_type in ALL {
+        let mut count_0 = 0;
+        let loop_num = 2000;
+        for seed in 0..loop_num {
+            let mut sim = SimulatorWrapper::new(sim_type, 3, SmallRng::seed_from_u64(seed));
 
-        let result = vec![Rc::new(Cell::new(0)); 2];
-        sim.add_h(0);
-        sim.add_cx(0, 1);
-        sim.add_measurement(0, Rc::clone(&result[0]), 0.0);
-        sim.add_measurement(1, Rc::clone(&result[1]), 0.0);
+            sim.add_h(0);
+            sim.add_cx(0, 1);
+            let m0 = sim.add_measurement(0, 0.0);
+            let m1 = sim.add_measurement(1, 0.0);
 
-        sim.run();
+            sim.run();
 
-        assert_eq!(result[0], result[1]);
-        if result[0].get() == 0 {
-            count_0 += 1;
+            let record = sim.measurement_record();
+            assert_eq!(record.get(m0), record.get(m1), "{:?}", sim_type);
+            if record.get(m0) == 0 {
+                count_0 += 1;
+            }
         }
-    }
 
-    println!("{}, {}", count_0 as f32, loop_num as f32);
+        let ratio = count_0 as f32 / loop_num as f32;
+        assert!((0.45..0.55).contains(&ratio), "{:?}: {}", sim_type, ratio);
+    }
 }
 
 #[test]
@@ -42,3 +64,655 @@ fn test_h() {
 
     sim.run();
 }
+
+#[test]
+fn ghz_state_across_words() {
+    // 状態ベクトルでは扱えない大きさ
+    let qubit_num = 130;
+    for seed in 0..20 {
+        for mut sim in simulators(&[Type::CHPSimulator, Type::FrameSimulator], qubit_num, seed) {
+            sim.add_h(0);
+            for i in 1..qubit_num {
+                sim.add_cx(i - 1, i);
+            }
+            for i in 0..qubit_num {
+                sim.add_measurement(i, 0.0);
+            }
+
+            sim.run();
+
+            let bits = sim.measurement_record().bits();
+            assert!(bits.iter().all(|&bit| bit == bits[0]));
+        }
+    }
+}
+
+#[test]
+fn repeated_measurement_is_consistent() {
+    let qubit_num = 70;
+    let mut circuit_rng = SmallRng::seed_from_u64(1);
+
+    for seed in 0..20 {
+        let types = [Type::CHPSimulator, Type::FrameSimulator];
+        for mut sim in simulators(&types, qubit_num, seed) {
+            for _ in 0..500 {
+                let a = circuit_rng.gen_range(0..qubit_num);
+                match circuit_rng.gen_range(0..3) {
+                    0 => sim.add_h(a),
+                    1 => sim.add_s(a),
+                    _ => {
+                        let b = (a + circuit_rng.gen_range(1..qubit_num)) % qubit_num;
+                        sim.add_cx(a, b);
+                    }
+                }
+            }
+
+            for _ in 0..2 {
+                for a in 0..qubit_num {
+                    sim.add_measurement(a, 0.0);
+                }
+            }
+
+            sim.run();
+
+            let (first, second) = sim.measurement_record().bits().split_at(qubit_num);
+            assert_eq!(first, second);
+        }
+    }
+}
+
+#[test]
+fn native_gates_match_decomposition() {
+    let qubit_num = 5;
+    let mut rng = SmallRng::seed_from_u64(3);
+
+    // ランダムなClifford回路で適当な状態を作る
+    let mut random_state = || {
+        let mut tableau = StabilizerTableau::new(qubit_num);
+        for _ in 0..50 {
+            let a = rng.gen_range(0..qubit_num);
+            let b = (a + rng.gen_range(1..qubit_num)) % qubit_num;
+            match rng.gen_range(0..3) {
+                0 => tableau.h(a),
+                1 => tableau.s(a),
+                _ => tableau.cx(a, b),
+            }
+        }
+        tableau
+    };
+
+    type Check = fn(&mut StabilizerTableau, &mut StabilizerTableau);
+    let checks: Vec<(&str, Check)> = vec![
+        ("y", |n, d| {
+            n.y(0);
+            d.z(0);
+            d.x(0);
+        }),
+        ("s_dag", |n, d| {
+            n.s_dag(0);
+            (0..3).for_each(|_| d.s(0));
+        }),
+        ("sqrt_x", |n, d| {
+            n.sqrt_x(0);
+            d.h(0);
+            d.s(0);
+            d.h(0);
+        }),
+        ("sqrt_x_dag", |n, d| {
+            n.sqrt_x_dag(0);
+            d.h(0);
+            (0..3).for_each(|_| d.s(0));
+            d.h(0);
+        }),
+        ("sqrt_y", |n, d| {
+            n.sqrt_y(0);
+            d.z(0);
+            d.h(0);
+        }),
+        ("sqrt_y_dag", |n, d| {
+            n.sqrt_y_dag(0);
+            d.h(0);
+            d.z(0);
+        }),
+        ("cz", |n, d| {
+            n.cz(0, 1);
+            d.h(1);
+            d.cx(0, 1);
+            d.h(1);
+        }),
+        ("cy", |n, d| {
+            n.cy(0, 1);
+            (0..3).for_each(|_| d.s(1));
+            d.cx(0, 1);
+            d.s(1);
+        }),
+        ("swap", |n, d| {
+            n.swap(0, 1);
+            d.cx(0, 1);
+            d.cx(1, 0);
+            d.cx(0, 1);
+        }),
+        ("iswap", |n, d| {
+            n.iswap(0, 1);
+            d.s(0);
+            d.s(1);
+            d.h(1);
+            d.cx(0, 1);
+            d.h(1);
+            d.cx(0, 1);
+            d.cx(1, 0);
+            d.cx(0, 1);
+        }),
+        ("iswap_dag", |n, d| {
+            n.iswap_dag(0, 1);
+            d.cx(0, 1);
+            d.cx(1, 0);
+            d.cx(0, 1);
+            d.h(1);
+            d.cx(0, 1);
+            d.h(1);
+            (0..3).for_each(|_| d.s(0));
+            (0..3).for_each(|_| d.s(1));
+        }),
+        ("xcx", |n, d| {
+            n.xcx(0, 1);
+            d.h(0);
+            d.cx(0, 1);
+            d.h(0);
+        }),
+        ("xcy", |n, d| {
+            n.xcy(0, 1);
+            d.h(0);
+            d.cy(0, 1);
+            d.h(0);
+        }),
+        ("xcz", |n, d| {
+            n.xcz(0, 1);
+            d.cx(1, 0);
+        }),
+        ("ycx", |n, d| {
+            n.ycx(0, 1);
+            d.h(0);
+            d.s(0);
+            d.h(0);
+            d.cx(0, 1);
+            d.h(0);
+            (0..3).for_each(|_| d.s(0));
+            d.h(0);
+        }),
+        ("ycy", |n, d| {
+            n.ycy(0, 1);
+            d.h(0);
+            d.s(0);
+            d.h(0);
+            d.cy(0, 1);
+            d.h(0);
+            (0..3).for_each(|_| d.s(0));
+            d.h(0);
+        }),
+        ("ycz", |n, d| {
+            n.ycz(0, 1);
+            d.cy(1, 0);
+        }),
+    ];
+
+    for (name, check) in checks {
+        for _ in 0..20 {
+            let mut native = random_state();
+            let mut decomposed = native.clone();
+            check(&mut native, &mut decomposed);
+            assert_eq!(native, decomposed, "{}", name);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "two qubit gate needs two different qubits")]
+fn two_qubit_gate_on_same_qubit_is_rejected() {
+    let mut tableau = StabilizerTableau::new(2);
+    tableau.cx(1, 1);
+}
+
+#[test]
+fn measurement_and_reset_in_x_and_y_basis() {
+    for seed in 0..100 {
+        for mut sim in simulators(&ALL, 4, seed) {
+            // |+>, |->, |+i>, |-i>
+            sim.add_reset_x(0);
+            sim.add_reset_x(1);
+            sim.add_z(1);
+            sim.add_reset_y(2);
+            sim.add_reset_y(3);
+            sim.add_x(3);
+            sim.add_measurement_x(0, 0.0);
+            sim.add_measurement_and_reset_x(1, 0.0);
+            sim.add_measurement_y(2, 0.0);
+            sim.add_measurement_and_reset_y(3, 0.0);
+            // MRX, MRYの後は+1固有状態に戻っている
+            sim.add_measurement_x(1, 0.0);
+            sim.add_measurement_y(3, 0.0);
+            // 測定誤差は記録される値のみを反転させる
+            sim.add_measurement_and_reset_x(0, 1.0);
+            sim.add_measurement_x(0, 0.0);
+
+            sim.run();
+
+            assert_eq!(sim.measurement_record().bits(), &[0, 1, 0, 1, 0, 0, 1, 0]);
+        }
+    }
+}
+
+#[test]
+fn pauli_product_measurement_of_bell_state() {
+    for seed in 0..100 {
+        for mut sim in simulators(&ALL, 3, seed) {
+            sim.add_h(0);
+            sim.add_cx(0, 1);
+            sim.add_measurement_pauli_product(vec![(0, Pauli::X), (1, Pauli::X)], 0.0);
+            sim.add_measurement_pauli_product(
+                vec![(0, Pauli::Z), (1, Pauli::Z), (2, Pauli::I)],
+                0.0,
+            );
+            // YY = -(XX)(ZZ)
+            sim.add_measurement_pauli_product(vec![(1, Pauli::Y), (0, Pauli::Y)], 0.0);
+            // X0の結果はランダムだが、XX = +1なのでX1と一致する
+            sim.add_measurement_pauli_product(vec![(0, Pauli::X)], 0.0);
+            sim.add_measurement_x(1, 0.0);
+
+            sim.run();
+
+            let record = sim.measurement_record();
+            assert_eq!(record.get(0), 0);
+            assert_eq!(record.get(1), 0);
+            assert_eq!(record.get(2), 1);
+            assert_eq!(record.get(3), record.get(4));
+        }
+    }
+}
+
+#[test]
+fn pauli_product_with_repeated_qubits() {
+    // X0*Z0 = -iY0は反エルミート
+    assert!(combine_pauli_product(&[(0, Pauli::X), (0, Pauli::Z)]).is_err());
+    assert_eq!(
+        combine_pauli_product(&[(0, Pauli::X), (1, Pauli::Z), (0, Pauli::X)]),
+        Ok((0, vec![(1, Pauli::Z)]))
+    );
+    // (X0*Z0)*(X1*Z1) = (-iY0)(-iY1) = -Y0*Y1
+    assert_eq!(
+        combine_pauli_product(&[(0, Pauli::X), (0, Pauli::Z), (1, Pauli::X), (1, Pauli::Z)]),
+        Ok((1, vec![(0, Pauli::Y), (1, Pauli::Y)]))
+    );
+
+    for seed in 0..20 {
+        for mut sim in simulators(&ALL, 2, seed) {
+            sim.add_h(0);
+            sim.add_cx(0, 1);
+            // X0*X0*Z0*Z1 = Z0*Z1
+            sim.add_measurement_pauli_product(
+                vec![(0, Pauli::X), (0, Pauli::X), (0, Pauli::Z), (1, Pauli::Z)],
+                0.0,
+            );
+            // Bell状態ではY0*Y1 = -1
+            sim.add_measurement_pauli_product(
+                vec![(0, Pauli::X), (0, Pauli::Z), (1, Pauli::X), (1, Pauli::Z)],
+                0.0,
+            );
+            // (Z0*X0)*(X1*Z1) = (iY0)(-iY1) = Y0*Y1
+            sim.add_measurement_pauli_product(
+                vec![(0, Pauli::Z), (1, Pauli::X), (0, Pauli::X), (1, Pauli::Z)],
+                0.0,
+            );
+
+            sim.run();
+
+            let record = sim.measurement_record();
+            assert_eq!(record.get(0), 0);
+            assert_eq!(record.get(1), 0);
+            assert_eq!(record.get(2), 1);
+        }
+    }
+}
+
+#[test]
+fn pauli_channel_and_depolarizing_noise() {
+    for seed in 0..20 {
+        for mut sim in simulators(&ALL, 3, seed) {
+            // |0>にX
+            sim.add_noise(0, NoiseType::PauliChannel1(1.0, 0.0, 0.0));
+            sim.add_measurement(0, 0.0);
+            // |+>にZ
+            sim.add_reset_x(1);
+            sim.add_noise(1, NoiseType::PauliChannel1(0.0, 0.0, 1.0));
+            sim.add_measurement_x(1, 0.0);
+            // Yは|0>と|+>の両方を反転する
+            sim.add_noise(2, NoiseType::PauliChannel1(0.0, 1.0, 0.0));
+            sim.add_measurement(2, 0.0);
+            sim.add_reset_x(2);
+            sim.add_noise(2, NoiseType::PauliChannel1(0.0, 1.0, 0.0));
+            sim.add_measurement_x(2, 0.0);
+
+            sim.run();
+
+            assert!(sim.measurement_record().iter().all(|&bit| bit == 1));
+        }
+    }
+
+    // p = 1の脱分極ではX, Y, Zが1/3ずつ起こるので、Z測定は2/3で反転する
+    for sim_type in ALL {
+        let loop_num = 3000;
+        let mut count_1 = 0;
+        for seed in 0..loop_num {
+            let mut sim = SimulatorWrapper::new(sim_type, 1, SmallRng::seed_from_u64(seed));
+
+            sim.add_noise(0, NoiseType::Depolarizing(1.0));
+            let result = sim.add_measurement(0, 0.0);
+            sim.run();
+
+            count_1 += sim.measurement_record().get(result) as usize;
+        }
+        let ratio = count_1 as f32 / loop_num as f32;
+        assert!((0.6..0.73).contains(&ratio), "{:?}: {}", sim_type, ratio);
+    }
+}
+
+#[test]
+fn two_qubit_pauli_channel_and_depolarizing_noise() {
+    // XZ (index 6)を確定で起こす
+    let mut p = [0.0; 15];
+    p[6] = 1.0;
+    for seed in 0..20 {
+        for mut sim in simulators(&ALL, 2, seed) {
+            sim.add_reset_x(1);
+            sim.add_noise2(0, 1, NoiseType::PauliChannel2(p));
+            sim.add_measurement(0, 0.0);
+            sim.add_measurement_x(1, 0.0);
+
+            sim.run();
+
+            assert_eq!(sim.measurement_record().bits(), &[1, 1]);
+        }
+    }
+
+    // p = 1の2量子ビット脱分極では、15個のPauliのうち8個がZ0を反転する
+    for sim_type in ALL {
+        let loop_num = 3000;
+        let mut count_1 = 0;
+        for seed in 0..loop_num {
+            let mut sim = SimulatorWrapper::new(sim_type, 2, SmallRng::seed_from_u64(seed));
+
+            sim.add_noise2(0, 1, NoiseType::Depolarizing2(1.0));
+            let result = sim.add_measurement(0, 0.0);
+            sim.run();
+
+            count_1 += sim.measurement_record().get(result) as usize;
+        }
+        let ratio = count_1 as f32 / loop_num as f32;
+        assert!((0.47..0.6).contains(&ratio), "{:?}: {}", sim_type, ratio);
+    }
+}
+
+#[test]
+fn measurement_record_indices_and_lookback() {
+    for mut sim in simulators(&ALL, 2, 0) {
+        // indexは回路に追加した順に振られる
+        assert_eq!(sim.add_measurement(0, 0.0), 0);
+        sim.add_x(1);
+        assert_eq!(sim.add_measurement(1, 0.0), 1);
+        assert_eq!(sim.add_measurement_x(0, 1.0), 2);
+        assert_eq!(sim.measurement_count(), 3);
+
+        // 実行ごとに記録はやり直される
+        for _ in 0..2 {
+            sim.reset();
+            sim.run();
+            let record = sim.measurement_record();
+            assert_eq!(record.len(), 3);
+            assert_eq!(record.lookback(1), record.get(2));
+            assert_eq!(record.lookback(2), 1);
+            assert_eq!(record.lookback(3), 0);
+        }
+    }
+}
+
+#[test]
+fn detectors_and_observables() {
+    for seed in 0..20 {
+        for mut sim in simulators(&ALL, 3, seed) {
+            // X測定の結果はランダムだが, 2回の測定のparityは決定的
+            sim.add_measurement_x(0, 0.0);
+            sim.add_measurement_x(0, 0.0);
+            assert_eq!(sim.add_detector(vec![0.0, 0.0], vec![1, 2]), 0);
+            // 2 qubitの繰り返し符号 (ZZを測定), 途中でqubit 2にX error
+            sim.add_cx(1, 2);
+            sim.add_noise(2, NoiseType::PauliChannel1(1.0, 0.0, 0.0));
+            sim.add_measurement(1, 0.0);
+            sim.add_measurement(2, 0.0);
+            sim.add_detector(vec![1.0, 0.0], vec![1, 2]);
+            sim.add_observable_include(0, vec![2]);
+            sim.add_observable_include(1, vec![1]);
+            assert_eq!(sim.detector_count(), 2);
+
+            // 実行ごとに同じ結果になる
+            for _ in 0..2 {
+                sim.reset();
+                sim.run();
+                assert_eq!(sim.detection_events(), &[0, 1]);
+                assert_eq!(sim.observable_flips(), &[0, 1]);
+            }
+        }
+    }
+}
+
+#[test]
+fn feedback_teleportation() {
+    for seed in 0..20 {
+        for mut sim in simulators(&ALL, 3, seed) {
+            // |->をqubit 0から2にテレポートする
+            sim.add_x(0);
+            sim.add_h(0);
+            sim.add_h(1);
+            sim.add_cx(1, 2);
+            sim.add_cx(0, 1);
+            sim.add_h(0);
+            sim.add_measurement(0, 0.0);
+            sim.add_measurement(1, 0.0);
+            sim.add_feedback(Pauli::X, 1, 2);
+            sim.add_feedback(Pauli::Z, 2, 2);
+            sim.add_measurement_x(2, 0.0);
+            // 測定結果を使ったactive reset
+            sim.add_feedback(Pauli::X, 2, 1);
+            sim.add_measurement(1, 0.0);
+
+            for _ in 0..5 {
+                sim.reset();
+                sim.run();
+                let record = sim.measurement_record();
+                assert_eq!(record.lookback(2), 1);
+                assert_eq!(record.lookback(1), 0);
+            }
+        }
+    }
+}
+
+#[test]
+fn feedback_to_missing_measurement_is_rejected() {
+    for mut sim in simulators(&ALL, 2, 0) {
+        sim.add_measurement(0, 0.0);
+        // rec[-2]はまだ存在しないので, 実行前に追加した時点で拒否する
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            sim.add_feedback(Pauli::X, 2, 1);
+        }));
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn anti_hermitian_pauli_product_is_rejected() {
+    // -(X0*Z0)*(X1*Z1) = Y0*Y1 としてまとめた積が保存される
+    let product = [(0, Pauli::X), (0, Pauli::Z), (1, Pauli::X), (1, Pauli::Z)];
+    let operation = Operation::measurement_pauli_product(&product, 1, 0.1).unwrap();
+    assert_eq!(
+        format!("{:?}", operation),
+        format!("{:?}", Operation::MPP(vec![(0, Pauli::Y), (1, Pauli::Y)], 0, 0.1))
+    );
+    assert!(Operation::measurement_pauli_product(&[(0, Pauli::X), (0, Pauli::Z)], 0, 0.0).is_err());
+
+    for mut sim in simulators(&ALL, 2, 0) {
+        // X0*Z0 = -iY0は実行前に追加した時点で拒否する
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            sim.add_measurement_pauli_product(vec![(0, Pauli::X), (0, Pauli::Z)], 0.0);
+        }));
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn peek_does_not_collapse_state() {
+    for seed in 0..20 {
+        let rng = SmallRng::seed_from_u64(seed);
+        let mut sim = CHPSimulator::new(3, rng);
+
+        sim.add_h(0);
+        sim.add_cx(0, 1);
+        sim.add_x(2);
+        sim.run();
+
+        let xx = [(0, Pauli::X), (1, Pauli::X)];
+        let yy = [(0, Pauli::Y), (1, Pauli::Y)];
+        let zz = [(0, Pauli::Z), (1, Pauli::Z)];
+        for _ in 0..2 {
+            assert_eq!(sim.peek_z(0), 0);
+            assert_eq!(sim.peek_x(1), 0);
+            assert_eq!(sim.peek_y(0), 0);
+            assert_eq!(sim.peek_z(2), -1);
+            assert_eq!(sim.peek_observable(&xx), 1);
+            assert_eq!(sim.peek_observable(&yy), -1);
+            assert_eq!(sim.peek_observable(&zz), 1);
+        }
+
+        // 測定すると決定的になる
+        let outcome = sim.measurement(0, 0.0) as i8;
+        assert_eq!(sim.peek_z(0), 1 - 2 * outcome);
+        assert_eq!(sim.peek_z(1), 1 - 2 * outcome);
+        assert_eq!(sim.peek_x(1), 0);
+    }
+}
+
+#[test]
+fn state_vector_peek_observable() {
+    let mut sim = StateVectorSimulator::new(3, SmallRng::seed_from_u64(0));
+    sim.add_h(0);
+    sim.add_cx(0, 1);
+    sim.add_x(2);
+    sim.run();
+
+    for _ in 0..2 {
+        assert_eq!(sim.peek_observable(&[(0, Pauli::X), (1, Pauli::X)]), 1);
+        assert_eq!(sim.peek_observable(&[(0, Pauli::Y), (1, Pauli::Y)]), -1);
+        assert_eq!(sim.peek_observable(&[(0, Pauli::Z), (1, Pauli::Z)]), 1);
+        assert_eq!(sim.peek_observable(&[(0, Pauli::Z)]), 0);
+        assert_eq!(sim.peek_observable(&[(2, Pauli::Z)]), -1);
+    }
+}
+
+#[test]
+fn stabilizers_as_pauli_strings() {
+    let rng = SmallRng::seed_from_u64(0);
+    let mut sim = CHPSimulator::new(3, rng);
+    sim.add_h(0);
+    sim.add_cx(0, 1);
+    sim.add_x(2);
+    sim.run();
+
+    let tableau = sim.stabilizer_tableau();
+    let canonical: Vec<String> = tableau
+        .canonical_stabilizers()
+        .iter()
+        .map(|stab| stab.to_string())
+        .collect();
+    assert_eq!(canonical, ["+XXI", "+ZZI", "-IIZ"]);
+    assert_eq!(tableau.stabilizers().len(), 3);
+    assert_eq!(tableau.destabilizers().len(), 3);
+
+    // stabilizerは全て+1の期待値を持つ
+    for stab in tableau.stabilizers() {
+        let expected = 1 - 2 * stab.sign as i8;
+        let product = stab.to_product();
+        assert_eq!(sim.peek_observable(&product), expected, "{}", stab);
+    }
+
+    let parsed: PauliString = "-XYZ_".parse().unwrap();
+    assert_eq!(parsed.to_string(), "-XYZI");
+    assert_eq!(
+        parsed.to_product(),
+        vec![(0, Pauli::X), (1, Pauli::Y), (2, Pauli::Z)]
+    );
+    assert!("+XA".parse::<PauliString>().is_err());
+}
+
+#[test]
+fn same_state_ignores_choice_of_generators() {
+    let ghz = |decomposition: fn(&mut StabilizerTableau)| {
+        let mut tableau = StabilizerTableau::new(3);
+        tableau.h(0);
+        decomposition(&mut tableau);
+        tableau
+    };
+    let chain = ghz(|t| {
+        t.cx(0, 1);
+        t.cx(1, 2);
+    });
+    let star = ghz(|t| {
+        t.cx(0, 2);
+        t.cx(0, 1);
+    });
+    let flipped = ghz(|t| {
+        t.cx(0, 1);
+        t.cx(1, 2);
+        t.z(2);
+    });
+
+    // 生成元やdestabilizerが違っても同じ状態
+    assert_ne!(chain.stabilizers(), star.stabilizers());
+    assert!(chain.same_state(&star));
+    assert_eq!(chain.canonical_stabilizers(), star.canonical_stabilizers());
+    // 符号が違う
+    assert!(!chain.same_state(&flipped));
+
+    // ランダムな回路でも, 同じstabilizer群にgateを作用させれば一致する
+    let mut rng = SmallRng::seed_from_u64(5);
+    for _ in 0..20 {
+        let mut a = StabilizerTableau::new(6);
+        for _ in 0..60 {
+            let q = rng.gen_range(0..6);
+            let r = (q + rng.gen_range(1..6)) % 6;
+            match rng.gen_range(0..3) {
+                0 => a.h(q),
+                1 => a.s(q),
+                _ => a.cx(q, r),
+            }
+        }
+        // SWAPを2回作用させると元に戻る
+        let mut b = a.clone();
+        b.swap(1, 4);
+        b.swap(4, 1);
+        assert!(a.same_state(&b));
+        // Y = iXZなので, X, Z, Yを続けて作用させても位相を除いて変わらない
+        b.x(3);
+        b.z(3);
+        b.y(3);
+        assert!(a.same_state(&b));
+
+        let canonical = a.canonical_stabilizers();
+        let mut c = a.clone();
+        for stab in canonical.iter() {
+            let product = stab.to_product();
+            assert!(!product.is_empty());
+            // 自分自身のstabilizerで測定しても状態は変わらない
+            let (x, z) = c.pack_pauli_product(&product);
+            assert!(c.random_pauli_measurement_pivot(&x, &z).is_none());
+            assert_eq!(c.deterministic_pauli_measurement(&x, &z), stab.sign);
+        }
+    }
+}
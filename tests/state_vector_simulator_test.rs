@@ -0,0 +1,110 @@
+use rand::{rngs::SmallRng, SeedableRng};
+
+use clifford::noise::noise_model::NoiseModel;
+use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+use clifford::simulator::{
+    chp_simulator::{self, CHPSimulator},
+    state_vector_simulator::StateVectorSimulator,
+    Operation, SimulatorInterface, Type,
+};
+
+mod common;
+use common::simulation::{add_repetition_code, assert_rates_close, detection_event_rates};
+
+/// 各測定結果が1になる割合
+fn rates(records: &[Vec<u8>]) -> Vec<f32> {
+    (0..records[0].len())
+        .map(|i| records.iter().map(|r| r[i] as f32).sum::<f32>() / records.len() as f32)
+        .collect()
+}
+
+fn sample(sim: &mut dyn SimulatorInterface, shots: usize) -> Vec<Vec<u8>> {
+    (0..shots)
+        .map(|_| {
+            sim.reset();
+            sim.run();
+            sim.measurement_record().bits().to_vec()
+        })
+        .collect()
+}
+
+#[test]
+fn t_gate() {
+    let shots = 20000;
+    let mut sim = StateVectorSimulator::new(2, SmallRng::seed_from_u64(0));
+
+    // H T H|0>で1が出る確率はsin^2(pi/8)
+    sim.add_h(0);
+    sim.add_t(0);
+    sim.add_h(0);
+    sim.add_measurement(0, 0.0);
+    // T T = S, T T^dag = I
+    sim.add_reset_x(1);
+    sim.add_t(1);
+    sim.add_t(1);
+    sim.add_s_dag(1);
+    sim.add_t(1);
+    sim.add_t_dag(1);
+    sim.add_measurement_x(1, 0.0);
+
+    let records = sample(&mut sim, shots);
+    let rate = rates(&records);
+    let expected = (std::f32::consts::PI / 8.0).sin().powi(2);
+    assert!(
+        (rate[0] - expected).abs() < 0.01,
+        "{} {}",
+        rate[0],
+        expected
+    );
+    assert_eq!(rate[1], 0.0);
+
+    // 状態は規格化されている
+    let norm: f64 = sim.state().iter().map(|a| a.norm_sqr()).sum();
+    assert!((norm - 1.0).abs() < 1e-9);
+}
+
+#[test]
+#[should_panic(expected = "is not a Clifford gate")]
+fn chp_rejects_t_gate() {
+    // T gateを含む回路はCHPでは実行できない
+    chp_simulator::reference_sample(1, &[Operation::H(0), Operation::T(0)], 0);
+}
+
+#[test]
+fn detection_event_rates_match_chp() {
+    let round = 3;
+    let p = 0.05;
+    let shots = 10000;
+
+    let mut chp = CHPSimulator::new(5, SmallRng::seed_from_u64(0));
+    let mut state_vector = StateVectorSimulator::new(5, SmallRng::seed_from_u64(0));
+    add_repetition_code(&mut chp, round, p);
+    add_repetition_code(&mut state_vector, round, p);
+
+    assert_rates_close(
+        &detection_event_rates(&mut chp, shots),
+        &detection_event_rates(&mut state_vector, shots),
+        0.02,
+    );
+}
+
+#[test]
+fn surface_code_on_state_vector_simulator() {
+    let distance = 3;
+    let mut code = RotatedSurfaceCode::new_with_simulator(
+        distance,
+        1,
+        NoiseModel::Phenomenological(0.0, 0.0),
+        Type::StateVectorSimulator,
+        0,
+    );
+    code.initialize();
+    code.syndrome_measurement();
+
+    for _ in 0..5 {
+        code.reset();
+        code.run();
+        code.decode_mwpm(1);
+        assert_eq!(code.logical_value(), 0);
+    }
+}
@@ -0,0 +1,299 @@
+use rand::{rngs::SmallRng, SeedableRng};
+
+use clifford::circuit::Circuit;
+use clifford::noise::noise_model::NoiseModel;
+use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+use clifford::simulator::{
+    chp_simulator::CHPSimulator, frame_simulator::FrameSimulator, pauli::Pauli, Operation,
+    SimulatorInterface,
+};
+
+/// Stimが生成する形式の距離3の繰り返し符号 (data: 0, 2, 4, ancilla: 1, 3)
+const REPETITION_CODE: &str = "\
+# generated circuit
+QUBIT_COORDS(0) 0
+R 0 1 2 3 4
+TICK
+DEPOLARIZE1(0.001) 0 1 2 3 4
+CX 0 1 2 3
+DEPOLARIZE2(0.001) 0 1 2 3
+TICK
+CX 2 1 4 3
+TICK
+X_ERROR(0.001) 1 3
+MR 1 3
+DETECTOR(1, 0) rec[-2]
+DETECTOR(3, 0) rec[-1]
+REPEAT 2 {
+    TICK
+    CNOT 0 1 2 3
+    TICK
+    cx 2 1 4 3
+    TICK
+    MR(0.001) 1 3
+    SHIFT_COORDS(0, 1)
+    DETECTOR(1, 0) rec[-2] rec[-4]
+    DETECTOR(3, 0) rec[-1] rec[-3]
+}
+M 0 2 4  # data qubit
+DETECTOR(1, 1) rec[-2] rec[-3] rec[-5]
+DETECTOR(3, 1) rec[-1] rec[-2] rec[-4]
+OBSERVABLE_INCLUDE(0) rec[-1]
+";
+
+/// Stimが読めるinstructionの名前
+const STIM_NAMES: [&str; 38] = [
+    "H", "S", "X", "Y", "Z", "S_DAG", "SQRT_X", "SQRT_X_DAG", "SQRT_Y", "SQRT_Y_DAG", "CX", "CY",
+    "CZ", "SWAP", "ISWAP", "ISWAP_DAG", "XCX", "XCY", "XCZ", "YCX", "YCY", "YCZ", "M", "MR", "MX",
+    "MY", "MRX", "MRY", "R", "RX", "RY", "MPP", "DEPOLARIZE1", "DEPOLARIZE2", "PAULI_CHANNEL_1",
+    "PAULI_CHANNEL_2", "DETECTOR", "OBSERVABLE_INCLUDE",
+];
+
+/// 書き出した各行がStimのinstruction (またはTICK, REPEATブロック) であることを確かめる
+fn assert_stim_names_only(text: &str) {
+    for line in text.lines().map(str::trim) {
+        let name = line.split([' ', '(']).next().unwrap();
+        assert!(
+            STIM_NAMES.contains(&name) || name == "TICK" || name == "REPEAT" || name == "}",
+            "{} is not a Stim instruction",
+            line
+        );
+    }
+}
+
+fn round_trip(circuit: &Circuit) {
+    let text = circuit.to_stim().unwrap();
+    assert_stim_names_only(&text);
+    let parsed = Circuit::from_stim(&text).unwrap();
+    assert_eq!(
+        format!("{:?}", parsed.operations()),
+        format!("{:?}", circuit.operations()),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn parse_stim_circuit() {
+    let circuit = Circuit::from_stim(REPETITION_CODE).unwrap();
+    assert_eq!(circuit.qubit_count(), 5);
+    assert_eq!(circuit.measurement_count(), 9);
+    assert_eq!(circuit.detector_count(), 8);
+    assert_eq!(circuit.observable_count(), 1);
+    let counts = circuit.operation_counts();
+    assert_eq!(counts["R"], 5);
+    assert_eq!(counts["CX"], 12);
+    assert_eq!(counts["PAULI_CHANNEL_1"], 2);
+    assert_eq!(counts["TICK"], 9);
+    assert!(matches!(
+        circuit.operations()[25],
+        Operation::Repeat(2, ref body) if body.len() == 11
+    ));
+
+    // ノイズのない回路ではdetection eventは起きない
+    let noiseless: Circuit = circuit.iter().filter_map(Operation::noiseless).collect();
+    let mut sims: Vec<Box<dyn SimulatorInterface>> = vec![
+        Box::new(CHPSimulator::new(5, SmallRng::seed_from_u64(0))),
+        Box::new(FrameSimulator::new(5, SmallRng::seed_from_u64(0))),
+    ];
+    for sim in sims.iter_mut() {
+        sim.add_circuit(&noiseless);
+        assert_eq!(sim.detector_count(), 8);
+        for _ in 0..10 {
+            sim.reset();
+            sim.run();
+            assert!(sim.detection_events().iter().all(|&event| event == 0));
+            assert_eq!(sim.observable_flips(), &[0]);
+        }
+    }
+
+    round_trip(&circuit);
+}
+
+#[test]
+fn round_trip_every_operation() {
+    use Operation::*;
+
+    let mut body = Circuit::from(vec![
+        MR(1, 0.01),
+        Detector(vec![1.0, 0.5], vec![1, 2]),
+        Tick,
+    ]);
+    body.push(Repeat(3, Circuit::from(vec![X(0), Tick])));
+    let mut p2 = [0.0; 15];
+    p2[4] = 0.125;
+    let circuit = Circuit::from(vec![
+        H(0),
+        S(1),
+        X(0),
+        Z(0),
+        Y(2),
+        SDag(0),
+        SqrtX(0),
+        SqrtXDag(1),
+        SqrtY(0),
+        SqrtYDag(2),
+        CX(0, 1),
+        CZ(1, 2),
+        CY(2, 0),
+        Swap(0, 1),
+        ISwap(0, 2),
+        ISwapDag(1, 2),
+        XCX(0, 1),
+        XCY(0, 1),
+        XCZ(0, 1),
+        YCX(0, 1),
+        YCY(0, 1),
+        YCZ(0, 1),
+        M(0, 0.0),
+        MR(1, 0.001),
+        MX(2, 0.0),
+        MY(0, 0.25),
+        R(0),
+        RX(1),
+        RY(2),
+        MRX(0, 0.0),
+        MRY(1, 0.0),
+        MPP(vec![(0, Pauli::X), (1, Pauli::Y), (2, Pauli::Z)], 0, 0.0),
+        MPP(vec![(2, Pauli::Z)], 1, 0.01),
+        Feedback(Pauli::X, 1, 2),
+        Feedback(Pauli::Z, 3, 0),
+        Depolarizing(0, 0.001),
+        PauliChannel1(1, 0.1, 0.0, 0.2),
+        Depolarizing2(0, 2, 0.003),
+        PauliChannel2(1, 2, p2),
+        Tick,
+        Repeat(100, body),
+        Detector(vec![], vec![1]),
+        ObservableInclude(2, vec![3, 1]),
+    ]);
+    round_trip(&circuit);
+
+    let text = circuit.to_stim().unwrap();
+    assert!(text.contains("\nMPP X0*Y1*Z2\n"));
+    assert!(text.contains("\nMPP(0.01) !Z2\n"));
+    assert!(text.contains("\nMR(0.001) 1\n"));
+    assert!(text.contains("\nREPEAT 100 {\n    MR(0.01) 1\n"));
+    assert!(text.contains("\n        X 0\n"));
+    assert!(text.contains("\nOBSERVABLE_INCLUDE(2) rec[-3] rec[-1]\n"));
+    assert!(text.contains("\nCX rec[-1] 2\nCZ rec[-3] 0\n"));
+}
+
+#[test]
+fn operations_without_stim_name_are_rejected() {
+    for op in [Operation::T(0), Operation::TDag(1), Operation::MToZero(0)] {
+        let circuit = Circuit::from(vec![Operation::H(0), op.clone()]);
+        let error = circuit.to_stim().unwrap_err();
+        assert!(error.contains("cannot be written in Stim"), "{:?}: {}", op, error);
+
+        let path = std::env::temp_dir().join("clifford_stim_test_rejected.stim");
+        let error = circuit.write_stim_file(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    // 恒等写像のFeedbackは書き出さない
+    let circuit = Circuit::from(vec![
+        Operation::M(0, 0.0),
+        Operation::Feedback(Pauli::I, 1, 1),
+        Operation::Feedback(Pauli::X, 1, 1),
+    ]);
+    assert_eq!(circuit.to_stim().unwrap(), "M 0\nCX rec[-1] 1\n");
+}
+
+#[test]
+fn stim_aliases_and_multiple_targets() {
+    let circuit = Circuit::from_stim(
+        "H_XZ 0 1\nZCZ 0 1 2 3\nMZ(0.1) 0 1\nMPP X0 * X1 Z2*Z3\nZ_ERROR(0.2) 3\nI 0\n\
+         CNOT rec[-1] 2 0 1\nCZ 3 rec[-2] rec[-4] 1\nZCY rec[-3] 0\n",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", circuit.operations()),
+        format!(
+            "{:?}",
+            [
+                Operation::H(0),
+                Operation::H(1),
+                Operation::CZ(0, 1),
+                Operation::CZ(2, 3),
+                Operation::M(0, 0.1),
+                Operation::M(1, 0.1),
+                Operation::MPP(vec![(0, Pauli::X), (1, Pauli::X)], 0, 0.0),
+                Operation::MPP(vec![(2, Pauli::Z), (3, Pauli::Z)], 0, 0.0),
+                Operation::PauliChannel1(3, 0.0, 0.0, 0.2),
+                Operation::Feedback(Pauli::X, 1, 2),
+                Operation::CX(0, 1),
+                Operation::Feedback(Pauli::Z, 2, 3),
+                Operation::Feedback(Pauli::Z, 4, 1),
+                Operation::Feedback(Pauli::Y, 3, 0),
+            ]
+        )
+    );
+}
+
+#[test]
+fn parse_errors_have_line_numbers() {
+    let cases = [
+        ("H 0\nCX 0 1 2\n", "line 2:"),
+        (
+            "H 0\n\n# comment\nFOO 1\n",
+            "line 4: unsupported operation FOO",
+        ),
+        (
+            "M 0\nDETECTOR rec[-1] 3\n",
+            "line 2: invalid measurement record target '3'",
+        ),
+        (
+            "DEPOLARIZE1 0\n",
+            "line 1: DEPOLARIZE1 takes 1 arguments, got 0",
+        ),
+        ("H(0.1 0\n", "line 1: missing ')'"),
+        ("M !0\n", "line 1: inverted target '!0' is not supported"),
+        ("H 0\n}\n", "line 2: '}' without REPEAT"),
+        ("REPEAT 3 {\nH 0\n", "line 1: REPEAT block is not closed"),
+        ("REPEAT x {\n}\n", "line 1: invalid repetition count 'x'"),
+        ("MPP X0*W1\n", "line 1: invalid Pauli target 'W1'"),
+        (
+            "MPP X0*Z0\n",
+            "line 1: Pauli product X0*Z0 is anti-Hermitian",
+        ),
+        (
+            "M 0\nSWAP rec[-1] 1\n",
+            "line 2: SWAP cannot be controlled by a measurement record",
+        ),
+        (
+            "M 0\nCX 1 rec[-1]\n",
+            "line 2: invalid feedback targets '1 rec[-1]'",
+        ),
+    ];
+    for (text, expected) in cases {
+        let error = Circuit::from_stim(text).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(
+            error.to_string().starts_with(expected),
+            "{:?}: {}",
+            text,
+            error
+        );
+    }
+}
+
+#[test]
+fn export_surface_code_circuit() {
+    let mut code = RotatedSurfaceCode::new_with_noise_model(3, 5, NoiseModel::SD6(0.001), 0);
+    code.initialize();
+    code.syndrome_measurement();
+    let circuit = code.circuit().clone();
+    let text = circuit.to_stim().unwrap();
+    assert_stim_names_only(&text);
+    assert!(text.starts_with("R "));
+    assert!(text.contains("MPP "));
+    round_trip(&circuit);
+
+    let path = std::env::temp_dir().join("clifford_stim_test_surface_code.stim");
+    circuit.write_stim_file(&path).unwrap();
+    let loaded = Circuit::from_stim_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.detector_count(), circuit.detector_count());
+    assert_eq!(loaded.operation_counts(), circuit.operation_counts());
+}
@@ -1,4 +1,5 @@
 use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+use clifford::simulator::Operation;
 
 #[test]
 fn gen_qec_code() {
@@ -15,10 +16,7 @@ fn gen_qec_code() {
 
         let result = code.classical_register();
 
-        let result_vec: Vec<Vec<u8>> = result
-            .iter()
-            .map(|row| row.iter().map(|value| value.get()).collect())
-            .collect();
+        let result_vec: Vec<Vec<u8>> = result.clone();
 
         let row_sum: Vec<u8> = result_vec
             .clone()
@@ -61,6 +59,7 @@ fn test_decode_scheme() {
     let mut code = RotatedSurfaceCode::new(distance, distance + 1, 0.01,0.01,  seed);
 
     code.initialize();
+    code.syndrome_measurement();
     code.run();
     
     code.decode_mwpm(distance);
@@ -68,3 +67,31 @@ fn test_decode_scheme() {
 
     println!("{}", ans);
 }
+
+#[test]
+fn logical_value_comes_from_recorded_data_measurement() {
+    let distance = 3;
+    let mut code = RotatedSurfaceCode::new(distance, distance, 0.0, 0.0, 0);
+    code.initialize();
+    code.syndrome_measurement();
+
+    // 最後にdata qubitを測定し, logical Zの上の測定結果をobservable 0に加える
+    let circuit = code.circuit();
+    assert_eq!(circuit.observable_count(), 1);
+    let ops: Vec<_> = circuit.iter().collect();
+    match ops.last() {
+        Some(Operation::ObservableInclude(0, lookback)) => assert_eq!(lookback.len(), distance),
+        other => panic!("last operation must be OBSERVABLE_INCLUDE(0): {:?}", other),
+    }
+    let data_measurements = ops
+        .iter()
+        .rev()
+        .skip(1)
+        .take_while(|op| matches!(op, Operation::M(_, _)))
+        .count();
+    assert_eq!(data_measurements, distance * distance);
+
+    code.run();
+    code.decode_mwpm(distance);
+    assert_eq!(code.logical_value(), 0);
+}
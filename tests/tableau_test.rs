@@ -0,0 +1,124 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use clifford::simulator::{
+    pauli::PauliString,
+    tableau::{circuits_equivalent, Tableau},
+    Operation,
+};
+
+fn pauli(s: &str) -> PauliString {
+    s.parse().unwrap()
+}
+
+fn random_circuit(qubit_num: usize, len: usize, rng: &mut SmallRng) -> Vec<Operation> {
+    (0..len)
+        .map(|_| {
+            let a = rng.gen_range(0..qubit_num);
+            let b = (a + rng.gen_range(1..qubit_num)) % qubit_num;
+            match rng.gen_range(0..10) {
+                0 => Operation::H(a),
+                1 => Operation::S(a),
+                2 => Operation::SqrtY(a),
+                3 => Operation::SqrtXDag(a),
+                4 => Operation::Y(a),
+                5 => Operation::CX(a, b),
+                6 => Operation::CY(a, b),
+                7 => Operation::ISwap(a, b),
+                8 => Operation::XCZ(a, b),
+                _ => Operation::YCY(a, b),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn conjugation_by_gates() {
+    let h = Tableau::from_operations(1, &[Operation::H(0)]).unwrap();
+    assert_eq!(h.x_image(0), &pauli("+Z"));
+    assert_eq!(h.conjugate(&pauli("+Y")), pauli("-Y"));
+
+    let cx = Tableau::from_operations(2, &[Operation::CX(0, 1)]).unwrap();
+    assert_eq!(cx.x_image(0), &pauli("+XX"));
+    assert_eq!(cx.z_image(1), &pauli("+ZZ"));
+    assert_eq!(cx.conjugate(&pauli("-YI")), pauli("-YX"));
+    assert_eq!(cx.conjugate(&pauli("+YZ")), pauli("+XY"));
+
+    // 空の回路は恒等写像
+    assert_eq!(
+        Tableau::from_operations(3, &[]).unwrap(),
+        Tableau::identity(3)
+    );
+}
+
+#[test]
+fn composition_and_inverse() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let qubit_num = 5;
+    for _ in 0..20 {
+        let a = random_circuit(qubit_num, 40, &mut rng);
+        let b = random_circuit(qubit_num, 40, &mut rng);
+        let ta = Tableau::from_operations(qubit_num, &a).unwrap();
+        let tb = Tableau::from_operations(qubit_num, &b).unwrap();
+
+        let ab: Vec<_> = a.iter().chain(b.iter()).cloned().collect();
+        assert_eq!(
+            Tableau::from_operations(qubit_num, &ab).unwrap(),
+            ta.then(&tb)
+        );
+
+        let identity = Tableau::identity(qubit_num);
+        assert_eq!(ta.then(&ta.inverse()), identity);
+        assert_eq!(ta.inverse().then(&ta), identity);
+        assert_eq!(ta.inverse().inverse(), ta);
+    }
+}
+
+#[test]
+fn equivalent_circuits() {
+    use Operation::*;
+
+    let cases: Vec<(Vec<Operation>, Vec<Operation>, bool)> = vec![
+        (vec![Swap(0, 1)], vec![CX(0, 1), CX(1, 0), CX(0, 1)], true),
+        (vec![H(0), S(0), S(0), H(0)], vec![X(0)], true),
+        (vec![H(0), S(0), H(0)], vec![SqrtX(0)], true),
+        (vec![S(0), S(0)], vec![Z(0)], true),
+        (vec![CZ(0, 1)], vec![CZ(1, 0)], true),
+        (vec![CX(0, 1)], vec![CX(1, 0)], false),
+        (vec![X(0)], vec![Z(0)], false),
+        (vec![S(0)], vec![SDag(0)], false),
+        // ノイズと注釈は無視する
+        (
+            vec![H(0), Depolarizing(0, 0.1), Detector(vec![], vec![])],
+            vec![H(0)],
+            true,
+        ),
+    ];
+    for (a, b, expected) in cases {
+        assert_eq!(circuits_equivalent(&a, &b), Ok(expected), "{:?} {:?}", a, b);
+    }
+}
+
+#[test]
+fn syndrome_extraction_schedule() {
+    use Operation::CX;
+
+    // X stabilizer (ancilla 4)とZ stabilizer (ancilla 5)がdata 0, 1を共有する
+    let sequential = vec![CX(4, 0), CX(4, 1), CX(0, 5), CX(1, 5)];
+    // 共有するqubitに同じ順で作用すれば同じユニタリになる
+    let interleaved = vec![CX(4, 0), CX(0, 5), CX(4, 1), CX(1, 5)];
+    // 順番が逆だとCX(4, 5)が余分に生じる
+    let hooked = vec![CX(0, 5), CX(4, 0), CX(4, 1), CX(1, 5)];
+
+    assert_eq!(circuits_equivalent(&sequential, &interleaved), Ok(true));
+    assert_eq!(circuits_equivalent(&sequential, &hooked), Ok(false));
+    let mut fixed = hooked.clone();
+    fixed.push(CX(4, 5));
+    assert_eq!(circuits_equivalent(&sequential, &fixed), Ok(true));
+}
+
+#[test]
+fn non_clifford_or_non_unitary_operations_are_rejected() {
+    assert!(circuits_equivalent(&[Operation::M(0, 0.0)], &[]).is_err());
+    assert!(circuits_equivalent(&[Operation::T(0)], &[Operation::T(0)]).is_err());
+    assert!(Tableau::from_operations(1, &[Operation::CX(0, 1)]).is_err());
+}
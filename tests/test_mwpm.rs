@@ -1,7 +1,7 @@
-use clifford::decoder::mwpm;
+use clifford::decoder::{mwpm::{self, Mwpm}, Decoder};
 use clifford::qubit_graph::ungraph::UnGraph;
-use std::cell::Cell;
-use std::rc::Rc;
+
+type Coord = (i32, i32, i32);
 
 #[test]
 fn test_local_dijkstra() {
@@ -23,7 +23,7 @@ fn test_local_dijkstra() {
     }
     graph.set_edge_weight(&((5, 5, 0), (4, 4, 0)), 1 as f32);
     for i in 1..11 {
-        graph.set_classical_register((i, i, 0), Rc::new(Cell::new(1)));
+        graph.set_classical_register((i, i, 0), 1);
     }
     let ans = mwpm::local_dijkstra(&graph, 5, &(1, 1, 0));
 
@@ -38,3 +38,141 @@ fn test_local_dijkstra() {
         ]
     );
 }
+
+#[test]
+fn decode_batch_predicts_observable() {
+    // boundary - a - b - c - boundary の1次元のgraph
+    let boundary = [(-1, 0, 0), (7, 0, 0)];
+    let nodes = [(1, 0, 0), (3, 0, 0), (5, 0, 0)];
+    let edges = vec![
+        (boundary[0], nodes[0]),
+        (nodes[0], nodes[1]),
+        (nodes[1], nodes[2]),
+        (nodes[2], boundary[1]),
+    ];
+    let mut graph = UnGraph::from_edges(&edges, 0, 0);
+    graph.set_edges_weight(&edges, 1.0);
+    graph.add_edge_from(&(boundary[0], boundary[1]));
+    graph.set_edge_weight(&(boundary[0], boundary[1]), 0.0);
+    for &node in boundary.iter() {
+        graph.set_is_boundary(node, true);
+        graph.set_classical_register(node, 0);
+    }
+    for &node in nodes.iter() {
+        graph.set_is_boundary(node, false);
+        graph.set_classical_register(node, 0);
+    }
+
+    let mut decoder = Mwpm::new(4);
+    graph.flip_classical_register(&nodes[0], 1);
+    graph.flip_boundary_defect();
+    assert_eq!(graph.get_register(&boundary[0]), Some(1));
+    assert_eq!(decoder.decode(&graph), vec![(0, 0)]);
+
+    // detectorのindexはnodesの順
+    let detectors: Vec<_> = nodes.iter().enumerate().map(|(id, &node)| (node, id)).collect();
+    decoder.configure(&graph, &detectors, &[(0, 0)]);
+    let detection_events = vec![
+        vec![1, 0, 0],
+        vec![0, 0, 1],
+        vec![1, 1, 0],
+        vec![0, 1, 1],
+        vec![0, 0, 0],
+    ];
+    let prediction = decoder.decode_batch(&detection_events);
+    assert_eq!(prediction, vec![1, 0, 0, 0, 0]);
+    // 設定したgraphは変更しない
+    assert_eq!(graph.get_register(&nodes[0]), Some(1));
+
+    let mut unconfigured = Mwpm::new(4);
+    let result = std::panic::catch_unwind(move || unconfigured.decode_batch(&detection_events));
+    assert!(result.is_err());
+}
+
+/// correctionのedgeの端点を反転させて, 残ったdefectを返す
+fn remaining_defects(
+    edges: &[(Coord, Coord)],
+    defects: &[Coord],
+    correction: &[(i32, i32)],
+) -> Vec<Coord> {
+    let mut remaining = defects.to_vec();
+    for qubit in correction {
+        let &(u, v) = edges
+            .iter()
+            .find(|&&edge| UnGraph::edge_to_qubit(edge) == *qubit)
+            .unwrap();
+        for n in [u, v] {
+            match remaining.iter().position(|&d| d == n) {
+                Some(i) => {
+                    remaining.remove(i);
+                }
+                None => remaining.push(n),
+            }
+        }
+    }
+    remaining.sort();
+    remaining
+}
+
+#[test]
+fn exact_mwpm_without_perfect_local_matching() {
+    // 中心のdefectに3つのdefectが繋がったstar graph
+    // m = 1では全てのleafが中心とだけ結ばれ, perfect matchingが存在しない
+    let center = (3, 3, 0);
+    let leaves = [(1, 1, 0), (5, 1, 0), (5, 5, 0)];
+    let boundary = (1, 5, 0);
+    let mut edges: Vec<_> = leaves.iter().map(|&leaf| (center, leaf)).collect();
+    edges.push((center, boundary));
+    let mut graph = UnGraph::from_edges(&edges, 0, 0);
+    graph.set_edges_weight(&edges[..3], 1.0);
+    graph.set_edge_weight(&edges[3], 10.0);
+    graph.set_is_boundary(boundary, true);
+    graph.set_classical_register(boundary, 0);
+    graph.set_is_boundary(center, false);
+    graph.set_classical_register(center, 1);
+    for &leaf in leaves.iter() {
+        graph.set_is_boundary(leaf, false);
+        graph.set_classical_register(leaf, 1);
+    }
+    let mut defects = vec![center];
+    defects.extend(leaves);
+    defects.sort();
+
+    for decoder in [&mut Mwpm::new(1), &mut Mwpm::exact()] {
+        let correction = decoder.decode(&graph);
+        // boundaryは使わずに3本のedgeで全てのdefectを消す
+        assert_eq!(correction.len(), 3, "{:?}", correction);
+        assert!(remaining_defects(&edges, &defects, &correction).is_empty());
+    }
+
+    // defectが1つならvirtual boundaryとmatchingする
+    graph.reset_register();
+    graph.flip_classical_register(&leaves[0], 1);
+    let correction = Mwpm::exact().decode(&graph);
+    assert_eq!(
+        remaining_defects(&edges, &[leaves[0]], &correction),
+        vec![boundary]
+    );
+}
+
+#[test]
+fn exact_mwpm_decodes_surface_code() {
+    use clifford::qec_code::rotated_surface_code::RotatedSurfaceCode;
+
+    let distance = 3;
+    let mut code = RotatedSurfaceCode::new(distance, distance, 0.01, 0.01, 0);
+    code.initialize();
+    code.syndrome_measurement();
+
+    let mut exact = Mwpm::exact();
+    let mut error_num = 0;
+    for _ in 0..200 {
+        code.reset();
+        code.run();
+        code.decode_with(&mut exact);
+        if code.logical_value_with(&mut exact) != 0 {
+            error_num += 1;
+        }
+    }
+    assert!(error_num <= 4, "{}", error_num);
+}
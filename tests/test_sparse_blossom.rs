@@ -0,0 +1,113 @@
+use rand::{rngs::SmallRng, SeedableRng};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+use clifford::decoder::{sparse_blossom, sparse_blossom::SparseBlossom};
+use clifford::qubit_graph::ungraph::UnGraph;
+
+mod common;
+
+type Coord = (i32, i32, i32);
+
+fn dijkstra(graph: &UnGraph, s: Coord) -> HashMap<Coord, f32> {
+    let mut distance = HashMap::new();
+    let mut heap = BinaryHeap::new();
+    heap.push((std::cmp::Reverse(0), s));
+    while let Some((std::cmp::Reverse(d), u)) = heap.pop() {
+        if distance.contains_key(&u) {
+            continue;
+        }
+        distance.insert(u, d as f32 / 1e6);
+        for &v in graph.neighbors(&u).unwrap() {
+            let w = (graph.edge_weight(&(u, v)).unwrap() * 1e6).round() as i64;
+            heap.push((std::cmp::Reverse(d + w), v));
+        }
+    }
+    distance
+}
+
+/// 全ての組み合わせを調べた最小重み完全マッチング (boundaryとも自由にmatchingできる)
+fn brute_force_weight(graph: &UnGraph, defects: &[Coord]) -> f32 {
+    let distance: Vec<_> = defects.iter().map(|&d| dijkstra(graph, d)).collect();
+    let to_boundary: Vec<f32> = distance
+        .iter()
+        .map(|dist| {
+            dist.iter()
+                .filter(|(coord, _)| graph.is_boundary(coord).unwrap())
+                .map(|(_, &d)| d)
+                .fold(f32::MAX, f32::min)
+        })
+        .collect();
+
+    let n = defects.len();
+    let mut best = vec![f32::MAX; 1 << n];
+    best[0] = 0.0;
+    for set in 1..1usize << n {
+        let i = set.trailing_zeros() as usize;
+        let rest = set & !(1 << i);
+        let mut w = best[rest] + to_boundary[i];
+        for j in (i + 1..n).filter(|&j| rest & (1 << j) != 0) {
+            w = w.min(best[rest & !(1 << j)] + distance[i][&defects[j]]);
+        }
+        best[set] = w;
+    }
+    best[(1 << n) - 1]
+}
+
+#[test]
+fn matching_is_minimum_weight() {
+    let mut rng = SmallRng::seed_from_u64(1);
+    let mut tested = 0;
+    while tested < 200 {
+        let graph = common::decoding::random_lattice(4, 3, 3, 0.15, &mut rng);
+        let mut defects: Vec<_> = graph
+            .iter_classical_register()
+            .filter(|&(_, &defect)| defect == 1)
+            .map(|(&coord, _)| coord)
+            .collect();
+        defects.sort();
+        if defects.len() > 12 {
+            continue;
+        }
+        tested += 1;
+
+        let paths = sparse_blossom::matched_paths(&graph);
+
+        // 各detection eventはちょうど1つのpathの端点になる
+        let mut endpoints = BTreeMap::new();
+        for path in paths.iter() {
+            for coord in [path[0], path[path.len() - 1]] {
+                if !graph.is_boundary(&coord).unwrap() {
+                    *endpoints.entry(coord).or_insert(0) += 1;
+                }
+            }
+        }
+        assert_eq!(endpoints.keys().copied().collect::<Vec<_>>(), defects);
+        assert!(endpoints.values().all(|&count| count == 1));
+
+        let weight: f32 = paths
+            .iter()
+            .flat_map(|path| path.windows(2))
+            .map(|edge| graph.edge_weight(&(edge[0], edge[1])).unwrap())
+            .sum();
+        let expected = brute_force_weight(&graph, &defects);
+        assert!(
+            (weight - expected).abs() < 1e-2,
+            "{:?}: {} != {}",
+            defects,
+            weight,
+            expected
+        );
+    }
+}
+
+#[test]
+fn decoder_matches_exact_mwpm_on_surface_code() {
+    let error_num = common::decoding::logical_errors_against_exact_mwpm(
+        Box::new(SparseBlossom::new()),
+        5,
+        0.02,
+        300,
+    );
+    assert!(error_num[0] <= 15, "{:?}", error_num);
+    assert!(error_num[0].abs_diff(error_num[1]) <= 3, "{:?}", error_num);
+}
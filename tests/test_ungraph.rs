@@ -3,8 +3,6 @@ use clifford::qubit_graph::ungraph::UnGraph;
 use petgraph::algo::matching;
 use petgraph::graphmap::GraphMap;
 use petgraph::graphmap::UnGraphMap;
-use std::cell::Cell;
-use std::rc::Rc;
 
 #[test]
 fn iterate_ungraph() {
@@ -12,9 +10,9 @@ fn iterate_ungraph() {
     let round = 2;
     let seed = 0;
     let mut g = UnGraph::from_edges(&edges, round, seed);
-    g.set_classical_register((1, 1, 0), Rc::new(Cell::new(0)));
-    g.set_classical_register((1, 1, 1), Rc::new(Cell::new(0)));
-    g.set_classical_register((2, 2, 0), Rc::new(Cell::new(0)));
+    g.set_classical_register((1, 1, 0), 0);
+    g.set_classical_register((1, 1, 1), 0);
+    g.set_classical_register((2, 2, 0), 0);
 
     for i in g.iter_classical_register() {
         println!("{:?}, {:?}", i.0, i.1);
@@ -36,7 +34,7 @@ fn test_xor() {
     let mut g = UnGraph::from_edges(&edges, round, seed);
 
     for i in 0..6 {
-        g.set_classical_register((1, 1, i), Rc::new(Cell::new(1)));
+        g.set_classical_register((1, 1, i), 1);
     }
 
     g.show_all_defect();
@@ -60,3 +58,19 @@ fn test_matching() {
         println!("{:?}", edge);
     }
 }
+
+#[test]
+fn log_likelihood_weight() {
+    let w = UnGraph::weight_from_error_rate(0.01);
+    assert!((w - 99f32.ln()).abs() < 1e-5);
+    // 確率の小さいエラーほど重い
+    assert!(UnGraph::weight_from_error_rate(0.001) > w);
+    // p = 0でも有限, p >= 1/2では0
+    assert!(UnGraph::weight_from_error_rate(0.0).is_finite());
+    assert_eq!(UnGraph::weight_from_error_rate(0.7), 0.0);
+
+    // 並列なエラーはどちらか一方だけが起こる確率にまとめる
+    let p = UnGraph::merge_error_rate(0.1, 0.2);
+    assert!((p - 0.26).abs() < 1e-6);
+    assert_eq!(UnGraph::merge_error_rate(0.1, 0.0), 0.1);
+}
@@ -0,0 +1,47 @@
+use rand::{rngs::SmallRng, SeedableRng};
+use std::collections::HashMap;
+
+use clifford::decoder::{union_find, union_find::UnionFind};
+
+mod common;
+
+#[test]
+fn correction_clears_all_defects() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    for _ in 0..200 {
+        let graph = common::decoding::random_lattice(5, 4, 3, 0.2, &mut rng);
+        let edges = union_find::correction_edges(&graph);
+
+        // correctionのedgeが各nodeに接する数の偶奇はdefectと一致する
+        let mut parity = HashMap::new();
+        for &(u, v) in edges.iter() {
+            assert!(graph.edge_weight(&(u, v)).is_some());
+            for node in [u, v] {
+                *parity.entry(node).or_insert(0) ^= 1;
+            }
+        }
+        for (coord, &defect) in graph.iter_classical_register() {
+            if !graph.is_boundary(coord).unwrap() {
+                assert_eq!(
+                    parity.get(coord).copied().unwrap_or(0),
+                    defect,
+                    "{:?}",
+                    coord
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn decoder_is_comparable_to_mwpm_on_surface_code() {
+    let error_num = common::decoding::logical_errors_against_exact_mwpm(
+        Box::new(UnionFind::new()),
+        5,
+        0.02,
+        300,
+    );
+    // union-findはMWPMより少し悪いが, 同程度の精度になる
+    assert!(error_num[0] <= 15, "{:?}", error_num);
+    assert!(error_num[0] <= error_num[1] + 5, "{:?}", error_num);
+}